@@ -12,6 +12,31 @@ fn main() -> GameResult {
         .init()
         .unwrap();
 
+    // Train AI players headlessly instead of playing a game, if that's what was asked for
+    match cli::get_command() {
+        Some(cli::Command::Train(opts)) => {
+            ai::trainer::run(&opts)?;
+            return Ok(());
+        }
+        Some(cli::Command::Imitate(opts)) => {
+            ai::imitation::run(&opts)?;
+            return Ok(());
+        }
+        Some(cli::Command::Dqn(opts)) => {
+            ai::dqn::run(&opts)?;
+            return Ok(());
+        }
+        Some(cli::Command::Evaluate(opts)) => {
+            ai::gauntlet::run(&opts)?;
+            return Ok(());
+        }
+        Some(cli::Command::Tournament(opts)) => {
+            ai::tournament::run(&opts)?;
+            return Ok(());
+        }
+        None => {}
+    }
+
     // What kind of game are we playing? 2 player, 1 player, etc.?
     let game_mode = cli::get_game_mode().unwrap_or(PLAYER_VS_PLAYER);
 
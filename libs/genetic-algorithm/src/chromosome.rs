@@ -1,11 +1,25 @@
+use rand::seq::SliceRandom;
+use rand::{Rng, RngCore};
+use serde::{Deserialize, Serialize};
+
 use std::{iter::FromIterator, ops::Index};
 
-#[derive(Clone, Debug, PartialEq)]
-pub struct Chromosome {
-    genes: Vec<f32>,
+/// A list of genes of any type G (f32 weights by default)
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Chromosome<G = f32> {
+    genes: Vec<G>,
 }
 
-impl Chromosome {
+/// A chromosome of on/off genes
+pub type BitString = Chromosome<bool>;
+
+/// A chromosome of whole number genes, e.g. for discrete settings
+pub type IntegerVector = Chromosome<i32>;
+
+/// A chromosome containing each of the numbers 0..len exactly once, e.g. for orderings
+pub type Permutation = Chromosome<usize>;
+
+impl<G> Chromosome<G> {
     pub fn len(&self) -> usize {
         self.genes.len()
     }
@@ -14,22 +28,59 @@ impl Chromosome {
         self.genes.is_empty()
     }
 
-    pub fn iter(&self) -> impl Iterator<Item = &f32> {
+    pub fn iter(&self) -> impl Iterator<Item = &G> {
         self.genes.iter()
     }
 
-    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut f32> {
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut G> {
         self.genes.iter_mut()
     }
 
-    pub fn as_slice(&self) -> &[f32] {
+    pub fn as_slice(&self) -> &[G] {
         self.genes.as_slice()
     }
+
+    /// Swap two genes around
+    pub fn swap(&mut self, a: usize, b: usize) {
+        self.genes.swap(a, b);
+    }
+}
+
+impl BitString {
+    /// Create a new BitString where each bit has an even chance of being on or off
+    pub fn random_bits(prng: &mut dyn RngCore, len: usize) -> Self {
+        (0..len).map(|_| prng.gen_bool(0.5)).collect()
+    }
+}
+
+impl IntegerVector {
+    /// Create a new IntegerVector with each gene picked at random from min..=max
+    pub fn random_integers(prng: &mut dyn RngCore, len: usize, min: i32, max: i32) -> Self {
+        (0..len).map(|_| prng.gen_range(min..=max)).collect()
+    }
+}
+
+impl Permutation {
+    /// Create a new Permutation of 0..len in a random order
+    pub fn random_permutation(prng: &mut dyn RngCore, len: usize) -> Self {
+        let mut genes: Vec<usize> = (0..len).collect();
+        genes.shuffle(prng);
+
+        Self { genes }
+    }
+
+    /// Check that every number in 0..len appears exactly once
+    pub fn is_permutation(&self) -> bool {
+        let mut seen = vec![false; self.len()];
+
+        self.iter()
+            .all(|gene| *gene < seen.len() && !std::mem::replace(&mut seen[*gene], true))
+    }
 }
 
 // The Index trait allows indexing into a custom type using the [i] syntax
-impl Index<usize> for Chromosome {
-    type Output = f32;
+impl<G> Index<usize> for Chromosome<G> {
+    type Output = G;
 
     fn index(&self, index: usize) -> &Self::Output {
         &self.genes[index]
@@ -37,8 +88,8 @@ impl Index<usize> for Chromosome {
 }
 
 // The FromIterator trait allows using .collect() into your a custom type
-impl FromIterator<f32> for Chromosome {
-    fn from_iter<T: IntoIterator<Item = f32>>(iter: T) -> Self {
+impl<G> FromIterator<G> for Chromosome<G> {
+    fn from_iter<T: IntoIterator<Item = G>>(iter: T) -> Self {
         Self {
             genes: iter.into_iter().collect(),
         }
@@ -46,9 +97,9 @@ impl FromIterator<f32> for Chromosome {
 }
 
 // The IntoIterator trait turns a custom type into an iterator
-impl IntoIterator for Chromosome {
-    type Item = f32;
-    type IntoIter = std::vec::IntoIter<f32>;
+impl<G> IntoIterator for Chromosome<G> {
+    type Item = G;
+    type IntoIter = std::vec::IntoIter<G>;
 
     fn into_iter(self) -> Self::IntoIter {
         self.genes.into_iter()
@@ -58,6 +109,8 @@ impl IntoIterator for Chromosome {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
 
     #[test]
     fn length_test() {
@@ -130,4 +183,27 @@ mod tests {
         approx::assert_relative_eq!(chromosome[1], genes[1]);
         approx::assert_relative_eq!(chromosome[2], genes[2]);
     }
+
+    #[test]
+    fn typed_chromosomes() {
+        // Seed a ChaCha8Rng for a predictable "random" number to use for testing
+        let mut prng = ChaCha8Rng::from_seed(Default::default());
+
+        let bits = BitString::random_bits(&mut prng, 100);
+        assert_eq!(bits.len(), 100);
+        assert!(bits.iter().any(|bit| *bit) && bits.iter().any(|bit| !*bit));
+
+        let integers = IntegerVector::random_integers(&mut prng, 100, -3, 3);
+        assert!(integers.iter().all(|gene| (-3..=3).contains(gene)));
+
+        let permutation = Permutation::random_permutation(&mut prng, 10);
+        assert!(permutation.is_permutation());
+        assert_ne!(
+            permutation.as_slice(),
+            (0..10).collect::<Vec<_>>().as_slice()
+        );
+
+        let not_a_permutation: Permutation = vec![0, 2, 2].into_iter().collect();
+        assert!(!not_a_permutation.is_permutation());
+    }
 }
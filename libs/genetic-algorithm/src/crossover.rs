@@ -2,13 +2,13 @@ use rand::{Rng, RngCore};
 
 use crate::chromosome::Chromosome;
 
-pub trait CrossoverMethod {
+pub trait CrossoverMethod<G = f32> {
     fn crossover(
         &self,
         prng: &mut dyn RngCore,
-        parent_a: &Chromosome,
-        parent_b: &Chromosome,
-    ) -> Chromosome;
+        parent_a: &Chromosome<G>,
+        parent_b: &Chromosome<G>,
+    ) -> Chromosome<G>;
 }
 
 #[derive(Clone, Debug)]
@@ -20,13 +20,13 @@ impl UniformCrossover {
     }
 }
 
-impl CrossoverMethod for UniformCrossover {
+impl<G: Clone> CrossoverMethod<G> for UniformCrossover {
     fn crossover(
         &self,
         prng: &mut dyn RngCore,
-        parent_a: &Chromosome,
-        parent_b: &Chromosome,
-    ) -> Chromosome {
+        parent_a: &Chromosome<G>,
+        parent_b: &Chromosome<G>,
+    ) -> Chromosome<G> {
         assert_eq!(parent_a.len(), parent_b.len());
 
         let mut child = Vec::new();
@@ -34,9 +34,9 @@ impl CrossoverMethod for UniformCrossover {
 
         for gene_idx in 0..gene_count {
             let gene = if prng.gen_bool(0.5) {
-                parent_a[gene_idx]
+                parent_a[gene_idx].clone()
             } else {
-                parent_b[gene_idx]
+                parent_b[gene_idx].clone()
             };
 
             child.push(gene);
@@ -46,6 +46,60 @@ impl CrossoverMethod for UniformCrossover {
     }
 }
 
+/// Order crossover (OX1) for permutations, where every gene has to appear exactly once
+/// The child gets a random slice of parent_a in the same place, and the rest of the genes in the order they appear in parent_b
+#[derive(Clone, Debug, Default)]
+pub struct OrderCrossover;
+
+impl OrderCrossover {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl<G: Clone + PartialEq> CrossoverMethod<G> for OrderCrossover {
+    fn crossover(
+        &self,
+        prng: &mut dyn RngCore,
+        parent_a: &Chromosome<G>,
+        parent_b: &Chromosome<G>,
+    ) -> Chromosome<G> {
+        assert_eq!(parent_a.len(), parent_b.len());
+
+        let gene_count = parent_a.len();
+        if gene_count == 0 {
+            return parent_a.clone();
+        }
+
+        let mut start = prng.gen_range(0..gene_count);
+        let mut end = prng.gen_range(0..gene_count);
+        if start > end {
+            std::mem::swap(&mut start, &mut end);
+        }
+
+        let kept = &parent_a.as_slice()[start..=end];
+
+        // Fill in the gaps around the kept slice with whatever's left, starting just after the slice and wrapping around
+        let mut rest = (0..gene_count)
+            .map(|idx| &parent_b[(end + 1 + idx) % gene_count])
+            .filter(|gene| !kept.contains(gene));
+
+        let mut child: Vec<Option<G>> = vec![None; gene_count];
+        for (idx, gene) in kept.iter().enumerate() {
+            child[start + idx] = Some(gene.clone());
+        }
+        for offset in 0..gene_count - kept.len() {
+            let idx = (end + 1 + offset) % gene_count;
+            child[idx] = rest.next().cloned();
+        }
+
+        child
+            .into_iter()
+            .map(|gene| gene.expect("The parents aren't permutations of each other!"))
+            .collect()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -82,4 +136,26 @@ mod tests {
         assert_eq!(diff_a, 49);
         assert_eq!(diff_b, 51);
     }
+
+    #[test]
+    fn order_crossover() {
+        // Seed a ChaCha8Rng for a predictable "random" number to use for testing
+        let mut prng = ChaCha8Rng::from_seed(Default::default());
+
+        let parent_a: Chromosome<usize> = (0..10).collect();
+        let parent_b: Chromosome<usize> = (0..10).rev().collect();
+
+        for _ in 0..20 {
+            let child = OrderCrossover::new().crossover(&mut prng, &parent_a, &parent_b);
+
+            // Every gene should still appear exactly once
+            let mut genes: Vec<usize> = child.iter().copied().collect();
+            genes.sort_unstable();
+            assert_eq!(genes, (0..10).collect::<Vec<_>>());
+        }
+
+        // Crossing a permutation with itself should give the same permutation back
+        let child = OrderCrossover::new().crossover(&mut prng, &parent_b, &parent_b);
+        assert_eq!(child, parent_b);
+    }
 }
@@ -0,0 +1,383 @@
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+
+use crate::chromosome::Chromosome;
+use crate::individual::Individual;
+use crate::optimizer::Optimizer;
+
+use super::{fittest_first, gaussian};
+
+/// Covariance Matrix Adaptation Evolution Strategy (CMA-ES)
+/// Samples each generation from a multivariate normal distribution, and learns which directions are worth searching in (the covariance matrix) and how far to go (sigma) from where the fittest samples ended up
+/// Works out everything internally in f64, and follows the defaults from Hansen's "The CMA Evolution Strategy: A Tutorial"
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CmaEs {
+    sigma: f64,
+    mean: Vec<f64>,
+    /// The covariance matrix C, stored row by row
+    covariance: Vec<f64>,
+    /// The eigenvectors of C (as columns), and the square roots of its eigenvalues, so that C = B D² Bᵀ
+    basis: Vec<f64>,
+    scales: Vec<f64>,
+    /// Evolution paths for sigma and for C, which remember which way the mean has been moving
+    path_sigma: Vec<f64>,
+    path_c: Vec<f64>,
+    generation: usize,
+}
+
+/// The learning rates and weights CMA-ES uses, which only depend on the number of genes and the population size
+struct Parameters {
+    weights: Vec<f64>,
+    mu_eff: f64,
+    c_sigma: f64,
+    d_sigma: f64,
+    c_c: f64,
+    c_1: f64,
+    c_mu: f64,
+    chi_n: f64,
+}
+
+impl Parameters {
+    fn new(genes: usize, population: usize) -> Self {
+        let n = genes as f64;
+        let mu = (population / 2).max(1);
+
+        // Better ranked samples count for more when working out the new mean
+        let raw: Vec<f64> = (0..mu)
+            .map(|rank| (mu as f64 + 0.5).ln() - (rank as f64 + 1.0).ln())
+            .collect();
+        let total: f64 = raw.iter().sum();
+        let weights: Vec<f64> = raw.iter().map(|weight| weight / total).collect();
+        let mu_eff = 1.0 / weights.iter().map(|weight| weight * weight).sum::<f64>();
+
+        let c_sigma = (mu_eff + 2.0) / (n + mu_eff + 5.0);
+        let d_sigma = 1.0 + 2.0 * (((mu_eff - 1.0) / (n + 1.0)).sqrt() - 1.0).max(0.0) + c_sigma;
+        let c_c = (4.0 + mu_eff / n) / (n + 4.0 + 2.0 * mu_eff / n);
+        let c_1 = 2.0 / ((n + 1.3).powi(2) + mu_eff);
+        let c_mu =
+            (1.0 - c_1).min(2.0 * (mu_eff - 2.0 + 1.0 / mu_eff) / ((n + 2.0).powi(2) + mu_eff));
+
+        // The expected length of a vector drawn from N(0, I)
+        let chi_n = n.sqrt() * (1.0 - 1.0 / (4.0 * n) + 1.0 / (21.0 * n * n));
+
+        Self {
+            weights,
+            mu_eff,
+            c_sigma,
+            d_sigma,
+            c_c,
+            c_1,
+            c_mu,
+            chi_n,
+        }
+    }
+}
+
+impl CmaEs {
+    /// Create a new CmaEs, with a starting step size of sigma
+    /// The mean starts off wherever the fittest members of the first population are
+    pub fn new(sigma: f32) -> Self {
+        assert!(sigma > 0.0);
+
+        Self {
+            sigma: sigma as f64,
+            mean: Vec::new(),
+            covariance: Vec::new(),
+            basis: Vec::new(),
+            scales: Vec::new(),
+            path_sigma: Vec::new(),
+            path_c: Vec::new(),
+            generation: 0,
+        }
+    }
+
+    /// The current step size
+    pub fn sigma(&self) -> f32 {
+        self.sigma as f32
+    }
+
+    /// The centre of the distribution that's being sampled from
+    pub fn mean(&self) -> Vec<f32> {
+        self.mean.iter().map(|gene| *gene as f32).collect()
+    }
+
+    /// Start off with an identity covariance matrix centred on the weighted mean of the fittest samples
+    fn initialise(&mut self, samples: &[Vec<f64>], parameters: &Parameters) {
+        let n = samples[0].len();
+
+        self.mean = weighted_mean(samples, &parameters.weights);
+        self.covariance = identity(n);
+        self.basis = identity(n);
+        self.scales = vec![1.0; n];
+        self.path_sigma = vec![0.0; n];
+        self.path_c = vec![0.0; n];
+    }
+
+    /// Move the mean towards the fittest samples, and update the evolution paths, covariance matrix and step size
+    fn update(&mut self, samples: &[Vec<f64>], parameters: &Parameters) {
+        let n = self.mean.len();
+        let p = parameters;
+
+        // How far each of the fittest samples was from the old mean, in units of sigma
+        let steps: Vec<Vec<f64>> = samples
+            .iter()
+            .map(|sample| {
+                sample
+                    .iter()
+                    .zip(&self.mean)
+                    .map(|(x, mean)| (x - mean) / self.sigma)
+                    .collect()
+            })
+            .collect();
+        let step = weighted_mean(&steps, &p.weights);
+
+        for (mean, step) in self.mean.iter_mut().zip(&step) {
+            *mean += self.sigma * step;
+        }
+
+        // C^-1/2 * step = B D^-1 Bᵀ * step
+        let rotated = multiply_transposed(&self.basis, &step, n);
+        let scaled: Vec<f64> = rotated
+            .iter()
+            .zip(&self.scales)
+            .map(|(value, scale)| value / scale)
+            .collect();
+        let whitened = multiply(&self.basis, &scaled, n);
+
+        let sigma_rate = (p.c_sigma * (2.0 - p.c_sigma) * p.mu_eff).sqrt();
+        for (path, value) in self.path_sigma.iter_mut().zip(&whitened) {
+            *path = (1.0 - p.c_sigma) * *path + sigma_rate * value;
+        }
+
+        let path_length = self.path_sigma.iter().map(|x| x * x).sum::<f64>().sqrt();
+        self.generation += 1;
+
+        // Stall the covariance path if sigma is growing fast, so C doesn't stretch too quickly
+        let expected = (1.0 - (1.0 - p.c_sigma).powi(2 * self.generation as i32)).sqrt();
+        let stalled = path_length / expected >= (1.4 + 2.0 / (n as f64 + 1.0)) * p.chi_n;
+        let h_sigma = if stalled { 0.0 } else { 1.0 };
+
+        let c_rate = (p.c_c * (2.0 - p.c_c) * p.mu_eff).sqrt();
+        for (path, value) in self.path_c.iter_mut().zip(&step) {
+            *path = (1.0 - p.c_c) * *path + h_sigma * c_rate * value;
+        }
+
+        // Rank-one update from the evolution path, plus rank-μ update from the fittest steps
+        let correction = (1.0 - h_sigma) * p.c_c * (2.0 - p.c_c);
+        for i in 0..n {
+            for j in 0..n {
+                let rank_one =
+                    self.path_c[i] * self.path_c[j] + correction * self.covariance[i * n + j];
+                let rank_mu: f64 = steps
+                    .iter()
+                    .zip(&p.weights)
+                    .map(|(step, weight)| weight * step[i] * step[j])
+                    .sum();
+
+                self.covariance[i * n + j] = (1.0 - p.c_1 - p.c_mu) * self.covariance[i * n + j]
+                    + p.c_1 * rank_one
+                    + p.c_mu * rank_mu;
+            }
+        }
+
+        self.sigma *= ((p.c_sigma / p.d_sigma) * (path_length / p.chi_n - 1.0)).exp();
+
+        let (eigenvalues, eigenvectors) = eigen(&self.covariance, n);
+        self.basis = eigenvectors;
+        self.scales = eigenvalues
+            .iter()
+            .map(|value| value.max(1e-20).sqrt())
+            .collect();
+    }
+}
+
+impl<I> Optimizer<I> for CmaEs
+where
+    I: Individual<Gene = f32>,
+{
+    fn evolve(&mut self, prng: &mut dyn RngCore, population: &[I]) -> Vec<I> {
+        assert!(!population.is_empty());
+
+        let n = population[0].chromosome().len();
+        let parameters = Parameters::new(n, population.len());
+
+        let samples: Vec<Vec<f64>> = fittest_first(population)
+            .into_iter()
+            .take(parameters.weights.len())
+            .map(|idx| {
+                population[idx]
+                    .chromosome()
+                    .iter()
+                    .map(|gene| *gene as f64)
+                    .collect()
+            })
+            .collect();
+
+        if self.mean.is_empty() {
+            self.initialise(&samples, &parameters);
+        } else {
+            self.update(&samples, &parameters);
+        }
+
+        // Each child is mean + sigma * B D z, where z is drawn from N(0, I)
+        (0..population.len())
+            .map(|_| {
+                let z: Vec<f64> = self
+                    .scales
+                    .iter()
+                    .map(|scale| scale * gaussian(prng) as f64)
+                    .collect();
+                let y = multiply(&self.basis, &z, n);
+
+                let chromosome: Chromosome = self
+                    .mean
+                    .iter()
+                    .zip(&y)
+                    .map(|(mean, y)| (mean + self.sigma * y) as f32)
+                    .collect();
+
+                I::create(chromosome)
+            })
+            .collect()
+    }
+}
+
+/// Combine vectors using the specified weights (which should add up to 1)
+fn weighted_mean(vectors: &[Vec<f64>], weights: &[f64]) -> Vec<f64> {
+    let mut mean = vec![0.0; vectors[0].len()];
+
+    for (vector, weight) in vectors.iter().zip(weights) {
+        for (mean, value) in mean.iter_mut().zip(vector) {
+            *mean += weight * value;
+        }
+    }
+
+    mean
+}
+
+fn identity(n: usize) -> Vec<f64> {
+    (0..n * n)
+        .map(|idx| if idx / n == idx % n { 1.0 } else { 0.0 })
+        .collect()
+}
+
+/// Multiply an n×n matrix by a vector
+fn multiply(matrix: &[f64], vector: &[f64], n: usize) -> Vec<f64> {
+    (0..n)
+        .map(|row| {
+            matrix[row * n..(row + 1) * n]
+                .iter()
+                .zip(vector)
+                .map(|(a, b)| a * b)
+                .sum()
+        })
+        .collect()
+}
+
+/// Multiply the transpose of an n×n matrix by a vector
+fn multiply_transposed(matrix: &[f64], vector: &[f64], n: usize) -> Vec<f64> {
+    (0..n)
+        .map(|col| (0..n).map(|row| matrix[row * n + col] * vector[row]).sum())
+        .collect()
+}
+
+/// Find the eigenvalues and eigenvectors of a symmetric n×n matrix with the cyclic Jacobi method
+/// The eigenvectors are returned as the columns of a matrix, in the same order as their eigenvalues
+fn eigen(matrix: &[f64], n: usize) -> (Vec<f64>, Vec<f64>) {
+    // Average out any rounding errors that have made the matrix slightly unsymmetrical
+    let mut a: Vec<f64> = (0..n * n)
+        .map(|idx| {
+            let (row, col) = (idx / n, idx % n);
+            (matrix[row * n + col] + matrix[col * n + row]) / 2.0
+        })
+        .collect();
+    let mut vectors = identity(n);
+
+    let scale: f64 = a.iter().map(|x| x * x).sum::<f64>().max(f64::MIN_POSITIVE);
+
+    for _ in 0..100 {
+        let off_diagonal: f64 = (0..n)
+            .flat_map(|row| (row + 1..n).map(move |col| (row, col)))
+            .map(|(row, col)| a[row * n + col].powi(2))
+            .sum();
+
+        if off_diagonal <= 1e-24 * scale {
+            break;
+        }
+
+        for p in 0..n {
+            for q in p + 1..n {
+                let apq = a[p * n + q];
+                if apq == 0.0 {
+                    continue;
+                }
+
+                // Rotate rows and columns p and q so that a[p][q] becomes zero
+                let theta = (a[q * n + q] - a[p * n + p]) / (2.0 * apq);
+                let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+                let c = 1.0 / (t * t + 1.0).sqrt();
+                let s = t * c;
+
+                for k in 0..n {
+                    let (akp, akq) = (a[k * n + p], a[k * n + q]);
+                    a[k * n + p] = c * akp - s * akq;
+                    a[k * n + q] = s * akp + c * akq;
+                }
+
+                for k in 0..n {
+                    let (apk, aqk) = (a[p * n + k], a[q * n + k]);
+                    a[p * n + k] = c * apk - s * aqk;
+                    a[q * n + k] = s * apk + c * aqk;
+                }
+
+                for k in 0..n {
+                    let (vkp, vkq) = (vectors[k * n + p], vectors[k * n + q]);
+                    vectors[k * n + p] = c * vkp - s * vkq;
+                    vectors[k * n + q] = s * vkp + c * vkq;
+                }
+            }
+        }
+    }
+
+    let values = (0..n).map(|idx| a[idx * n + idx]).collect();
+
+    (values, vectors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::optimize;
+    use super::*;
+
+    #[test]
+    fn eigen_decomposition() {
+        let matrix = [4.0, 1.0, 2.0, 1.0, 3.0, 0.5, 2.0, 0.5, 5.0];
+        let (values, vectors) = eigen(&matrix, 3);
+
+        // A v = λ v for every eigenvector v
+        for (idx, value) in values.iter().enumerate() {
+            let vector: Vec<f64> = (0..3).map(|row| vectors[row * 3 + idx]).collect();
+            let product = multiply(&matrix, &vector, 3);
+
+            for (a, b) in product.iter().zip(&vector) {
+                approx::assert_abs_diff_eq!(*a, value * b, epsilon = 1e-9);
+            }
+        }
+
+        // The eigenvalues of a matrix add up to its trace
+        approx::assert_abs_diff_eq!(values.iter().sum::<f64>(), 12.0, epsilon = 1e-9);
+    }
+
+    #[test]
+    fn cma_es() {
+        let mut cma = CmaEs::new(0.5);
+        let (first, last) = optimize(&mut cma, 60);
+
+        assert!(last > first);
+        assert!(last > 0.99);
+
+        // The mean should have homed in on the optimum, and the step size should have shrunk as it got closer
+        assert!(cma.mean().iter().all(|gene| (gene - 1.0).abs() < 0.05));
+        assert!(cma.sigma() < 0.5);
+    }
+}
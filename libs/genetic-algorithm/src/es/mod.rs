@@ -0,0 +1,240 @@
+// Evolution strategies
+// Rather than breeding chromosomes together, these sample new f32 chromosomes from a distribution around the best ones found so far, and adapt how far they search as they go
+
+use rand::{Rng, RngCore};
+use serde::{Deserialize, Serialize};
+
+use crate::chromosome::Chromosome;
+use crate::individual::Individual;
+use crate::optimizer::Optimizer;
+
+mod cma;
+mod nes;
+
+pub use cma::CmaEs;
+pub use nes::Nes;
+
+/// Whether the parents of one generation get to compete with their children to be the parents of the next
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Replacement {
+    /// (μ,λ): the parents are always picked from the children, so the strategy can wander away from a lucky score
+    Comma,
+    /// (μ+λ): the parents are picked from the children and the old parents, so the best score never gets worse
+    Plus,
+}
+
+/// One of the parents an EvolutionStrategy breeds from, along with the step size it was mutated with
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Parent {
+    chromosome: Chromosome,
+    fitness: f32,
+    sigma: f32,
+}
+
+/// A (μ,λ) or (μ+λ) evolution strategy with self-adaptive step sizes
+/// Each child is a copy of a random parent with Gaussian noise added to every gene, and carries its own step size which is mutated along with it, so step sizes that work well get passed on
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EvolutionStrategy {
+    parents: usize,
+    replacement: Replacement,
+    sigma: f32,
+    survivors: Vec<Parent>,
+    /// The step size of each child handed out last generation, in the same order
+    offspring: Vec<f32>,
+}
+
+impl EvolutionStrategy {
+    /// Create a new EvolutionStrategy that breeds from the specified number of parents (μ), starting off with a step size of sigma
+    /// The number of children (λ) is however big the population passed to evolve is
+    pub fn new(parents: usize, replacement: Replacement, sigma: f32) -> Self {
+        assert!(parents > 0);
+        assert!(sigma > 0.0);
+
+        Self {
+            parents,
+            replacement,
+            sigma,
+            survivors: Vec::new(),
+            offspring: Vec::new(),
+        }
+    }
+
+    /// The average step size the last generation of children was mutated with (or the starting step size, before there's been one)
+    pub fn sigma(&self) -> f32 {
+        if self.offspring.is_empty() {
+            self.sigma
+        } else {
+            self.offspring.iter().sum::<f32>() / self.offspring.len() as f32
+        }
+    }
+}
+
+impl<I> Optimizer<I> for EvolutionStrategy
+where
+    I: Individual<Gene = f32>,
+{
+    fn evolve(&mut self, prng: &mut dyn RngCore, population: &[I]) -> Vec<I> {
+        assert!(!population.is_empty());
+
+        // The first population didn't come from this strategy, so it all gets the starting step size
+        let sigmas = if self.offspring.len() == population.len() {
+            self.offspring.clone()
+        } else {
+            vec![self.sigma; population.len()]
+        };
+
+        let mut candidates: Vec<Parent> = population
+            .iter()
+            .zip(sigmas)
+            .map(|(individual, sigma)| Parent {
+                chromosome: individual.chromosome().clone(),
+                fitness: individual.fitness(),
+                sigma,
+            })
+            .collect();
+
+        if self.replacement == Replacement::Plus {
+            candidates.append(&mut self.survivors);
+        }
+
+        candidates.sort_by(|a, b| {
+            b.fitness
+                .partial_cmp(&a.fitness)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        candidates.truncate(self.parents);
+        self.survivors = candidates;
+
+        // The usual learning rate for a single self-adaptive step size
+        let tau = 1.0 / (population[0].chromosome().len() as f32).sqrt();
+
+        let (children, sigmas): (Vec<I>, Vec<f32>) = (0..population.len())
+            .map(|_| {
+                let parent = &self.survivors[prng.gen_range(0..self.survivors.len())];
+                let sigma = parent.sigma * (tau * gaussian(prng)).exp();
+
+                let chromosome = parent
+                    .chromosome
+                    .iter()
+                    .map(|gene| gene + sigma * gaussian(prng))
+                    .collect();
+
+                (I::create(chromosome), sigma)
+            })
+            .unzip();
+
+        self.offspring = sigmas;
+
+        children
+    }
+}
+
+/// Sample from the standard normal distribution, using the Box-Muller transform
+pub(crate) fn gaussian(prng: &mut dyn RngCore) -> f32 {
+    let u1: f32 = prng.gen_range(f32::EPSILON..1.0);
+    let u2: f32 = prng.gen();
+
+    (-2.0 * u1.ln()).sqrt() * (2.0 * std::f32::consts::PI * u2).cos()
+}
+
+/// The indexes of a population's individuals, from the most fit to the least fit
+pub(crate) fn fittest_first<I: Individual>(population: &[I]) -> Vec<usize> {
+    let mut ranked: Vec<usize> = (0..population.len()).collect();
+    ranked.sort_by(|a, b| {
+        population[*b]
+            .fitness()
+            .partial_cmp(&population[*a].fitness())
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    ranked
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    /// Scores chromosomes on how close every gene is to 1.0, with a best possible fitness of 1.0
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct Sphere(Chromosome);
+
+    impl Individual for Sphere {
+        type Gene = f32;
+
+        fn create(chromosome: Chromosome) -> Self {
+            Self(chromosome)
+        }
+
+        fn chromosome(&self) -> &Chromosome {
+            &self.0
+        }
+
+        fn fitness(&self) -> f32 {
+            let distance: f32 = self.0.iter().map(|gene| (gene - 1.0).powi(2)).sum();
+            1.0 / (1.0 + distance)
+        }
+    }
+
+    /// Run an Optimizer on the Sphere problem, and return the best fitness of the first and last generations
+    pub fn optimize(optimizer: &mut dyn Optimizer<Sphere>, generations: usize) -> (f32, f32) {
+        // Seed a ChaCha8Rng for a predictable "random" number to use for testing
+        let mut prng = ChaCha8Rng::seed_from_u64(42);
+
+        let mut population: Vec<Sphere> = (0..12)
+            .map(|_| Sphere((0..5).map(|_| prng.gen_range(-1.0..1.0)).collect()))
+            .collect();
+
+        let best = |population: &[Sphere]| {
+            population
+                .iter()
+                .map(Individual::fitness)
+                .fold(f32::NEG_INFINITY, f32::max)
+        };
+        let first = best(&population);
+
+        for _ in 0..generations {
+            population = optimizer.evolve(&mut prng, &population);
+            assert_eq!(population.len(), 12);
+        }
+
+        (first, best(&population))
+    }
+
+    #[test]
+    fn standard_normal() {
+        let mut prng = ChaCha8Rng::from_seed(Default::default());
+
+        let samples: Vec<f32> = (0..10_000).map(|_| gaussian(&mut prng)).collect();
+        let mean = samples.iter().sum::<f32>() / samples.len() as f32;
+        let variance =
+            samples.iter().map(|x| (x - mean).powi(2)).sum::<f32>() / samples.len() as f32;
+
+        approx::assert_abs_diff_eq!(mean, 0.0, epsilon = 0.05);
+        approx::assert_abs_diff_eq!(variance, 1.0, epsilon = 0.05);
+    }
+
+    #[test]
+    fn comma_strategy() {
+        let mut es = EvolutionStrategy::new(3, Replacement::Comma, 0.3);
+        let (first, last) = optimize(&mut es, 50);
+
+        assert!(last > first);
+        assert!(last > 0.9);
+    }
+
+    #[test]
+    fn plus_strategy() {
+        let mut es = EvolutionStrategy::new(3, Replacement::Plus, 0.3);
+        let (first, last) = optimize(&mut es, 50);
+
+        // The parents can only ever get better, so at least one of the last children should be nearly as good
+        assert!(last > first);
+        assert!(es
+            .survivors
+            .windows(2)
+            .all(|pair| pair[0].fitness >= pair[1].fitness));
+        assert!(es.survivors[0].fitness > 0.9);
+    }
+}
@@ -0,0 +1,125 @@
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+
+use crate::chromosome::Chromosome;
+use crate::individual::Individual;
+use crate::optimizer::Optimizer;
+
+use super::{fittest_first, gaussian};
+
+/// OpenAI-style natural evolution strategy (Salimans et al, "Evolution Strategies as a Scalable Alternative to Reinforcement Learning")
+/// Estimates which way the fitness goes up by trying random nudges to the mean in pairs of opposite directions, then takes a gradient ascent step that way
+/// Only the rank of each fitness is used, so a few very lucky (or unlucky) scores can't throw the estimate off
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Nes {
+    sigma: f32,
+    learning_rate: f32,
+    mean: Vec<f32>,
+}
+
+impl Nes {
+    /// Create a new Nes that nudges the mean by sigma to estimate the gradient, and moves it by the learning rate times the gradient
+    /// The mean starts off as the fittest member of the first population
+    pub fn new(sigma: f32, learning_rate: f32) -> Self {
+        assert!(sigma > 0.0);
+        assert!(learning_rate > 0.0);
+
+        Self {
+            sigma,
+            learning_rate,
+            mean: Vec::new(),
+        }
+    }
+
+    /// The current centre of the search
+    pub fn mean(&self) -> &[f32] {
+        &self.mean
+    }
+
+    /// How far the children are nudged from the mean
+    pub fn sigma(&self) -> f32 {
+        self.sigma
+    }
+}
+
+impl<I> Optimizer<I> for Nes
+where
+    I: Individual<Gene = f32>,
+{
+    fn evolve(&mut self, prng: &mut dyn RngCore, population: &[I]) -> Vec<I> {
+        assert!(!population.is_empty());
+
+        let ranked = fittest_first(population);
+
+        if self.mean.is_empty() {
+            self.mean = population[ranked[0]].chromosome().iter().copied().collect();
+        } else {
+            // Centred ranks: the fittest gets 0.5, the least fit gets -0.5, and everyone else is spread evenly in between
+            let count = population.len();
+            let mut utilities = vec![0.0; count];
+            for (rank, idx) in ranked.iter().enumerate() {
+                utilities[*idx] = if count > 1 {
+                    0.5 - rank as f32 / (count - 1) as f32
+                } else {
+                    0.0
+                };
+            }
+
+            // Each child was mean + sigma * noise, so the noise can be worked back out from the child
+            let mut gradient = vec![0.0; self.mean.len()];
+            for (individual, utility) in population.iter().zip(&utilities) {
+                for ((gradient, gene), mean) in gradient
+                    .iter_mut()
+                    .zip(individual.chromosome().iter())
+                    .zip(&self.mean)
+                {
+                    *gradient += utility * (gene - mean) / self.sigma;
+                }
+            }
+
+            let step = self.learning_rate / (count as f32 * self.sigma);
+            for (mean, gradient) in self.mean.iter_mut().zip(&gradient) {
+                *mean += step * gradient;
+            }
+        }
+
+        // Antithetic sampling: every noise vector is tried in both directions, which cancels out a lot of the noise in the gradient estimate
+        let mut children = Vec::with_capacity(population.len());
+        while children.len() < population.len() {
+            let noise: Vec<f32> = self.mean.iter().map(|_| gaussian(prng)).collect();
+
+            for direction in [1.0, -1.0].iter() {
+                if children.len() == population.len() {
+                    break;
+                }
+
+                let chromosome: Chromosome = self
+                    .mean
+                    .iter()
+                    .zip(&noise)
+                    .map(|(mean, noise)| mean + direction * self.sigma * noise)
+                    .collect();
+
+                children.push(I::create(chromosome));
+            }
+        }
+
+        children
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::optimize;
+    use super::*;
+
+    #[test]
+    fn natural_evolution_strategy() {
+        let mut nes = Nes::new(0.2, 0.1);
+        let (first, last) = optimize(&mut nes, 200);
+
+        // The children are always sigma away from the mean, so it's the mean that should have found the optimum
+        assert!(last > first);
+        assert!(nes.mean().iter().all(|gene| (gene - 1.0).abs() < 0.1));
+    }
+}
@@ -0,0 +1,286 @@
+use serde::{Deserialize, Serialize};
+
+use std::collections::HashMap;
+use std::marker::PhantomData;
+
+use crate::chromosome::Chromosome;
+use crate::individual::Individual;
+
+/// The result of scoring an individual
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Evaluation {
+    pub fitness: f32,
+    /// Every objective the individual was scored on, where bigger is always better
+    pub objectives: Vec<f32>,
+}
+
+impl Evaluation {
+    /// Create a new single objective Evaluation, where the only objective is the fitness
+    pub fn new(fitness: f32) -> Self {
+        Self {
+            fitness,
+            objectives: vec![fitness],
+        }
+    }
+
+    /// Create a new Evaluation with a separate list of objectives
+    pub fn with_objectives(fitness: f32, objectives: Vec<f32>) -> Self {
+        Self {
+            fitness,
+            objectives,
+        }
+    }
+}
+
+/// An Individual that gets scored some time after it's created (e.g. by playing a game), rather than straight from its chromosome
+pub trait Evaluable: Individual {
+    /// Record the score, after which the individual counts as evaluated
+    fn set_evaluation(&mut self, evaluation: Evaluation);
+}
+
+/// Turns a gene into something that can be hashed, so identical chromosomes can be spotted
+/// Floats can't be hashed directly, so they're compared bit for bit instead
+pub trait GeneKey {
+    fn key(&self) -> u64;
+}
+
+impl GeneKey for f32 {
+    fn key(&self) -> u64 {
+        self.to_bits() as u64
+    }
+}
+
+impl GeneKey for f64 {
+    fn key(&self) -> u64 {
+        self.to_bits()
+    }
+}
+
+impl GeneKey for bool {
+    fn key(&self) -> u64 {
+        *self as u64
+    }
+}
+
+impl GeneKey for i32 {
+    fn key(&self) -> u64 {
+        *self as u32 as u64
+    }
+}
+
+impl GeneKey for usize {
+    fn key(&self) -> u64 {
+        *self as u64
+    }
+}
+
+fn chromosome_key<G: GeneKey>(chromosome: &Chromosome<G>) -> Vec<u64> {
+    chromosome.iter().map(GeneKey::key).collect()
+}
+
+/// Scores a population, reusing the last generation's scores for any chromosome it has already seen
+/// Elites and children that came through crossover and mutation unchanged are identical to a chromosome from the previous generation, so there's no need to score them again
+/// Only the most recent generation is remembered, so the cache never grows bigger than the population
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct MemoizingEvaluator<G> {
+    #[serde(with = "cache")]
+    cache: HashMap<Vec<u64>, Evaluation>,
+    hits: usize,
+    misses: usize,
+    gene: PhantomData<G>,
+}
+
+/// JSON only allows strings as map keys, so the cache gets saved as a list of (chromosome key, evaluation) pairs instead
+mod cache {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::collections::HashMap;
+
+    use super::Evaluation;
+
+    pub fn serialize<S: Serializer>(
+        cache: &HashMap<Vec<u64>, Evaluation>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        // Sorted, so the same cache always saves the same way
+        let mut entries: Vec<_> = cache.iter().collect();
+        entries.sort_by(|a, b| a.0.cmp(b.0));
+
+        serializer.collect_seq(entries)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<HashMap<Vec<u64>, Evaluation>, D::Error> {
+        let entries: Vec<(Vec<u64>, Evaluation)> = Vec::deserialize(deserializer)?;
+
+        Ok(entries.into_iter().collect())
+    }
+}
+
+impl<G: GeneKey> Default for MemoizingEvaluator<G> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<G: GeneKey> MemoizingEvaluator<G> {
+    pub fn new() -> Self {
+        Self {
+            cache: HashMap::new(),
+            hits: 0,
+            misses: 0,
+            gene: PhantomData,
+        }
+    }
+
+    /// Score every individual in a population genericised over an Evaluable I that hasn't already been evaluated
+    /// The score function gets each individual's index in the population along with the individual, and is only called for chromosomes that aren't in the cache
+    /// Returns how many times the score function was called
+    pub fn evaluate<I, F>(&mut self, population: &mut [I], mut score: F) -> usize
+    where
+        I: Evaluable<Gene = G>,
+        F: FnMut(usize, &I) -> Evaluation,
+    {
+        let mut next = HashMap::with_capacity(population.len());
+        let mut scored = 0;
+
+        for (idx, individual) in population.iter_mut().enumerate() {
+            let key = chromosome_key(individual.chromosome());
+
+            if individual.is_evaluated() {
+                let evaluation =
+                    Evaluation::with_objectives(individual.fitness(), individual.objectives());
+                next.insert(key, evaluation);
+                continue;
+            }
+
+            // Duplicates within the same generation count as hits too
+            let evaluation = match self.cache.get(&key).or_else(|| next.get(&key)) {
+                Some(evaluation) => {
+                    self.hits += 1;
+                    evaluation.clone()
+                }
+                None => {
+                    self.misses += 1;
+                    scored += 1;
+                    score(idx, individual)
+                }
+            };
+
+            individual.set_evaluation(evaluation.clone());
+            next.insert(key, evaluation);
+        }
+
+        self.cache = next;
+
+        scored
+    }
+
+    /// How many individuals have had their score reused so far
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+
+    /// How many individuals have had to be scored so far
+    pub fn misses(&self) -> usize {
+        self.misses
+    }
+
+    /// Forget every score, e.g. because the way individuals are scored has changed
+    pub fn clear(&mut self) {
+        self.cache.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Scored {
+        chromosome: Chromosome<i32>,
+        evaluation: Option<Evaluation>,
+    }
+
+    impl Individual for Scored {
+        type Gene = i32;
+
+        fn create(chromosome: Chromosome<i32>) -> Self {
+            Self {
+                chromosome,
+                evaluation: None,
+            }
+        }
+
+        fn chromosome(&self) -> &Chromosome<i32> {
+            &self.chromosome
+        }
+
+        fn fitness(&self) -> f32 {
+            self.evaluation
+                .as_ref()
+                .map(|evaluation| evaluation.fitness)
+                .unwrap_or(0.0)
+        }
+
+        fn objectives(&self) -> Vec<f32> {
+            self.evaluation
+                .as_ref()
+                .map(|evaluation| evaluation.objectives.clone())
+                .unwrap_or_default()
+        }
+
+        fn is_evaluated(&self) -> bool {
+            self.evaluation.is_some()
+        }
+    }
+
+    impl Evaluable for Scored {
+        fn set_evaluation(&mut self, evaluation: Evaluation) {
+            self.evaluation = Some(evaluation);
+        }
+    }
+
+    fn scored(genes: &[i32]) -> Scored {
+        Scored::create(genes.iter().cloned().collect())
+    }
+
+    fn sum(_: usize, individual: &Scored) -> Evaluation {
+        let fitness = individual.chromosome().iter().sum::<i32>() as f32;
+        Evaluation::with_objectives(fitness, vec![fitness, -fitness])
+    }
+
+    #[test]
+    fn memoized_evaluation() {
+        let mut evaluator = MemoizingEvaluator::new();
+
+        // The duplicate in the first generation only gets scored once
+        let mut population = vec![scored(&[1, 2]), scored(&[3, 4]), scored(&[1, 2])];
+        assert_eq!(evaluator.evaluate(&mut population, sum), 2);
+        assert!(population.iter().all(Individual::is_evaluated));
+        assert_eq!(population[2].fitness(), 3.0);
+        assert_eq!(population[2].objectives(), vec![3.0, -3.0]);
+
+        // An elite carried over from the last generation, and a child identical to one of its parents, don't get scored again
+        let elite = population[1].clone();
+        let mut population = vec![elite, scored(&[1, 2]), scored(&[5, 6])];
+        assert_eq!(evaluator.evaluate(&mut population, sum), 1);
+        assert_eq!(population[1].fitness(), 3.0);
+        assert_eq!(population[2].fitness(), 11.0);
+        assert_eq!(evaluator.hits(), 2);
+        assert_eq!(evaluator.misses(), 3);
+
+        // Only the last generation is remembered, so [1, 2] has been forgotten by now
+        let mut population = vec![scored(&[5, 6]), scored(&[3, 4])];
+        assert_eq!(evaluator.evaluate(&mut population, sum), 0);
+
+        let mut population = vec![scored(&[1, 2])];
+        assert_eq!(evaluator.evaluate(&mut population, sum), 1);
+
+        // Clearing the cache means everything needs scoring again
+        evaluator.clear();
+        let mut population = vec![scored(&[1, 2])];
+        assert_eq!(evaluator.evaluate(&mut population, sum), 1);
+    }
+}
@@ -1,9 +1,24 @@
 use crate::chromosome::Chromosome;
 
 pub trait Individual {
-    fn create(chromosome: Chromosome) -> Self;
-    fn chromosome(&self) -> &Chromosome;
+    /// The type of gene in the individual's Chromosome (e.g. f32 for network weights, bool for a BitString)
+    type Gene;
+
+    fn create(chromosome: Chromosome<Self::Gene>) -> Self;
+    fn chromosome(&self) -> &Chromosome<Self::Gene>;
     fn fitness(&self) -> f32;
+
+    /// Every objective the individual is being scored on, where bigger is always better
+    /// Single objective individuals don't need to implement this, as it defaults to just their fitness
+    fn objectives(&self) -> Vec<f32> {
+        vec![self.fitness()]
+    }
+
+    /// Whether the individual has been scored yet
+    /// Individuals that work their fitness out straight from their chromosome are always evaluated, so this defaults to true
+    fn is_evaluated(&self) -> bool {
+        true
+    }
 }
 
 #[cfg(test)]
@@ -11,6 +26,7 @@ pub trait Individual {
 pub enum TestIndividual {
     WithChromosome { chromosome: Chromosome },
     WithFitness { fitness: f32 },
+    WithObjectives { objectives: Vec<f32> },
 }
 
 #[cfg(test)]
@@ -18,10 +34,18 @@ impl TestIndividual {
     pub fn new(fitness: f32) -> Self {
         Self::WithFitness { fitness }
     }
+
+    pub fn with_objectives(objectives: &[f32]) -> Self {
+        Self::WithObjectives {
+            objectives: objectives.to_vec(),
+        }
+    }
 }
 
 #[cfg(test)]
 impl Individual for TestIndividual {
+    type Gene = f32;
+
     fn create(chromosome: Chromosome) -> Self {
         Self::WithChromosome { chromosome }
     }
@@ -30,6 +54,9 @@ impl Individual for TestIndividual {
         match self {
             Self::WithChromosome { chromosome } => chromosome,
             Self::WithFitness { .. } => panic!("not supported for TestIndividual::WithFitness"),
+            Self::WithObjectives { .. } => {
+                panic!("not supported for TestIndividual::WithObjectives")
+            }
         }
     }
 
@@ -37,6 +64,14 @@ impl Individual for TestIndividual {
         match self {
             Self::WithChromosome { chromosome } => chromosome.iter().sum(),
             Self::WithFitness { fitness } => *fitness,
+            Self::WithObjectives { objectives } => objectives.iter().sum(),
+        }
+    }
+
+    fn objectives(&self) -> Vec<f32> {
+        match self {
+            Self::WithObjectives { objectives } => objectives.clone(),
+            _ => vec![self.fitness()],
         }
     }
 }
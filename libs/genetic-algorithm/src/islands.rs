@@ -0,0 +1,321 @@
+// Island model: the population is split into sub-populations ("islands") that evolve on their own
+// Every so often each island sends copies of its best individuals to another island, so good genes spread without one strategy taking over everywhere
+
+use rand::{Rng, RngCore};
+use serde::{Deserialize, Serialize};
+
+use std::str::FromStr;
+
+use crate::individual::Individual;
+use crate::optimizer::Optimizer;
+
+/// Which island each island sends its migrants to
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum MigrationTopology {
+    /// Each island sends its migrants to the next one along, and the last one sends them back round to the first
+    Ring,
+    /// Each island sends its migrants to another island picked at random
+    Random,
+}
+
+impl FromStr for MigrationTopology {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ring" => Ok(Self::Ring),
+            "random" => Ok(Self::Random),
+            _ => Err(format!(
+                "Unknown migration topology {:?} (expected ring or random)",
+                s
+            )),
+        }
+    }
+}
+
+/// How often individuals move between islands, how many of them go, and where they go to
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Migration {
+    /// Islands exchange migrants every this many generations
+    pub interval: usize,
+    /// How many of each island's best individuals get sent
+    pub migrants: usize,
+    pub topology: MigrationTopology,
+}
+
+impl Default for Migration {
+    fn default() -> Self {
+        Self {
+            interval: 10,
+            migrants: 2,
+            topology: MigrationTopology::Ring,
+        }
+    }
+}
+
+/// A group of islands, each evolving its own sub-population with its own Optimizer (e.g. a GeneticAlgorithm with its own selection, crossover and mutation methods)
+pub struct Archipelago<I> {
+    islands: Vec<Box<dyn Optimizer<I>>>,
+    migration: Migration,
+    generation: usize,
+    until_migration: usize,
+}
+
+impl<I> Archipelago<I>
+where
+    I: Individual + Clone,
+{
+    /// Create a new Archipelago with one island for each of the specified evolvers
+    pub fn new(islands: Vec<Box<dyn Optimizer<I>>>, migration: Migration) -> Self {
+        assert!(!islands.is_empty());
+        assert!(migration.interval > 0);
+
+        Self {
+            islands,
+            until_migration: migration.interval,
+            migration,
+            generation: 0,
+        }
+    }
+
+    /// How many islands there are
+    pub fn len(&self) -> usize {
+        self.islands.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.islands.is_empty()
+    }
+
+    /// How many generations have been evolved so far
+    pub fn generation(&self) -> usize {
+        self.generation
+    }
+
+    /// Carry on from the specified generation (e.g. when resuming a training run), so migrations keep happening on the same generations as before
+    pub fn resume(&mut self, generation: usize) {
+        self.generation = generation;
+        self.until_migration = self.migration.interval - generation % self.migration.interval;
+    }
+
+    /// Breed the next generation on every island, after exchanging migrants if it's time to
+    /// Takes one evaluated sub-population per island, and returns the children in the same order
+    pub fn evolve(&mut self, prng: &mut dyn RngCore, populations: &[Vec<I>]) -> Vec<Vec<I>> {
+        assert_eq!(populations.len(), self.islands.len());
+
+        self.generation += 1;
+        self.until_migration -= 1;
+
+        let mut populations = populations.to_vec();
+        if self.until_migration == 0 {
+            self.until_migration = self.migration.interval;
+            self.migrate(prng, &mut populations);
+        }
+
+        self.islands
+            .iter_mut()
+            .zip(&populations)
+            .map(|(island, population)| island.evolve(prng, population))
+            .collect()
+    }
+
+    /// Copy each island's best individuals over the worst individuals of the island they migrate to
+    /// Everyone picks their migrants before anyone arrives, so a migrant never moves more than one island at a time
+    pub fn migrate(&self, prng: &mut dyn RngCore, populations: &mut [Vec<I>]) {
+        let count = populations.len();
+        if count < 2 {
+            return;
+        }
+
+        let mut arrivals: Vec<Vec<I>> = vec![Vec::new(); count];
+
+        for (from, population) in populations.iter().enumerate() {
+            let to = match self.migration.topology {
+                MigrationTopology::Ring => (from + 1) % count,
+                MigrationTopology::Random => {
+                    // Pick from every island except this one
+                    let to = prng.gen_range(0..count - 1);
+                    if to >= from {
+                        to + 1
+                    } else {
+                        to
+                    }
+                }
+            };
+
+            let migrants = ranked(population)
+                .into_iter()
+                .rev()
+                .take(self.migration.migrants)
+                .map(|idx| population[idx].clone());
+
+            arrivals[to].extend(migrants);
+        }
+
+        for (population, arrivals) in populations.iter_mut().zip(arrivals) {
+            // Worst first, and never replace the whole island
+            let worst = ranked(population);
+            let space = population.len().saturating_sub(1);
+
+            for (idx, migrant) in worst.into_iter().zip(arrivals).take(space) {
+                population[idx] = migrant;
+            }
+        }
+    }
+}
+
+/// Split a population into the specified number of islands, as evenly as possible
+pub fn split<I>(population: Vec<I>, islands: usize) -> Vec<Vec<I>> {
+    assert!(islands > 0);
+
+    let size = population.len() / islands;
+    let remainder = population.len() % islands;
+    let mut population = population.into_iter();
+
+    (0..islands)
+        .map(|island| {
+            let size = if island < remainder { size + 1 } else { size };
+            population.by_ref().take(size).collect()
+        })
+        .collect()
+}
+
+/// The indexes of a population's individuals, from the least fit to the most fit
+fn ranked<I: Individual>(population: &[I]) -> Vec<usize> {
+    let mut ranked: Vec<usize> = (0..population.len()).collect();
+    ranked.sort_by(|a, b| {
+        population[*a]
+            .fitness()
+            .partial_cmp(&population[*b].fitness())
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    ranked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::individual::TestIndividual;
+    use crate::{crossover, mutation, selection, GeneticAlgorithm};
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    fn individual(genes: &[f32]) -> TestIndividual {
+        TestIndividual::create(genes.iter().cloned().collect())
+    }
+
+    fn fitnesses(population: &[TestIndividual]) -> Vec<f32> {
+        population.iter().map(Individual::fitness).collect()
+    }
+
+    fn archipelago(islands: usize, migration: Migration) -> Archipelago<TestIndividual> {
+        let islands = (0..islands)
+            .map(|island| {
+                // Give every island a different mutation strength
+                let ga = GeneticAlgorithm::new(
+                    selection::RouletteWheelSelection::new(),
+                    crossover::UniformCrossover::new(),
+                    mutation::GaussianMutation::new(0.5, 0.1 * (island + 1) as f32),
+                );
+
+                Box::new(ga) as Box<dyn Optimizer<TestIndividual>>
+            })
+            .collect();
+
+        Archipelago::new(islands, migration)
+    }
+
+    #[test]
+    fn ring_migration() {
+        let mut prng = ChaCha8Rng::from_seed(Default::default());
+
+        let migration = Migration {
+            interval: 1,
+            migrants: 1,
+            topology: MigrationTopology::Ring,
+        };
+        let archipelago = archipelago(3, migration);
+
+        let mut populations = vec![
+            vec![individual(&[1.0]), individual(&[2.0])],
+            vec![individual(&[10.0]), individual(&[20.0])],
+            vec![individual(&[100.0]), individual(&[200.0])],
+        ];
+        archipelago.migrate(&mut prng, &mut populations);
+
+        // Each island's best replaces the worst on the next island along, and the last island's best goes back round to the first
+        assert_eq!(fitnesses(&populations[0]), vec![200.0, 2.0]);
+        assert_eq!(fitnesses(&populations[1]), vec![2.0, 20.0]);
+        assert_eq!(fitnesses(&populations[2]), vec![20.0, 200.0]);
+    }
+
+    #[test]
+    fn random_migration() {
+        let mut prng = ChaCha8Rng::from_seed(Default::default());
+
+        let migration = Migration {
+            interval: 1,
+            migrants: 1,
+            topology: MigrationTopology::Random,
+        };
+        let archipelago = archipelago(4, migration);
+
+        for _ in 0..10 {
+            let mut populations: Vec<Vec<TestIndividual>> = (0..4)
+                .map(|island| vec![individual(&[island as f32]); 4])
+                .collect();
+            archipelago.migrate(&mut prng, &mut populations);
+
+            // Nobody migrates to the island they came from, so exactly 4 individuals have moved
+            let moved: usize = populations
+                .iter()
+                .enumerate()
+                .map(|(island, population)| {
+                    population
+                        .iter()
+                        .filter(|individual| individual.fitness() != island as f32)
+                        .count()
+                })
+                .sum();
+            assert_eq!(moved, 4);
+        }
+    }
+
+    #[test]
+    fn island_evolution() {
+        let mut prng = ChaCha8Rng::from_seed(Default::default());
+
+        let migration = Migration {
+            interval: 2,
+            ..Migration::default()
+        };
+        let mut islands = archipelago(3, migration.clone());
+
+        let population: Vec<TestIndividual> = (0..10)
+            .map(|idx| individual(&[idx as f32, 1.0, 2.0]))
+            .collect();
+        let mut populations = split(population, 3);
+        assert_eq!(
+            populations.iter().map(Vec::len).collect::<Vec<_>>(),
+            vec![4, 3, 3]
+        );
+
+        for _ in 0..5 {
+            populations = islands.evolve(&mut prng, &populations);
+        }
+
+        // Islands keep their sizes, whether or not they've exchanged migrants
+        assert_eq!(islands.generation(), 5);
+        assert_eq!(islands.until_migration, 1);
+        assert_eq!(
+            populations.iter().map(Vec::len).collect::<Vec<_>>(),
+            vec![4, 3, 3]
+        );
+
+        // Resuming from the same generation puts the next migration in the same place
+        let mut resumed = archipelago(3, migration);
+        resumed.resume(5);
+        assert_eq!(resumed.until_migration, 1);
+    }
+}
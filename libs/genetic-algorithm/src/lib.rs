@@ -1,4 +1,5 @@
 use crossover::CrossoverMethod;
+use evaluation::{Evaluable, Evaluation, GeneKey, MemoizingEvaluator};
 use individual::Individual;
 use mutation::MutationMethod;
 use rand::RngCore;
@@ -6,44 +7,92 @@ use selection::SelectionMethod;
 
 pub mod chromosome;
 pub mod crossover;
+pub mod es;
+pub mod evaluation;
 pub mod individual;
+pub mod islands;
 pub mod mutation;
+pub mod neat;
+pub mod optimizer;
+pub mod pareto;
 pub mod selection;
-mod statistics;
+pub mod speciation;
+pub mod statistics;
 
 #[derive(Clone, Debug)]
-pub struct GeneticAlgorithm<S, C, G> {
+pub struct GeneticAlgorithm<S, C, M> {
     selection_method: S,
     crossover_method: C,
-    mutation_method: G,
+    mutation_method: M,
+    elitism: usize,
 }
 
-impl<S, C, G> GeneticAlgorithm<S, C, G>
+impl<S, C, M> GeneticAlgorithm<S, C, M>
 where
     S: SelectionMethod,
-    C: CrossoverMethod,
-    G: MutationMethod,
 {
     /// Create a new GeneticAlgorithm genericised over SelectionMethod S
-    pub fn new(selection_method: S, crossover_method: C, mutation_method: G) -> Self {
+    /// The crossover and mutation methods only need to suit the kind of gene being evolved, which is checked when evolving
+    pub fn new(selection_method: S, crossover_method: C, mutation_method: M) -> Self {
         Self {
             selection_method,
             crossover_method,
             mutation_method,
+            elitism: 0,
         }
     }
 
+    /// Carry the specified number of the fittest individuals over to the next generation unchanged
+    pub fn with_elitism(mut self, elitism: usize) -> Self {
+        self.elitism = elitism;
+        self
+    }
+
     pub fn evolve<I>(&self, prng: &mut dyn RngCore, population: &[I]) -> Vec<I>
     where
         I: Individual,
+        I::Gene: Clone,
+        C: CrossoverMethod<I::Gene>,
+        M: MutationMethod<I::Gene>,
     {
         assert!(!population.is_empty());
 
-        (0..population.len())
+        let prepared = self.selection_method.prepare(population);
+
+        // Elitism: the best individuals go through untouched, fittest first
+        // Only the elites need ranking, and each fitness only gets looked up once
+        let mut elites = Vec::new();
+        if self.elitism > 0 {
+            let elitism = self.elitism.min(population.len());
+            let fitnesses: Vec<f32> = population.iter().map(Individual::fitness).collect();
+            let fittest_first = |a: &usize, b: &usize| {
+                fitnesses[*b]
+                    .partial_cmp(&fitnesses[*a])
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            };
+
+            let mut ranked: Vec<usize> = (0..population.len()).collect();
+            ranked.select_nth_unstable_by(elitism - 1, fittest_first);
+            ranked.truncate(elitism);
+            ranked.sort_by(fittest_first);
+
+            elites = ranked
+                .into_iter()
+                .map(|idx| I::create(population[idx].chromosome().clone()))
+                .collect();
+        }
+
+        let children = (elites.len()..population.len())
             .map(|_| {
                 // Selection
-                let parent_a = self.selection_method.select(prng, population).chromosome();
-                let parent_b = self.selection_method.select(prng, population).chromosome();
+                let parent_a = self
+                    .selection_method
+                    .select(prng, population, &prepared)
+                    .chromosome();
+                let parent_b = self
+                    .selection_method
+                    .select(prng, population, &prepared)
+                    .chromosome();
 
                 // Crossover
                 let mut child = self.crossover_method.crossover(prng, parent_a, parent_b);
@@ -54,6 +103,81 @@ where
                 // Create a new individual
                 I::create(child)
             })
+            .collect::<Vec<_>>();
+
+        elites.into_iter().chain(children).collect()
+    }
+
+    /// Score a population, then breed the next generation from it
+    /// The evaluator skips any chromosome it scored last generation (e.g. the elites), so the score function is only called for new ones
+    pub fn evaluate_and_evolve<I, F>(
+        &self,
+        prng: &mut dyn RngCore,
+        population: &mut [I],
+        evaluator: &mut MemoizingEvaluator<I::Gene>,
+        score: F,
+    ) -> Vec<I>
+    where
+        I: Evaluable,
+        I::Gene: GeneKey + Clone,
+        C: CrossoverMethod<I::Gene>,
+        M: MutationMethod<I::Gene>,
+        F: FnMut(usize, &I) -> Evaluation,
+    {
+        evaluator.evaluate(population, score);
+
+        self.evolve(prng, population)
+    }
+
+    /// Evolve a population that has been split into species, so that a single strategy can't take over the whole population
+    /// Each species gets a share of the children based on its shared fitness, and parents are only ever chosen from within the same species
+    /// Species that have stagnated for too long are pruned, and have no children at all
+    pub fn evolve_speciated<I>(
+        &self,
+        prng: &mut dyn RngCore,
+        population: &[I],
+        speciation: &mut speciation::Speciation,
+    ) -> Vec<I>
+    where
+        I: Individual<Gene = f32> + Clone,
+        C: CrossoverMethod,
+        M: MutationMethod,
+    {
+        assert!(!population.is_empty());
+
+        speciation.speciate(population);
+        let offspring = speciation.offspring(population, population.len());
+
+        speciation
+            .species()
+            .iter()
+            .zip(offspring)
+            .flat_map(|(species, offspring)| {
+                let members: Vec<I> = species
+                    .members
+                    .iter()
+                    .map(|idx| population[*idx].clone())
+                    .collect();
+                let prepared = self.selection_method.prepare(&members);
+
+                (0..offspring)
+                    .map(|_| {
+                        let parent_a = self
+                            .selection_method
+                            .select(prng, &members, &prepared)
+                            .chromosome();
+                        let parent_b = self
+                            .selection_method
+                            .select(prng, &members, &prepared)
+                            .chromosome();
+
+                        let mut child = self.crossover_method.crossover(prng, parent_a, parent_b);
+                        self.mutation_method.mutate(prng, &mut child);
+
+                        I::create(child)
+                    })
+                    .collect::<Vec<_>>()
+            })
             .collect()
     }
 }
@@ -109,4 +233,232 @@ mod tests {
 
         assert_eq!(population, expected_population);
     }
+
+    #[test]
+    fn speciated_evolution() {
+        // Seed a ChaCha8Rng for a predictable "random" number to use for testing
+        let mut prng = ChaCha8Rng::seed_from_u64(42);
+
+        let ga = GeneticAlgorithm::new(
+            selection::RouletteWheelSelection::new(),
+            crossover::UniformCrossover::new(),
+            mutation::GaussianMutation::new(0.5, 0.1),
+        );
+
+        let mut speciation = speciation::Speciation::new(speciation::SpeciationSettings {
+            threshold: 1.0,
+            stagnation_limit: 100,
+        });
+
+        // Two clusters that are much too far apart to breed with each other
+        let mut population = vec![
+            individual(&[0.0, 0.0, 0.0]),
+            individual(&[0.5, 0.0, 0.5]),
+            individual(&[10.0, 10.0, 10.0]),
+            individual(&[10.5, 10.0, 10.0]),
+        ];
+
+        for _ in 0..5 {
+            population = ga.evolve_speciated(&mut prng, &population, &mut speciation);
+            assert_eq!(population.len(), 4);
+        }
+
+        // Children only ever come from parents in the same species, so nothing ends up stranded between the clusters
+        assert!(population.iter().all(|individual| {
+            let fitness = individual.fitness();
+            !(5.0..=25.0).contains(&fitness)
+        }));
+    }
+
+    /// OneMax: a BitString whose fitness is just how many of its bits are set
+    #[derive(Clone, Debug, PartialEq)]
+    struct OneMax(chromosome::BitString);
+
+    impl Individual for OneMax {
+        type Gene = bool;
+
+        fn create(chromosome: chromosome::BitString) -> Self {
+            Self(chromosome)
+        }
+
+        fn chromosome(&self) -> &chromosome::BitString {
+            &self.0
+        }
+
+        fn fitness(&self) -> f32 {
+            self.0.iter().filter(|bit| **bit).count() as f32
+        }
+    }
+
+    /// A tour around points on a line, whose fitness is higher the less it doubles back on itself
+    #[derive(Clone, Debug, PartialEq)]
+    struct Tour(chromosome::Permutation);
+
+    impl Individual for Tour {
+        type Gene = usize;
+
+        fn create(chromosome: chromosome::Permutation) -> Self {
+            Self(chromosome)
+        }
+
+        fn chromosome(&self) -> &chromosome::Permutation {
+            &self.0
+        }
+
+        fn fitness(&self) -> f32 {
+            let length: usize = self
+                .0
+                .as_slice()
+                .windows(2)
+                .map(|pair| pair[0].max(pair[1]) - pair[0].min(pair[1]))
+                .sum();
+
+            1.0 / length as f32
+        }
+    }
+
+    #[test]
+    fn bit_string_evolution() {
+        // Seed a ChaCha8Rng for a predictable "random" number to use for testing
+        let mut prng = ChaCha8Rng::seed_from_u64(42);
+
+        let ga = GeneticAlgorithm::new(
+            selection::RouletteWheelSelection::new(),
+            crossover::UniformCrossover::new(),
+            mutation::BitFlipMutation::new(0.02),
+        );
+
+        let mut population: Vec<OneMax> = (0..20)
+            .map(|_| OneMax(chromosome::BitString::random_bits(&mut prng, 20)))
+            .collect();
+
+        let average = |population: &[OneMax]| {
+            population.iter().map(|i| i.fitness()).sum::<f32>() / population.len() as f32
+        };
+        let before = average(&population);
+
+        for _ in 0..30 {
+            population = ga.evolve(&mut prng, &population);
+        }
+
+        assert!(average(&population) > before);
+    }
+
+    #[test]
+    fn permutation_evolution() {
+        // Seed a ChaCha8Rng for a predictable "random" number to use for testing
+        let mut prng = ChaCha8Rng::seed_from_u64(42);
+
+        let ga = GeneticAlgorithm::new(
+            selection::RouletteWheelSelection::new(),
+            crossover::OrderCrossover::new(),
+            mutation::SwapMutation::new(0.05),
+        );
+
+        let mut population: Vec<Tour> = (0..20)
+            .map(|_| Tour(chromosome::Permutation::random_permutation(&mut prng, 8)))
+            .collect();
+
+        for _ in 0..10 {
+            population = ga.evolve(&mut prng, &population);
+        }
+
+        // However much the tours have been bred and mutated, they should all still visit every point exactly once
+        assert!(population.iter().all(|tour| tour.0.is_permutation()));
+    }
+
+    /// A OneMax that only knows its fitness once it's been scored
+    #[derive(Clone, Debug, PartialEq)]
+    struct Unscored {
+        bits: chromosome::BitString,
+        evaluation: Option<Evaluation>,
+    }
+
+    impl Individual for Unscored {
+        type Gene = bool;
+
+        fn create(bits: chromosome::BitString) -> Self {
+            Self {
+                bits,
+                evaluation: None,
+            }
+        }
+
+        fn chromosome(&self) -> &chromosome::BitString {
+            &self.bits
+        }
+
+        fn fitness(&self) -> f32 {
+            self.evaluation
+                .as_ref()
+                .map(|evaluation| evaluation.fitness)
+                .unwrap_or(0.0)
+        }
+
+        fn is_evaluated(&self) -> bool {
+            self.evaluation.is_some()
+        }
+    }
+
+    impl Evaluable for Unscored {
+        fn set_evaluation(&mut self, evaluation: Evaluation) {
+            self.evaluation = Some(evaluation);
+        }
+    }
+
+    #[test]
+    fn memoized_elitist_evolution() {
+        // Seed a ChaCha8Rng for a predictable "random" number to use for testing
+        let mut prng = ChaCha8Rng::seed_from_u64(42);
+
+        let ga = GeneticAlgorithm::new(
+            selection::RouletteWheelSelection::new(),
+            crossover::UniformCrossover::new(),
+            mutation::BitFlipMutation::new(0.05),
+        )
+        .with_elitism(2);
+
+        let mut evaluator = MemoizingEvaluator::new();
+        let mut population: Vec<Unscored> = (0..10)
+            .map(|_| Unscored::create(chromosome::BitString::random_bits(&mut prng, 16)))
+            .collect();
+
+        let mut scored = 0;
+        let mut best = 0.0;
+        for generation in 0..10 {
+            let before = scored;
+            let next = ga.evaluate_and_evolve(
+                &mut prng,
+                &mut population,
+                &mut evaluator,
+                |_, individual| {
+                    scored += 1;
+                    Evaluation::new(individual.bits.iter().filter(|bit| **bit).count() as f32)
+                },
+            );
+
+            // Everyone needs scoring at first, but after that the elites never get scored again
+            if generation == 0 {
+                assert_eq!(scored - before, 10);
+            } else {
+                assert!(scored - before <= 8);
+            }
+
+            // The elites come through first and unchanged, so the best fitness never drops
+            let fittest = population
+                .iter()
+                .map(Individual::fitness)
+                .fold(f32::NEG_INFINITY, f32::max);
+            assert!(fittest >= best);
+            assert!(population.iter().any(|individual| {
+                individual.chromosome() == next[0].chromosome() && individual.fitness() == fittest
+            }));
+            best = fittest;
+
+            population = next;
+        }
+
+        assert_eq!(evaluator.misses(), scored);
+        assert!(evaluator.hits() >= 2 * 9);
+    }
 }
@@ -23,7 +23,7 @@ impl GaussianMutation {
     }
 }
 
-impl MutationMethod for GaussianMutation {
+impl MutationMethod<f32> for GaussianMutation {
     fn mutate(&self, prng: &mut dyn RngCore, child: &mut Chromosome) {
         for gene in child.iter_mut() {
             let sign = if prng.gen_bool(0.5) { -1.0 } else { 1.0 };
@@ -35,8 +35,94 @@ impl MutationMethod for GaussianMutation {
     }
 }
 
-pub trait MutationMethod {
-    fn mutate(&self, prng: &mut dyn RngCore, child: &mut Chromosome);
+/// Flips each bit of a BitString with the specified chance
+#[derive(Clone, Debug)]
+pub struct BitFlipMutation {
+    chance: f32,
+}
+
+impl BitFlipMutation {
+    pub fn new(chance: f32) -> Self {
+        assert!((0.0..=1.0).contains(&chance));
+
+        Self { chance }
+    }
+}
+
+impl MutationMethod<bool> for BitFlipMutation {
+    fn mutate(&self, prng: &mut dyn RngCore, child: &mut Chromosome<bool>) {
+        for gene in child.iter_mut() {
+            if prng.gen_bool(self.chance as _) {
+                *gene = !*gene;
+            }
+        }
+    }
+}
+
+/// Moves each integer gene up or down by at most step with the specified chance, without leaving min..=max
+#[derive(Clone, Debug)]
+pub struct CreepMutation {
+    chance: f32,
+    step: i32,
+    min: i32,
+    max: i32,
+}
+
+impl CreepMutation {
+    pub fn new(chance: f32, step: i32, min: i32, max: i32) -> Self {
+        assert!((0.0..=1.0).contains(&chance));
+        assert!(step > 0 && min <= max);
+
+        Self {
+            chance,
+            step,
+            min,
+            max,
+        }
+    }
+}
+
+impl MutationMethod<i32> for CreepMutation {
+    fn mutate(&self, prng: &mut dyn RngCore, child: &mut Chromosome<i32>) {
+        for gene in child.iter_mut() {
+            if prng.gen_bool(self.chance as _) {
+                let creep = prng.gen_range(1..=self.step);
+                let creep = if prng.gen_bool(0.5) { -creep } else { creep };
+
+                *gene = (*gene + creep).clamp(self.min, self.max);
+            }
+        }
+    }
+}
+
+/// Swaps each gene with another one picked at random with the specified chance
+/// This never adds or removes a gene, so it's safe to use on a Permutation
+#[derive(Clone, Debug)]
+pub struct SwapMutation {
+    chance: f32,
+}
+
+impl SwapMutation {
+    pub fn new(chance: f32) -> Self {
+        assert!((0.0..=1.0).contains(&chance));
+
+        Self { chance }
+    }
+}
+
+impl<G> MutationMethod<G> for SwapMutation {
+    fn mutate(&self, prng: &mut dyn RngCore, child: &mut Chromosome<G>) {
+        for idx in 0..child.len() {
+            if prng.gen_bool(self.chance as _) {
+                let other = prng.gen_range(0..child.len());
+                child.swap(idx, other);
+            }
+        }
+    }
+}
+
+pub trait MutationMethod<G = f32> {
+    fn mutate(&self, prng: &mut dyn RngCore, child: &mut Chromosome<G>);
 }
 
 #[cfg(test)]
@@ -102,4 +188,54 @@ mod tests {
         // Check the actual mutation matches the expected mutation
         approx::assert_relative_eq!(child.as_slice(), expected.as_slice());
     }
+
+    #[test]
+    fn bit_flip_mutation() {
+        // Seed a ChaCha8Rng for a predictable "random" number to use for testing
+        let mut prng = ChaCha8Rng::from_seed(Default::default());
+
+        // A 100% chance flips every bit, and a 0% chance flips none
+        let mut child: Chromosome<bool> = vec![true, false, true].into_iter().collect();
+        BitFlipMutation::new(1.0).mutate(&mut prng, &mut child);
+        assert_eq!(child.as_slice(), [false, true, false].as_ref());
+
+        BitFlipMutation::new(0.0).mutate(&mut prng, &mut child);
+        assert_eq!(child.as_slice(), [false, true, false].as_ref());
+    }
+
+    #[test]
+    fn creep_mutation() {
+        // Seed a ChaCha8Rng for a predictable "random" number to use for testing
+        let mut prng = ChaCha8Rng::from_seed(Default::default());
+
+        let mutator = CreepMutation::new(1.0, 2, 0, 5);
+        let mut child: Chromosome<i32> = vec![0, 3, 5].into_iter().collect();
+
+        for _ in 0..100 {
+            let before = child.clone();
+            mutator.mutate(&mut prng, &mut child);
+
+            // Every gene moves by at most 2, and never leaves 0..=5
+            assert!(child
+                .iter()
+                .zip(before.iter())
+                .all(|(after, before)| (after - before).abs() <= 2 && (0..=5).contains(after)));
+        }
+    }
+
+    #[test]
+    fn swap_mutation() {
+        // Seed a ChaCha8Rng for a predictable "random" number to use for testing
+        let mut prng = ChaCha8Rng::from_seed(Default::default());
+
+        let mut child: Chromosome<usize> = (0..10).collect();
+        SwapMutation::new(0.5).mutate(&mut prng, &mut child);
+
+        // The genes should have moved around, but they should all still be there
+        assert_ne!(child.as_slice(), (0..10).collect::<Vec<_>>().as_slice());
+
+        let mut genes: Vec<usize> = child.iter().copied().collect();
+        genes.sort_unstable();
+        assert_eq!(genes, (0..10).collect::<Vec<_>>());
+    }
 }
@@ -0,0 +1,455 @@
+use rand::seq::SliceRandom;
+use rand::{Rng, RngCore};
+use serde::{Deserialize, Serialize};
+
+use std::collections::{HashMap, HashSet};
+
+use super::innovation::Innovations;
+
+/// What part a node plays in the network a Genome describes
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum NodeKind {
+    Input,
+    Hidden,
+    Output,
+}
+
+/// A gene describing a single node
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct NodeGene {
+    pub id: usize,
+    pub kind: NodeKind,
+    pub bias: f32,
+}
+
+/// A gene describing a weighted connection between two nodes
+/// The innovation number identifies the same structural change across every genome in the population
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ConnectionGene {
+    pub innovation: usize,
+    pub from: usize,
+    pub to: usize,
+    pub weight: f32,
+    pub enabled: bool,
+}
+
+/// How much each kind of difference between two genomes counts towards their compatibility distance
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Compatibility {
+    /// Genes beyond the end of the other genome's innovation numbers
+    pub excess: f32,
+    /// Genes missing from the other genome within the range of its innovation numbers
+    pub disjoint: f32,
+    /// The mean weight difference of the genes both genomes share
+    pub weight: f32,
+}
+
+impl Default for Compatibility {
+    fn default() -> Self {
+        // The coefficients from the original NEAT paper
+        Self {
+            excess: 1.0,
+            disjoint: 1.0,
+            weight: 0.4,
+        }
+    }
+}
+
+/// A NEAT genome: a list of node genes, and a list of connection genes sorted by innovation number
+/// The network it describes is always feed-forward, as connections that would create a cycle are never added
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Genome {
+    nodes: Vec<NodeGene>,
+    connections: Vec<ConnectionGene>,
+}
+
+impl Genome {
+    /// Create a new Genome with the specified genes
+    pub fn new(nodes: Vec<NodeGene>, mut connections: Vec<ConnectionGene>) -> Self {
+        connections.sort_by_key(|connection| connection.innovation);
+
+        Self { nodes, connections }
+    }
+
+    /// Create a new Genome with no hidden nodes, where every input is connected to every output with a random weight
+    pub fn minimal(
+        prng: &mut dyn RngCore,
+        innovations: &mut Innovations,
+        inputs: usize,
+        outputs: usize,
+    ) -> Self {
+        assert_eq!(innovations.inputs(), inputs);
+        assert_eq!(innovations.outputs(), outputs);
+
+        // Node IDs 0..inputs are the inputs, and the next outputs IDs are the outputs
+        let nodes = (0..inputs)
+            .map(|id| NodeGene {
+                id,
+                kind: NodeKind::Input,
+                bias: 0.0,
+            })
+            .chain((inputs..inputs + outputs).map(|id| NodeGene {
+                id,
+                kind: NodeKind::Output,
+                bias: prng.gen_range(-1.0..=1.0),
+            }))
+            .collect();
+
+        let mut connections = Vec::with_capacity(inputs * outputs);
+        for from in 0..inputs {
+            for to in inputs..inputs + outputs {
+                connections.push(ConnectionGene {
+                    innovation: innovations.connection(from, to),
+                    from,
+                    to,
+                    weight: prng.gen_range(-1.0..=1.0),
+                    enabled: true,
+                });
+            }
+        }
+
+        Self::new(nodes, connections)
+    }
+
+    pub fn nodes(&self) -> &[NodeGene] {
+        &self.nodes
+    }
+
+    pub fn connections(&self) -> &[ConnectionGene] {
+        &self.connections
+    }
+
+    /// Nudge each weight and bias by at most coeff, with the specified chance (like GaussianMutation does for a Chromosome)
+    pub fn mutate_weights(&mut self, prng: &mut dyn RngCore, chance: f32, coeff: f32) {
+        let biases = self
+            .nodes
+            .iter_mut()
+            .filter(|node| node.kind != NodeKind::Input)
+            .map(|node| &mut node.bias);
+        let weights = self
+            .connections
+            .iter_mut()
+            .map(|connection| &mut connection.weight);
+
+        for gene in biases.chain(weights) {
+            let sign = if prng.gen_bool(0.5) { -1.0 } else { 1.0 };
+
+            if prng.gen_bool(chance as _) {
+                *gene += sign * coeff * prng.gen::<f32>();
+            }
+        }
+    }
+
+    /// Connect two previously unconnected nodes with a random weight
+    /// Returns false if there was nowhere left to add a connection without creating a cycle
+    pub fn add_connection(
+        &mut self,
+        prng: &mut dyn RngCore,
+        innovations: &mut Innovations,
+    ) -> bool {
+        let existing: HashSet<(usize, usize)> = self
+            .connections
+            .iter()
+            .map(|connection| (connection.from, connection.to))
+            .collect();
+
+        let mut candidates = Vec::new();
+        for from in &self.nodes {
+            for to in &self.nodes {
+                if from.kind != NodeKind::Output
+                    && to.kind != NodeKind::Input
+                    && from.id != to.id
+                    && !existing.contains(&(from.id, to.id))
+                    && !self.has_path(to.id, from.id)
+                {
+                    candidates.push((from.id, to.id));
+                }
+            }
+        }
+
+        match candidates.choose(prng) {
+            Some(&(from, to)) => {
+                self.insert_connection(ConnectionGene {
+                    innovation: innovations.connection(from, to),
+                    from,
+                    to,
+                    weight: prng.gen_range(-1.0..=1.0),
+                    enabled: true,
+                });
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Split a random enabled connection in two by putting a new hidden node in the middle of it
+    /// The connection into the new node gets a weight of 1.0 and the one out of it keeps the old weight, so the network behaves much as before
+    /// Returns false if there were no enabled connections to split
+    pub fn add_node(&mut self, prng: &mut dyn RngCore, innovations: &mut Innovations) -> bool {
+        let enabled: Vec<usize> = (0..self.connections.len())
+            .filter(|idx| self.connections[*idx].enabled)
+            .collect();
+
+        let idx = match enabled.choose(prng) {
+            Some(idx) => *idx,
+            None => return false,
+        };
+
+        self.connections[idx].enabled = false;
+        let old = self.connections[idx].clone();
+
+        // If this genome already has the node that usually goes here (e.g. the split connection got re-enabled by crossover), it needs a brand new one
+        let mut id = innovations.split(old.innovation);
+        if self.nodes.iter().any(|node| node.id == id) {
+            id = innovations.node();
+        }
+
+        self.nodes.push(NodeGene {
+            id,
+            kind: NodeKind::Hidden,
+            bias: 0.0,
+        });
+
+        self.insert_connection(ConnectionGene {
+            innovation: innovations.connection(old.from, id),
+            from: old.from,
+            to: id,
+            weight: 1.0,
+            enabled: true,
+        });
+        self.insert_connection(ConnectionGene {
+            innovation: innovations.connection(id, old.to),
+            from: id,
+            to: old.to,
+            weight: old.weight,
+            enabled: true,
+        });
+
+        true
+    }
+
+    /// Breed two genomes, lining up their connection genes by innovation number
+    /// Matching genes are picked at random from either parent, but disjoint and excess genes only come from the fitter parent
+    pub fn crossover(prng: &mut dyn RngCore, fitter: &Genome, other: &Genome) -> Genome {
+        let other_connections: HashMap<usize, &ConnectionGene> = other
+            .connections
+            .iter()
+            .map(|connection| (connection.innovation, connection))
+            .collect();
+        let other_nodes: HashMap<usize, &NodeGene> =
+            other.nodes.iter().map(|node| (node.id, node)).collect();
+
+        let connections = fitter
+            .connections
+            .iter()
+            .map(
+                |connection| match other_connections.get(&connection.innovation) {
+                    Some(matching) => {
+                        let mut child = if prng.gen_bool(0.5) {
+                            connection.clone()
+                        } else {
+                            (*matching).clone()
+                        };
+
+                        // A gene that's disabled in either parent is usually disabled in the child too
+                        child.enabled = if connection.enabled && matching.enabled {
+                            true
+                        } else {
+                            !prng.gen_bool(0.75)
+                        };
+
+                        child
+                    }
+                    None => connection.clone(),
+                },
+            )
+            .collect();
+
+        let nodes = fitter
+            .nodes
+            .iter()
+            .map(|node| match other_nodes.get(&node.id) {
+                Some(matching) if prng.gen_bool(0.5) => (*matching).clone(),
+                _ => node.clone(),
+            })
+            .collect();
+
+        // Every connection came from the fitter parent's structure, so the child can't have any cycles either
+        Genome::new(nodes, connections)
+    }
+
+    /// How different two genomes are, using the excess genes, disjoint genes and mean weight difference of matching genes
+    pub fn distance(&self, other: &Genome, compatibility: &Compatibility) -> f32 {
+        let mut excess = 0;
+        let mut disjoint = 0;
+        let mut matching = 0;
+        let mut weight_difference = 0.0;
+
+        let last_a = self.connections.last().map_or(0, |c| c.innovation);
+        let last_b = other.connections.last().map_or(0, |c| c.innovation);
+
+        let mut a = self.connections.iter().peekable();
+        let mut b = other.connections.iter().peekable();
+
+        // Both lists are sorted by innovation number, so walk along them together
+        loop {
+            match (a.peek(), b.peek()) {
+                (Some(gene_a), Some(gene_b)) => {
+                    if gene_a.innovation == gene_b.innovation {
+                        matching += 1;
+                        weight_difference += (gene_a.weight - gene_b.weight).abs();
+                        a.next();
+                        b.next();
+                    } else if gene_a.innovation < gene_b.innovation {
+                        disjoint += 1;
+                        a.next();
+                    } else {
+                        disjoint += 1;
+                        b.next();
+                    }
+                }
+                (Some(gene_a), None) => {
+                    if gene_a.innovation > last_b {
+                        excess += 1;
+                    } else {
+                        disjoint += 1;
+                    }
+                    a.next();
+                }
+                (None, Some(gene_b)) => {
+                    if gene_b.innovation > last_a {
+                        excess += 1;
+                    } else {
+                        disjoint += 1;
+                    }
+                    b.next();
+                }
+                (None, None) => break,
+            }
+        }
+
+        // Small genomes aren't normalised by their size, as in the original paper
+        let genes = self.connections.len().max(other.connections.len());
+        let normaliser = if genes < 20 { 1.0 } else { genes as f32 };
+
+        let mean_weight_difference = if matching > 0 {
+            weight_difference / matching as f32
+        } else {
+            0.0
+        };
+
+        compatibility.excess * excess as f32 / normaliser
+            + compatibility.disjoint * disjoint as f32 / normaliser
+            + compatibility.weight * mean_weight_difference
+    }
+
+    /// Keep the connections sorted by innovation number
+    fn insert_connection(&mut self, connection: ConnectionGene) {
+        let idx = self
+            .connections
+            .partition_point(|existing| existing.innovation < connection.innovation);
+
+        self.connections.insert(idx, connection);
+    }
+
+    /// Check whether there's already a path of connections (enabled or not) leading from one node to another
+    fn has_path(&self, from: usize, to: usize) -> bool {
+        let mut visited = HashSet::new();
+        let mut stack = vec![from];
+
+        while let Some(node) = stack.pop() {
+            if node == to {
+                return true;
+            }
+
+            if visited.insert(node) {
+                stack.extend(
+                    self.connections
+                        .iter()
+                        .filter(|connection| connection.from == node)
+                        .map(|connection| connection.to),
+                );
+            }
+        }
+
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    #[test]
+    fn minimal_genome() {
+        let mut prng = ChaCha8Rng::from_seed(Default::default());
+        let mut innovations = Innovations::new(3, 2);
+
+        let genome = Genome::minimal(&mut prng, &mut innovations, 3, 2);
+
+        assert_eq!(genome.nodes().len(), 3 + 2);
+        assert_eq!(genome.connections().len(), 3 * 2);
+
+        // A second minimal genome should share every innovation number with the first
+        let other = Genome::minimal(&mut prng, &mut innovations, 3, 2);
+        let innovations_a: Vec<usize> = genome.connections().iter().map(|c| c.innovation).collect();
+        let innovations_b: Vec<usize> = other.connections().iter().map(|c| c.innovation).collect();
+        assert_eq!(innovations_a, innovations_b);
+    }
+
+    #[test]
+    fn structural_mutations() {
+        let mut prng = ChaCha8Rng::from_seed(Default::default());
+        let mut innovations = Innovations::new(2, 1);
+
+        let mut genome = Genome::minimal(&mut prng, &mut innovations, 2, 1);
+
+        // Splitting a connection disables it, and adds a node with a connection either side
+        assert!(genome.add_node(&mut prng, &mut innovations));
+        assert_eq!(genome.nodes().len(), 4);
+        assert_eq!(genome.connections().len(), 4);
+        assert_eq!(
+            genome.connections().iter().filter(|c| !c.enabled).count(),
+            1
+        );
+
+        // There's only one place left to connect: the input that doesn't feed the new node yet
+        assert!(genome.add_connection(&mut prng, &mut innovations));
+        assert_eq!(genome.connections().len(), 5);
+        assert!(!genome.add_connection(&mut prng, &mut innovations));
+
+        // The connections should still be sorted by innovation number
+        assert!(genome
+            .connections()
+            .windows(2)
+            .all(|pair| pair[0].innovation < pair[1].innovation));
+    }
+
+    #[test]
+    fn crossover_and_distance() {
+        let mut prng = ChaCha8Rng::from_seed(Default::default());
+        let mut innovations = Innovations::new(2, 1);
+
+        let fitter = Genome::minimal(&mut prng, &mut innovations, 2, 1);
+        let mut other = fitter.clone();
+        other.add_node(&mut prng, &mut innovations);
+
+        // A genome is no distance from itself
+        approx::assert_relative_eq!(fitter.distance(&fitter, &Compatibility::default()), 0.0);
+
+        // The 2 new connections are excess genes
+        let compatibility = Compatibility {
+            excess: 1.0,
+            disjoint: 0.0,
+            weight: 0.0,
+        };
+        approx::assert_relative_eq!(fitter.distance(&other, &compatibility), 2.0);
+
+        // The child should only have the fitter parent's structure
+        let child = Genome::crossover(&mut prng, &fitter, &other);
+        assert_eq!(child.nodes().len(), fitter.nodes().len());
+        assert_eq!(child.connections().len(), fitter.connections().len());
+    }
+}
@@ -0,0 +1,120 @@
+use serde::{Deserialize, Serialize};
+
+use std::collections::HashMap;
+
+/// Hands out node IDs and innovation numbers, so the same structural change gets the same number in every genome
+/// Without this, crossover would have no way of telling which genes in two different genomes line up
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Innovations {
+    inputs: usize,
+    outputs: usize,
+    next_node: usize,
+    next_innovation: usize,
+    #[serde(with = "connection_list")]
+    connections: HashMap<(usize, usize), usize>,
+    splits: HashMap<usize, usize>,
+}
+
+impl Innovations {
+    /// Create a new Innovations tracker, with node IDs already reserved for the specified number of inputs and outputs
+    pub fn new(inputs: usize, outputs: usize) -> Self {
+        Self {
+            inputs,
+            outputs,
+            next_node: inputs + outputs,
+            next_innovation: 0,
+            connections: HashMap::new(),
+            splits: HashMap::new(),
+        }
+    }
+
+    pub fn inputs(&self) -> usize {
+        self.inputs
+    }
+
+    pub fn outputs(&self) -> usize {
+        self.outputs
+    }
+
+    /// Return the innovation number for a connection between two nodes, creating a new one if it's never been seen before
+    pub fn connection(&mut self, from: usize, to: usize) -> usize {
+        let next = &mut self.next_innovation;
+
+        *self.connections.entry((from, to)).or_insert_with(|| {
+            *next += 1;
+            *next - 1
+        })
+    }
+
+    /// Return the ID of the node that goes in the middle of the specified connection when it's split
+    pub fn split(&mut self, innovation: usize) -> usize {
+        let next = &mut self.next_node;
+
+        *self.splits.entry(innovation).or_insert_with(|| {
+            *next += 1;
+            *next - 1
+        })
+    }
+
+    /// Return a brand new node ID that's never been used before
+    pub fn node(&mut self) -> usize {
+        self.next_node += 1;
+        self.next_node - 1
+    }
+}
+
+/// JSON maps can only have strings as keys, so the connections are saved as a list of (from, to, innovation) instead
+mod connection_list {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use std::collections::HashMap;
+
+    pub fn serialize<S>(
+        connections: &HashMap<(usize, usize), usize>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut list: Vec<(usize, usize, usize)> = connections
+            .iter()
+            .map(|((from, to), innovation)| (*from, *to, *innovation))
+            .collect();
+        list.sort_unstable_by_key(|(_, _, innovation)| *innovation);
+
+        list.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<HashMap<(usize, usize), usize>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let list = Vec::<(usize, usize, usize)>::deserialize(deserializer)?;
+
+        Ok(list
+            .into_iter()
+            .map(|(from, to, innovation)| ((from, to), innovation))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn innovation_numbers() {
+        let mut innovations = Innovations::new(2, 1);
+
+        // The same connection always gets the same number, and a new one gets the next number
+        assert_eq!(innovations.connection(0, 2), 0);
+        assert_eq!(innovations.connection(1, 2), 1);
+        assert_eq!(innovations.connection(0, 2), 0);
+
+        // Node IDs carry on after the inputs and outputs, and splitting the same connection twice gives the same node
+        assert_eq!(innovations.split(0), 3);
+        assert_eq!(innovations.split(1), 4);
+        assert_eq!(innovations.split(0), 3);
+        assert_eq!(innovations.node(), 5);
+    }
+}
@@ -0,0 +1,266 @@
+// NeuroEvolution of Augmenting Topologies (NEAT)
+// Instead of evolving the weights of a fixed Chromosome, this evolves Genomes that describe both the weights and the shape of a network
+
+use rand::seq::SliceRandom;
+use rand::{Rng, RngCore};
+use serde::{Deserialize, Serialize};
+
+use crate::speciation::allocate;
+
+mod genome;
+mod innovation;
+
+pub use genome::*;
+pub use innovation::Innovations;
+
+/// Everything that controls how NEAT evolves a population
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct NeatSettings {
+    /// How much each kind of difference between two genomes counts
+    pub compatibility: Compatibility,
+    /// Genomes closer than this to a species' representative belong to that species
+    pub compatibility_threshold: f32,
+    /// Probability of nudging each weight and bias
+    pub mutation_chance: f32,
+    /// How far a nudged weight or bias can move
+    pub mutation_coeff: f32,
+    /// Probability of a child getting a new hidden node
+    pub add_node_chance: f32,
+    /// Probability of a child getting a new connection
+    pub add_connection_chance: f32,
+    /// The fraction of each species (best first) that gets to breed
+    pub survival_threshold: f32,
+    /// Species with at least this many members pass their champion on unchanged
+    pub elitism_size: usize,
+}
+
+impl Default for NeatSettings {
+    fn default() -> Self {
+        Self {
+            compatibility: Compatibility::default(),
+            compatibility_threshold: 3.0,
+            mutation_chance: 0.8,
+            mutation_coeff: 0.5,
+            add_node_chance: 0.03,
+            add_connection_chance: 0.05,
+            survival_threshold: 0.5,
+            elitism_size: 5,
+        }
+    }
+}
+
+/// A group of similar genomes, which only compete and breed amongst themselves
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Species {
+    pub id: usize,
+    /// New genomes are compared against this to decide whether they belong to the species
+    pub representative: Genome,
+    /// The index of each member in the population
+    pub members: Vec<usize>,
+}
+
+/// Evolves a population of Genomes, growing their topology as it goes
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Neat {
+    settings: NeatSettings,
+    innovations: Innovations,
+    species: Vec<Species>,
+    next_species: usize,
+}
+
+impl Neat {
+    /// Create a new Neat for genomes with the specified number of inputs and outputs
+    pub fn new(inputs: usize, outputs: usize, settings: NeatSettings) -> Self {
+        Self {
+            settings,
+            innovations: Innovations::new(inputs, outputs),
+            species: Vec::new(),
+            next_species: 0,
+        }
+    }
+
+    /// How genomes get compared and bred
+    pub fn settings(&self) -> &NeatSettings {
+        &self.settings
+    }
+
+    /// Create a population of minimal genomes with random weights
+    pub fn initial_population(&mut self, prng: &mut dyn RngCore, size: usize) -> Vec<Genome> {
+        let (inputs, outputs) = (self.innovations.inputs(), self.innovations.outputs());
+
+        (0..size)
+            .map(|_| Genome::minimal(prng, &mut self.innovations, inputs, outputs))
+            .collect()
+    }
+
+    /// The species the population was divided into the last time it evolved
+    pub fn species(&self) -> &[Species] {
+        &self.species
+    }
+
+    /// Divide the population into species, keeping the existing species' representatives so species carry on between generations
+    pub fn speciate(&mut self, population: &[Genome]) {
+        for species in &mut self.species {
+            species.members.clear();
+        }
+
+        for (idx, genome) in population.iter().enumerate() {
+            let compatibility = &self.settings.compatibility;
+            let threshold = self.settings.compatibility_threshold;
+
+            match self
+                .species
+                .iter_mut()
+                .find(|species| species.representative.distance(genome, compatibility) < threshold)
+            {
+                Some(species) => species.members.push(idx),
+                None => {
+                    self.species.push(Species {
+                        id: self.next_species,
+                        representative: genome.clone(),
+                        members: vec![idx],
+                    });
+                    self.next_species += 1;
+                }
+            }
+        }
+
+        // Species that died out are gone for good, and the survivors are represented by their first member from now on
+        self.species.retain(|species| !species.members.is_empty());
+        for species in &mut self.species {
+            species.representative = population[species.members[0]].clone();
+        }
+    }
+
+    /// Breed the next generation from a population and each genome's fitness
+    /// Each species gets a share of the children in proportion to its members' shared fitness (fitness divided by species size)
+    pub fn evolve(
+        &mut self,
+        prng: &mut dyn RngCore,
+        population: &[Genome],
+        fitnesses: &[f32],
+    ) -> Vec<Genome> {
+        assert!(!population.is_empty());
+        assert_eq!(population.len(), fitnesses.len());
+
+        self.speciate(population);
+
+        // Fitness sharing: big species don't get to take over just by being big
+        let shared: Vec<f32> = self
+            .species
+            .iter()
+            .map(|species| {
+                let total: f32 = species.members.iter().map(|idx| fitnesses[*idx]).sum();
+                total / species.members.len() as f32
+            })
+            .collect();
+
+        let allocation = allocate(&shared, population.len());
+        let mut children = Vec::with_capacity(population.len());
+
+        for (species, offspring) in self.species.clone().iter().zip(allocation) {
+            if offspring == 0 {
+                continue;
+            }
+
+            // Best members first
+            let mut members = species.members.clone();
+            members.sort_by(|a, b| {
+                fitnesses[*b]
+                    .partial_cmp(&fitnesses[*a])
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            });
+
+            let mut offspring = offspring;
+            if members.len() >= self.settings.elitism_size {
+                children.push(population[members[0]].clone());
+                offspring -= 1;
+            }
+
+            let survivors =
+                ((members.len() as f32 * self.settings.survival_threshold).ceil() as usize).max(1);
+            let parents = &members[..survivors];
+
+            for _ in 0..offspring {
+                let a = *parents.choose(prng).expect("Species has no parents!");
+                let b = *parents.choose(prng).expect("Species has no parents!");
+
+                let (fitter, other) = if fitnesses[a] >= fitnesses[b] {
+                    (a, b)
+                } else {
+                    (b, a)
+                };
+
+                let mut child = Genome::crossover(prng, &population[fitter], &population[other]);
+                self.mutate(prng, &mut child);
+                children.push(child);
+            }
+        }
+
+        children
+    }
+
+    /// Apply weight and structural mutations to a child
+    fn mutate(&mut self, prng: &mut dyn RngCore, child: &mut Genome) {
+        child.mutate_weights(
+            prng,
+            self.settings.mutation_chance,
+            self.settings.mutation_coeff,
+        );
+
+        if prng.gen_bool(self.settings.add_node_chance as _) {
+            child.add_node(prng, &mut self.innovations);
+        }
+
+        if prng.gen_bool(self.settings.add_connection_chance as _) {
+            child.add_connection(prng, &mut self.innovations);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    #[test]
+    fn neat_evolution() {
+        let mut prng = ChaCha8Rng::from_seed(Default::default());
+
+        // Make structural mutations happen all the time, so we can see the topology grow
+        let settings = NeatSettings {
+            add_node_chance: 1.0,
+            add_connection_chance: 1.0,
+            ..NeatSettings::default()
+        };
+        let mut neat = Neat::new(2, 1, settings);
+
+        let mut population = neat.initial_population(&mut prng, 10);
+
+        for _ in 0..5 {
+            // Reward bigger genomes, just to have something to select on
+            let fitnesses: Vec<f32> = population
+                .iter()
+                .map(|genome| genome.connections().len() as f32)
+                .collect();
+
+            population = neat.evolve(&mut prng, &population, &fitnesses);
+            assert_eq!(population.len(), 10);
+        }
+
+        // Every genome should have grown some hidden nodes, and the population should have split into species
+        assert!(population.iter().all(|genome| genome
+            .nodes()
+            .iter()
+            .any(|node| node.kind == NodeKind::Hidden)));
+        assert!(!neat.species().is_empty());
+        let speciated: usize = neat.species().iter().map(|s| s.members.len()).sum();
+        assert_eq!(speciated, 10);
+
+        // Mostly negative fitnesses (like a raw score) should still breed a full population
+        let fitnesses: Vec<f32> = (0..10).map(|i| i as f32 - 8.0).collect();
+        population = neat.evolve(&mut prng, &population, &fitnesses);
+        assert_eq!(population.len(), 10);
+    }
+}
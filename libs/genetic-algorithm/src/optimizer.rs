@@ -0,0 +1,28 @@
+use rand::RngCore;
+
+use crate::crossover::CrossoverMethod;
+use crate::individual::Individual;
+use crate::mutation::MutationMethod;
+use crate::selection::SelectionMethod;
+use crate::GeneticAlgorithm;
+
+/// Anything that can breed the next generation from a population genericised over an Individual I that has already been evaluated
+/// GeneticAlgorithm and the evolution strategies all implement this, so whatever's training a population can swap between them
+pub trait Optimizer<I> {
+    /// Return the next generation, which needs evaluating before it's passed back in
+    /// Some optimizers keep track of the population they handed out, so the children should come back in the same order
+    fn evolve(&mut self, prng: &mut dyn RngCore, population: &[I]) -> Vec<I>;
+}
+
+impl<S, C, M, I> Optimizer<I> for GeneticAlgorithm<S, C, M>
+where
+    S: SelectionMethod,
+    C: CrossoverMethod<I::Gene>,
+    M: MutationMethod<I::Gene>,
+    I: Individual,
+    I::Gene: Clone,
+{
+    fn evolve(&mut self, prng: &mut dyn RngCore, population: &[I]) -> Vec<I> {
+        GeneticAlgorithm::evolve(self, prng, population)
+    }
+}
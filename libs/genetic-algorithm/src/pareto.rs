@@ -0,0 +1,172 @@
+use crate::individual::Individual;
+
+/// Check whether objectives a Pareto-dominate objectives b (i.e. a is no worse at anything, and better at something)
+pub fn dominates(a: &[f32], b: &[f32]) -> bool {
+    assert_eq!(a.len(), b.len());
+
+    a.iter().zip(b).all(|(a, b)| a >= b) && a.iter().zip(b).any(|(a, b)| a > b)
+}
+
+/// Sort the objectives into non-dominated fronts, as in NSGA-II
+/// Returns the front each set of objectives belongs to, where front 0 is the Pareto front (nothing dominates it)
+pub fn non_dominated_fronts(objectives: &[Vec<f32>]) -> Vec<usize> {
+    let count = objectives.len();
+
+    // For each individual, who it dominates and how many others dominate it
+    let mut dominated: Vec<Vec<usize>> = vec![Vec::new(); count];
+    let mut domination_count = vec![0; count];
+
+    for a in 0..count {
+        for b in 0..count {
+            if dominates(&objectives[a], &objectives[b]) {
+                dominated[a].push(b);
+            } else if dominates(&objectives[b], &objectives[a]) {
+                domination_count[a] += 1;
+            }
+        }
+    }
+
+    // Peel off one front at a time: once a front is removed, anyone it was the last thing dominating joins the next front
+    let mut fronts = vec![0; count];
+    let mut current: Vec<usize> = (0..count).filter(|a| domination_count[*a] == 0).collect();
+    let mut front = 0;
+
+    while !current.is_empty() {
+        let mut next = Vec::new();
+
+        for a in current {
+            fronts[a] = front;
+
+            for &b in &dominated[a] {
+                domination_count[b] -= 1;
+                if domination_count[b] == 0 {
+                    next.push(b);
+                }
+            }
+        }
+
+        current = next;
+        front += 1;
+    }
+
+    fronts
+}
+
+/// Work out how crowded each individual's neighbourhood is within its own front, as in NSGA-II
+/// Individuals at the edges of a front get an infinite distance, so they're always kept
+pub fn crowding_distances(objectives: &[Vec<f32>], fronts: &[usize]) -> Vec<f32> {
+    assert_eq!(objectives.len(), fronts.len());
+
+    let mut distances = vec![0.0; objectives.len()];
+    let front_count = fronts.iter().max().map_or(0, |max| max + 1);
+    let objective_count = objectives.first().map_or(0, Vec::len);
+
+    for front in 0..front_count {
+        let members: Vec<usize> = (0..fronts.len()).filter(|a| fronts[*a] == front).collect();
+
+        for objective in 0..objective_count {
+            let values: Vec<f32> = objectives.iter().map(|o| o[objective]).collect();
+
+            let mut sorted = members.clone();
+            sorted.sort_by(|a, b| {
+                values[*a]
+                    .partial_cmp(&values[*b])
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            });
+
+            let first = sorted[0];
+            let last = sorted[sorted.len() - 1];
+            distances[first] = f32::INFINITY;
+            distances[last] = f32::INFINITY;
+
+            // If everyone scored the same on this objective, it can't tell them apart
+            let range = values[last] - values[first];
+            if range <= 0.0 {
+                continue;
+            }
+
+            for window in sorted.windows(3) {
+                distances[window[1]] += (values[window[2]] - values[window[0]]) / range;
+            }
+        }
+    }
+
+    distances
+}
+
+/// The front and crowding distance of every individual in a population
+#[derive(Clone, Debug)]
+pub struct ParetoRanking {
+    fronts: Vec<usize>,
+    crowding: Vec<f32>,
+}
+
+impl ParetoRanking {
+    /// Rank a population genericised over an Individual I using its objectives
+    pub fn new<I>(population: &[I]) -> Self
+    where
+        I: Individual,
+    {
+        let objectives: Vec<Vec<f32>> = population.iter().map(Individual::objectives).collect();
+
+        let fronts = non_dominated_fronts(&objectives);
+        let crowding = crowding_distances(&objectives, &fronts);
+
+        Self { fronts, crowding }
+    }
+
+    /// Return the front the specified individual belongs to (0 is the Pareto front)
+    pub fn front(&self, idx: usize) -> usize {
+        self.fronts[idx]
+    }
+
+    /// Return the crowding distance of the specified individual within its front
+    pub fn crowding(&self, idx: usize) -> f32 {
+        self.crowding[idx]
+    }
+
+    /// NSGA-II's crowded comparison: a better front wins, and within the same front the less crowded individual wins
+    pub fn is_better(&self, a: usize, b: usize) -> bool {
+        self.fronts[a] < self.fronts[b]
+            || (self.fronts[a] == self.fronts[b] && self.crowding[a] > self.crowding[b])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::individual::TestIndividual;
+
+    #[test]
+    fn domination() {
+        assert!(dominates(&[2.0, 2.0], &[1.0, 2.0]));
+        assert!(!dominates(&[2.0, 2.0], &[2.0, 2.0]));
+        assert!(!dominates(&[3.0, 1.0], &[1.0, 3.0]));
+    }
+
+    #[test]
+    fn fronts_and_crowding() {
+        let population = vec![
+            TestIndividual::with_objectives(&[1.0, 4.0]), // Pareto front
+            TestIndividual::with_objectives(&[2.0, 3.0]), // Pareto front
+            TestIndividual::with_objectives(&[4.0, 1.0]), // Pareto front
+            TestIndividual::with_objectives(&[1.0, 3.0]), // Dominated by [1.0, 4.0] and [2.0, 3.0]
+            TestIndividual::with_objectives(&[0.0, 0.0]), // Dominated by everyone
+        ];
+
+        let ranking = ParetoRanking::new(&population);
+
+        let fronts: Vec<usize> = (0..population.len()).map(|a| ranking.front(a)).collect();
+        assert_eq!(fronts, vec![0, 0, 0, 1, 2]);
+
+        // The edges of the Pareto front are never crowded out, and the middle gets the sum of its normalised gaps
+        assert!(ranking.crowding(0).is_infinite());
+        assert!(ranking.crowding(2).is_infinite());
+        approx::assert_relative_eq!(ranking.crowding(1), 3.0 / 3.0 + 3.0 / 3.0);
+
+        // A better front always wins, then the less crowded individual wins
+        assert!(ranking.is_better(1, 3));
+        assert!(ranking.is_better(0, 1));
+        assert!(!ranking.is_better(4, 3));
+    }
+}
@@ -1,7 +1,8 @@
 use rand::seq::SliceRandom;
-use rand::RngCore;
+use rand::{Rng, RngCore};
 
 use crate::individual::Individual;
+use crate::pareto::ParetoRanking;
 
 #[derive(Clone, Debug, Default)]
 pub struct RouletteWheelSelection;
@@ -13,7 +14,20 @@ impl RouletteWheelSelection {
 }
 
 impl SelectionMethod for RouletteWheelSelection {
-    fn select<'a, I>(&self, prng: &mut dyn RngCore, population: &'a [I]) -> &'a I
+    type Prepared = ();
+
+    fn prepare<I>(&self, _population: &[I]) -> Self::Prepared
+    where
+        I: Individual,
+    {
+    }
+
+    fn select<'a, I>(
+        &self,
+        prng: &mut dyn RngCore,
+        population: &'a [I],
+        _prepared: &Self::Prepared,
+    ) -> &'a I
     where
         I: Individual,
     {
@@ -23,8 +37,65 @@ impl SelectionMethod for RouletteWheelSelection {
     }
 }
 
+/// NSGA-II style binary tournament: pick two individuals at random, and keep whichever has the better Pareto front (or is less crowded)
+/// This uses every objective of each individual, rather than its single fitness score
+#[derive(Clone, Debug, Default)]
+pub struct ParetoTournamentSelection;
+
+impl ParetoTournamentSelection {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl SelectionMethod for ParetoTournamentSelection {
+    /// Sorting the population into fronts is by far the slowest part, so it's only done once for every selection from the same population
+    type Prepared = ParetoRanking;
+
+    fn prepare<I>(&self, population: &[I]) -> Self::Prepared
+    where
+        I: Individual,
+    {
+        ParetoRanking::new(population)
+    }
+
+    fn select<'a, I>(
+        &self,
+        prng: &mut dyn RngCore,
+        population: &'a [I],
+        ranking: &Self::Prepared,
+    ) -> &'a I
+    where
+        I: Individual,
+    {
+        assert!(!population.is_empty(), "Population is empty!");
+
+        let a = prng.gen_range(0..population.len());
+        let b = prng.gen_range(0..population.len());
+
+        if ranking.is_better(b, a) {
+            &population[b]
+        } else {
+            &population[a]
+        }
+    }
+}
+
 pub trait SelectionMethod {
-    fn select<'a, I>(&self, prng: &mut dyn RngCore, population: &'a [I]) -> &'a I
+    /// Whatever the method needs to know about the whole population before picking from it, worked out once per population rather than once per selection
+    type Prepared;
+
+    fn prepare<I>(&self, population: &[I]) -> Self::Prepared
+    where
+        I: Individual;
+
+    /// Pick an individual from the population, which must be the same one the prepared state came from
+    fn select<'a, I>(
+        &self,
+        prng: &mut dyn RngCore,
+        population: &'a [I],
+        prepared: &Self::Prepared,
+    ) -> &'a I
     where
         I: Individual;
 }
@@ -56,7 +127,7 @@ mod tests {
 
         // Spin the wheel 1000 times and see how many times each individual is chosen
         let actual = (0..1000)
-            .map(|_| roulette_wheel.select(&mut prng, &population))
+            .map(|_| roulette_wheel.select(&mut prng, &population, &()))
             .fold(
                 HashMap::default(),
                 |mut map: HashMap<i32, i32>, individual| {
@@ -76,4 +147,31 @@ mod tests {
         // Check the actual selection histogram matches what we expected
         assert_eq!(actual, expected);
     }
+
+    #[test]
+    fn pareto_tournament_selection() {
+        // Seed a ChaCha8Rng for a predictable "random" number to use for testing
+        let mut prng = ChaCha8Rng::from_seed(Default::default());
+
+        let tournament = ParetoTournamentSelection::new();
+
+        // Two individuals on the Pareto front, and one that's dominated by both of them
+        let population = vec![
+            TestIndividual::with_objectives(&[3.0, 1.0]),
+            TestIndividual::with_objectives(&[1.0, 3.0]),
+            TestIndividual::with_objectives(&[0.5, 0.5]),
+        ];
+
+        // The fronts only get worked out once, however many tournaments are held
+        let ranking = tournament.prepare(&population);
+        assert_eq!(ranking.front(2), 1);
+
+        let dominated = (0..1000)
+            .map(|_| tournament.select(&mut prng, &population, &ranking))
+            .filter(|individual| **individual == population[2])
+            .count();
+
+        // The dominated individual only survives a tournament against itself, which happens 1 time in 9
+        assert!(dominated > 70 && dominated < 150, "{}", dominated);
+    }
 }
@@ -0,0 +1,264 @@
+use serde::{Deserialize, Serialize};
+
+use crate::chromosome::Chromosome;
+use crate::individual::Individual;
+
+/// Everything that controls how a population is split into species
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SpeciationSettings {
+    /// Chromosomes closer than this to a species' representative belong to that species
+    pub threshold: f32,
+    /// A species whose best fitness hasn't improved for this many generations gets pruned
+    pub stagnation_limit: usize,
+}
+
+impl Default for SpeciationSettings {
+    fn default() -> Self {
+        Self {
+            threshold: 0.5,
+            stagnation_limit: 15,
+        }
+    }
+}
+
+/// A group of similar chromosomes, which only breed amongst themselves
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Species {
+    pub id: usize,
+    /// New chromosomes are compared against this to decide whether they belong to the species
+    pub representative: Chromosome,
+    /// The index of each member in the population
+    pub members: Vec<usize>,
+    /// The best fitness any member of the species has ever had
+    pub best_fitness: f32,
+    /// How many generations it's been since best_fitness last improved
+    pub stagnant_for: usize,
+}
+
+/// Splits a population of fixed length chromosomes into species, which carry on from one generation to the next
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Speciation {
+    settings: SpeciationSettings,
+    species: Vec<Species>,
+    next_species: usize,
+}
+
+impl Speciation {
+    pub fn new(settings: SpeciationSettings) -> Self {
+        Self {
+            settings,
+            species: Vec::new(),
+            next_species: 0,
+        }
+    }
+
+    /// The species the population was divided into the last time it was speciated
+    pub fn species(&self) -> &[Species] {
+        &self.species
+    }
+
+    /// Divide a population genericised over an Individual I into species, then prune any that have stagnated
+    /// The species holding the fittest individual is never pruned, so there's always at least one left
+    pub fn speciate<I>(&mut self, population: &[I])
+    where
+        I: Individual<Gene = f32>,
+    {
+        assert!(!population.is_empty());
+
+        for species in &mut self.species {
+            species.members.clear();
+        }
+
+        for (idx, individual) in population.iter().enumerate() {
+            let threshold = self.settings.threshold;
+
+            match self.species.iter_mut().find(|species| {
+                distance(&species.representative, individual.chromosome()) < threshold
+            }) {
+                Some(species) => species.members.push(idx),
+                None => {
+                    self.species.push(Species {
+                        id: self.next_species,
+                        representative: individual.chromosome().clone(),
+                        members: vec![idx],
+                        best_fitness: f32::NEG_INFINITY,
+                        stagnant_for: 0,
+                    });
+                    self.next_species += 1;
+                }
+            }
+        }
+
+        // Species that died out are gone for good, and the survivors are represented by their first member from now on
+        self.species.retain(|species| !species.members.is_empty());
+
+        for species in &mut self.species {
+            species.representative = population[species.members[0]].chromosome().clone();
+
+            let best = species
+                .members
+                .iter()
+                .map(|idx| population[*idx].fitness())
+                .fold(f32::NEG_INFINITY, f32::max);
+
+            if best > species.best_fitness {
+                species.best_fitness = best;
+                species.stagnant_for = 0;
+            } else {
+                species.stagnant_for += 1;
+            }
+        }
+
+        let fittest = (0..population.len())
+            .max_by(|a, b| {
+                population[*a]
+                    .fitness()
+                    .partial_cmp(&population[*b].fitness())
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            })
+            .expect("Population is empty!");
+
+        let stagnation_limit = self.settings.stagnation_limit;
+        self.species.retain(|species| {
+            species.stagnant_for < stagnation_limit || species.members.contains(&fittest)
+        });
+    }
+
+    /// Share out a number of children between the species, in proportion to the members' shared fitness (fitness divided by species size)
+    /// This stops a big species taking over the whole population just by being big
+    pub fn offspring<I>(&self, population: &[I], total: usize) -> Vec<usize>
+    where
+        I: Individual,
+    {
+        let shared: Vec<f32> = self
+            .species
+            .iter()
+            .map(|species| {
+                species
+                    .members
+                    .iter()
+                    .map(|idx| population[*idx].fitness() / species.members.len() as f32)
+                    .sum()
+            })
+            .collect();
+
+        allocate(&shared, total)
+    }
+}
+
+/// The mean absolute difference between the genes of two chromosomes
+pub fn distance(a: &Chromosome, b: &Chromosome) -> f32 {
+    assert_eq!(a.len(), b.len());
+
+    if a.is_empty() {
+        return 0.0;
+    }
+
+    a.iter()
+        .zip(b.iter())
+        .map(|(a, b)| (a - b).abs())
+        .sum::<f32>()
+        / a.len() as f32
+}
+
+/// Share out a number of children between species in proportion to their scores, so the total always comes out exactly right
+/// Whoever loses the most to rounding down gets the leftovers
+/// Negative scores count as zero, since a species can't be given fewer than no children
+pub(crate) fn allocate(scores: &[f32], total: usize) -> Vec<usize> {
+    let scores: Vec<f32> = scores.iter().map(|score| score.max(0.0)).collect();
+    let sum: f32 = scores.iter().sum();
+
+    // If no one scored anything, share them out evenly
+    let exact: Vec<f32> = scores
+        .iter()
+        .map(|score| {
+            if sum > 0.0 {
+                score / sum * total as f32
+            } else {
+                total as f32 / scores.len() as f32
+            }
+        })
+        .collect();
+
+    let mut allocation: Vec<usize> = exact.iter().map(|exact| exact.floor() as usize).collect();
+
+    let mut remainders: Vec<usize> = (0..scores.len()).collect();
+    remainders.sort_by(|a, b| {
+        (exact[*b] - exact[*b].floor())
+            .partial_cmp(&(exact[*a] - exact[*a].floor()))
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    let allocated: usize = allocation.iter().sum();
+    for idx in remainders.iter().cycle().take(total - allocated) {
+        allocation[*idx] += 1;
+    }
+
+    allocation
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::individual::TestIndividual;
+
+    fn individual(genes: &[f32]) -> TestIndividual {
+        TestIndividual::create(genes.iter().cloned().collect())
+    }
+
+    #[test]
+    fn offspring_allocation() {
+        assert_eq!(allocate(&[1.0, 1.0, 2.0], 8), vec![2, 2, 4]);
+        assert_eq!(allocate(&[1.0, 1.0, 1.0], 10), vec![4, 3, 3]);
+        assert_eq!(allocate(&[0.0, 0.0], 5).iter().sum::<usize>(), 5);
+
+        // Negative scores get nothing, and if nobody scored above zero everyone gets an even share
+        assert_eq!(allocate(&[-1.0, 2.0], 10), vec![0, 10]);
+        assert_eq!(allocate(&[-1.0, -3.0], 10), vec![5, 5]);
+    }
+
+    #[test]
+    fn chromosome_distance() {
+        let a = [0.0, 1.0, 2.0].iter().cloned().collect();
+        let b = [1.0, 1.0, 0.0].iter().cloned().collect();
+
+        approx::assert_relative_eq!(distance(&a, &b), 1.0);
+        approx::assert_relative_eq!(distance(&a, &a), 0.0);
+    }
+
+    #[test]
+    fn speciation_and_sharing() {
+        let mut speciation = Speciation::new(SpeciationSettings {
+            threshold: 0.5,
+            stagnation_limit: 2,
+        });
+
+        // Two clusters: a big one near 0.0 and a small one near 5.0 (a TestIndividual's fitness is the sum of its genes)
+        let population = vec![
+            individual(&[0.1, 0.1]),
+            individual(&[0.2, 0.1]),
+            individual(&[0.1, 0.2]),
+            individual(&[0.2, 0.2]),
+            individual(&[5.0, 5.0]),
+        ];
+
+        speciation.speciate(&population);
+
+        let members: Vec<Vec<usize>> = speciation
+            .species()
+            .iter()
+            .map(|species| species.members.clone())
+            .collect();
+        assert_eq!(members, vec![vec![0, 1, 2, 3], vec![4]]);
+
+        // The big species can't outbreed the small one just by being big
+        assert_eq!(speciation.offspring(&population, 10), vec![0, 10]);
+
+        // Nothing improves, so the species near 0.0 stagnates and gets pruned, but the one with the fittest individual survives
+        speciation.speciate(&population);
+        assert_eq!(speciation.species().len(), 2);
+        speciation.speciate(&population);
+        assert_eq!(speciation.species().len(), 1);
+        assert_eq!(speciation.species()[0].members, vec![4]);
+    }
+}
@@ -1,6 +1,8 @@
+use serde::{Deserialize, Serialize};
+
 use crate::*;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Statistics {
     pub(crate) min_fitness: f32,
     pub(crate) max_fitness: f32,
@@ -10,7 +12,7 @@ pub struct Statistics {
 
 impl Statistics {
     /// Create a new Statistics struct genericised over an Individual I
-    pub(crate) fn new<I>(population: &[I]) -> Self
+    pub fn new<I>(population: &[I]) -> Self
     where
         I: Individual,
     {
@@ -0,0 +1,81 @@
+// Compare the allocating propagate() against propagate_into() and propagate_batch(), which reuse the same buffers every time
+// Run with `cargo bench -p neural-network`
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+use neural_network::activation::Activation;
+use neural_network::topology::LayerTopology;
+use neural_network::{Network, Scratch};
+
+/// How many inputs to push through at once for the batched benchmarks
+const BATCH: usize = 64;
+
+/// A network the same shape as the ones the Pong AI players use by default (5 inputs, a hidden layer, 1 output)
+fn pong_network() -> Network {
+    let mut prng = ChaCha8Rng::seed_from_u64(42);
+
+    Network::random(
+        &mut prng,
+        &[
+            LayerTopology::new(5, Activation::Relu),
+            LayerTopology::new(8, Activation::Relu),
+            LayerTopology::new(1, Activation::Tanh),
+        ],
+    )
+}
+
+fn single(c: &mut Criterion) {
+    let network = pong_network();
+    let inputs = vec![0.1, -0.2, 0.3, -0.4, 0.5];
+
+    let mut group = c.benchmark_group("single");
+
+    group.bench_function("propagate", |b| {
+        b.iter_batched(
+            || inputs.clone(),
+            |inputs| network.propagate(black_box(inputs)),
+            BatchSize::SmallInput,
+        )
+    });
+
+    group.bench_function("propagate_into", |b| {
+        let mut outputs = vec![0.0; network.outputs()];
+        let mut scratch = Scratch::default();
+
+        b.iter(|| network.propagate_into(black_box(&inputs), &mut outputs, &mut scratch))
+    });
+
+    group.finish();
+}
+
+fn batch(c: &mut Criterion) {
+    let network = pong_network();
+    let inputs: Vec<f32> = (0..BATCH * network.inputs())
+        .map(|i| (i as f32 * 0.37).sin())
+        .collect();
+
+    let mut group = c.benchmark_group("batch");
+
+    group.bench_function("propagate", |b| {
+        b.iter(|| {
+            inputs
+                .chunks_exact(network.inputs())
+                .map(|inputs| network.propagate(black_box(inputs.to_vec())))
+                .collect::<Vec<_>>()
+        })
+    });
+
+    group.bench_function("propagate_batch", |b| {
+        let mut outputs = vec![0.0; BATCH * network.outputs()];
+        let mut scratch = Scratch::default();
+
+        b.iter(|| network.propagate_batch(black_box(&inputs), &mut outputs, &mut scratch))
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, single, batch);
+criterion_main!(benches);
@@ -0,0 +1,72 @@
+use serde::{Deserialize, Serialize};
+
+/// The function applied to the output of every neuron in a layer
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Activation {
+    /// max(0.0, x) - only ever produces positive outputs
+    #[default]
+    Relu,
+    /// Squashes the output into -1..=1
+    Tanh,
+    /// Leaves the output untouched
+    Linear,
+}
+
+impl Activation {
+    /// Apply the activation function to a neuron's weighted sum of inputs
+    pub fn apply(&self, x: f32) -> f32 {
+        match self {
+            Self::Relu => x.max(0.0),
+            Self::Tanh => x.tanh(),
+            Self::Linear => x,
+        }
+    }
+
+    /// The slope of the activation function at the specified weighted sum, which backpropagation needs to work out how much each weight was to blame
+    /// ReLU doesn't have a slope at exactly 0.0, so it's treated as flat there
+    pub fn derivative(&self, x: f32) -> f32 {
+        match self {
+            Self::Relu => {
+                if x > 0.0 {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            Self::Tanh => 1.0 - x.tanh().powi(2),
+            Self::Linear => 1.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn activation_functions() {
+        approx::assert_relative_eq!(Activation::Relu.apply(-0.5), 0.0);
+        approx::assert_relative_eq!(Activation::Relu.apply(0.5), 0.5);
+
+        approx::assert_relative_eq!(Activation::Tanh.apply(0.0), 0.0);
+        approx::assert_relative_eq!(Activation::Tanh.apply(-0.5), -0.46211717);
+        approx::assert_relative_eq!(Activation::Tanh.apply(100.0), 1.0);
+
+        approx::assert_relative_eq!(Activation::Linear.apply(-0.5), -0.5);
+        approx::assert_relative_eq!(Activation::Linear.apply(7.0), 7.0);
+    }
+
+    #[test]
+    fn activation_derivatives() {
+        approx::assert_relative_eq!(Activation::Relu.derivative(-0.5), 0.0);
+        approx::assert_relative_eq!(Activation::Relu.derivative(0.0), 0.0);
+        approx::assert_relative_eq!(Activation::Relu.derivative(0.5), 1.0);
+
+        approx::assert_relative_eq!(Activation::Tanh.derivative(0.0), 1.0);
+        approx::assert_relative_eq!(Activation::Tanh.derivative(-0.5), 0.7864477);
+        approx::assert_abs_diff_eq!(Activation::Tanh.derivative(100.0), 0.0);
+
+        approx::assert_relative_eq!(Activation::Linear.derivative(-0.5), 1.0);
+        approx::assert_relative_eq!(Activation::Linear.derivative(7.0), 1.0);
+    }
+}
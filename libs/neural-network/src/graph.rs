@@ -0,0 +1,253 @@
+use serde::{Deserialize, Serialize};
+
+use std::collections::HashMap;
+
+use crate::activation::Activation;
+
+/// What part a node plays in a GraphNetwork
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum NodeRole {
+    Input,
+    Hidden,
+    Output,
+}
+
+/// A node in a GraphNetwork, as described by whatever built it (e.g. a NEAT genome)
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Node {
+    pub id: usize,
+    pub role: NodeRole,
+    pub bias: f32,
+    pub activation: Activation,
+}
+
+/// A weighted connection between two nodes in a GraphNetwork
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Edge {
+    pub from: usize,
+    pub to: usize,
+    pub weight: f32,
+}
+
+/// A feed-forward network with an arbitrary topology, rather than a neat stack of fully connected layers
+/// The nodes are kept in an order where every node comes after all the nodes that feed into it, so one pass is enough to propagate
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GraphNetwork {
+    nodes: Vec<Node>,
+    incoming: Vec<Vec<(usize, f32)>>,
+    inputs: Vec<usize>,
+    outputs: Vec<usize>,
+}
+
+impl GraphNetwork {
+    /// Build a new GraphNetwork from the specified nodes and edges
+    /// Inputs and outputs are fed and read in the order they appear in the nodes
+    /// This panics if an edge refers to a node that doesn't exist, feeds into an input, or creates a cycle
+    pub fn new(nodes: &[Node], edges: &[Edge]) -> GraphNetwork {
+        let index: HashMap<usize, usize> = nodes
+            .iter()
+            .enumerate()
+            .map(|(idx, node)| (node.id, idx))
+            .collect();
+        assert_eq!(index.len(), nodes.len(), "Node IDs must be unique!");
+
+        // Who feeds into each node, and how many unsorted nodes each node is still waiting on
+        let mut incoming: Vec<Vec<(usize, f32)>> = vec![Vec::new(); nodes.len()];
+        let mut outgoing: Vec<Vec<usize>> = vec![Vec::new(); nodes.len()];
+        let mut waiting_on = vec![0; nodes.len()];
+
+        for edge in edges {
+            let from = *index.get(&edge.from).expect("Edge from an unknown node!");
+            let to = *index.get(&edge.to).expect("Edge to an unknown node!");
+            assert_ne!(nodes[to].role, NodeRole::Input, "Edge into an input node!");
+
+            incoming[to].push((from, edge.weight));
+            outgoing[from].push(to);
+            waiting_on[to] += 1;
+        }
+
+        // Kahn's algorithm: keep taking nodes that aren't waiting on anything else
+        let mut order = Vec::with_capacity(nodes.len());
+        let mut ready: Vec<usize> = (0..nodes.len())
+            .filter(|idx| waiting_on[*idx] == 0)
+            .collect();
+
+        while let Some(idx) = ready.pop() {
+            order.push(idx);
+
+            for &to in &outgoing[idx] {
+                waiting_on[to] -= 1;
+                if waiting_on[to] == 0 {
+                    ready.push(to);
+                }
+            }
+        }
+
+        assert_eq!(order.len(), nodes.len(), "The network has a cycle in it!");
+
+        // Renumber everything so the nodes are stored in the order they need to be worked out in
+        let mut position = vec![0; nodes.len()];
+        for (new, old) in order.iter().enumerate() {
+            position[*old] = new;
+        }
+
+        let sorted_nodes = order.iter().map(|idx| nodes[*idx].clone()).collect();
+        let sorted_incoming = order
+            .iter()
+            .map(|idx| {
+                incoming[*idx]
+                    .iter()
+                    .map(|(from, weight)| (position[*from], *weight))
+                    .collect()
+            })
+            .collect();
+
+        let find = |role: NodeRole| -> Vec<usize> {
+            (0..nodes.len())
+                .filter(|idx| nodes[*idx].role == role)
+                .map(|idx| position[idx])
+                .collect()
+        };
+
+        GraphNetwork {
+            nodes: sorted_nodes,
+            incoming: sorted_incoming,
+            inputs: find(NodeRole::Input),
+            outputs: find(NodeRole::Output),
+        }
+    }
+
+    /// Feed the inputs through the network and return the values of the output nodes
+    pub fn propagate(&self, inputs: Vec<f32>) -> Vec<f32> {
+        let values = self.activations(inputs);
+
+        self.outputs.iter().map(|idx| values[*idx]).collect()
+    }
+
+    /// Propagate the inputs, returning the value of every node (in the same order as GraphNetwork::nodes()) rather than just the outputs
+    pub fn activations(&self, inputs: Vec<f32>) -> Vec<f32> {
+        assert_eq!(inputs.len(), self.inputs.len());
+
+        let mut values = vec![0.0; self.nodes.len()];
+
+        for (idx, input) in self.inputs.iter().zip(inputs) {
+            values[*idx] = input;
+        }
+
+        // Every node comes after everything that feeds into it, so a single pass works everything out
+        for (idx, node) in self.nodes.iter().enumerate() {
+            if node.role == NodeRole::Input {
+                continue;
+            }
+
+            let sum = self.incoming[idx]
+                .iter()
+                .fold(node.bias, |sum, (from, weight)| {
+                    sum + values[*from] * weight
+                });
+
+            values[idx] = node.activation.apply(sum);
+        }
+
+        values
+    }
+
+    /// Return the nodes, in the order they get worked out in
+    pub fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+
+    /// Return every edge in the network
+    pub fn edges(&self) -> Vec<Edge> {
+        self.incoming
+            .iter()
+            .enumerate()
+            .flat_map(|(to, incoming)| {
+                incoming
+                    .iter()
+                    .map(move |(from, weight)| (to, *from, *weight))
+            })
+            .map(|(to, from, weight)| Edge {
+                from: self.nodes[from].id,
+                to: self.nodes[to].id,
+                weight,
+            })
+            .collect()
+    }
+
+    /// Return every bias and edge weight in the network, one node at a time
+    pub fn weights(&self) -> impl Iterator<Item = f32> + '_ {
+        self.nodes
+            .iter()
+            .zip(&self.incoming)
+            .filter(|(node, _)| node.role != NodeRole::Input)
+            .flat_map(|(node, incoming)| {
+                std::iter::once(node.bias).chain(incoming.iter().map(|(_, weight)| *weight))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(id: usize, role: NodeRole, bias: f32) -> Node {
+        Node {
+            id,
+            role,
+            bias,
+            activation: Activation::Linear,
+        }
+    }
+
+    fn edge(from: usize, to: usize, weight: f32) -> Edge {
+        Edge { from, to, weight }
+    }
+
+    #[test]
+    fn graph_propagation() {
+        // Two inputs, one output, a hidden node in between, and a connection that skips straight past it
+        // The nodes are deliberately listed out of order, to check they get sorted
+        let nodes = [
+            node(3, NodeRole::Hidden, 0.5),
+            node(0, NodeRole::Input, 0.0),
+            node(2, NodeRole::Output, -1.0),
+            node(1, NodeRole::Input, 0.0),
+        ];
+        let edges = [
+            edge(3, 2, 2.0),
+            edge(0, 3, 1.0),
+            edge(1, 3, -1.0),
+            edge(0, 2, 3.0),
+        ];
+
+        let network = GraphNetwork::new(&nodes, &edges);
+
+        // hidden = 0.5 + 1.0 * 1.0 - 1.0 * 2.0 = -0.5
+        // output = -1.0 + 2.0 * -0.5 + 3.0 * 1.0 = 1.0
+        let outputs = network.propagate(vec![1.0, 2.0]);
+        approx::assert_relative_eq!(outputs.as_slice(), [1.0].as_ref());
+
+        assert_eq!(network.edges().len(), 4);
+        assert_eq!(network.weights().count(), 2 + 4);
+    }
+
+    #[test]
+    #[should_panic(expected = "cycle")]
+    fn graph_with_cycle() {
+        let nodes = [
+            node(0, NodeRole::Input, 0.0),
+            node(1, NodeRole::Hidden, 0.0),
+            node(2, NodeRole::Hidden, 0.0),
+            node(3, NodeRole::Output, 0.0),
+        ];
+        let edges = [
+            edge(0, 1, 1.0),
+            edge(1, 2, 1.0),
+            edge(2, 1, 1.0),
+            edge(2, 3, 1.0),
+        ];
+
+        GraphNetwork::new(&nodes, &edges);
+    }
+}
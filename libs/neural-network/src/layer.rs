@@ -1,49 +1,193 @@
+use serde::{Deserialize, Serialize};
+
+use std::convert::TryFrom;
+
+use crate::activation::Activation;
 use crate::neuron::Neuron;
+use crate::topology::Initializer;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "LayerData", into = "LayerData")]
 pub struct Layer {
-    pub(crate) neurons: Vec<Neuron>,
+    inputs: usize,
+    /// Each neuron's bias followed by its weights, one neuron after another
+    /// Keeping them all in one contiguous block means propagating only ever walks forwards through memory
+    parameters: Vec<f32>,
+    activation: Activation,
+}
+
+/// How a Layer gets saved, which is still one neuron at a time so brains saved before the weights were contiguous still load
+#[derive(Clone, Serialize, Deserialize)]
+struct LayerData {
+    neurons: Vec<Neuron>,
+    #[serde(default)]
+    activation: Activation,
+}
+
+impl TryFrom<LayerData> for Layer {
+    type Error = String;
+
+    fn try_from(data: LayerData) -> Result<Self, Self::Error> {
+        let inputs = match data.neurons.first() {
+            Some(neuron) => neuron.weights.len(),
+            None => return Err("A layer needs at least one neuron".to_string()),
+        };
+
+        if inputs == 0
+            || data
+                .neurons
+                .iter()
+                .any(|neuron| neuron.weights.len() != inputs)
+        {
+            return Err(format!(
+                "Every neuron in a layer needs the same number of weights (expected {})",
+                inputs
+            ));
+        }
+
+        Ok(Layer::with_activation(data.neurons, data.activation))
+    }
+}
+
+impl From<Layer> for LayerData {
+    fn from(layer: Layer) -> Self {
+        Self {
+            neurons: layer.neurons(),
+            activation: layer.activation,
+        }
+    }
 }
 
 impl Layer {
     /// Create a new Layer with the specified neurons
     pub fn new(neurons: Vec<Neuron>) -> Self {
+        Self::with_activation(neurons, Activation::Relu)
+    }
+
+    /// Create a new Layer with the specified neurons, which all use the specified activation function
+    pub fn with_activation(neurons: Vec<Neuron>, activation: Activation) -> Self {
         assert!(!neurons.is_empty());
 
-        Self { neurons }
+        let inputs = neurons[0].weights.len();
+        assert!(neurons.iter().all(|neuron| neuron.weights.len() == inputs));
+
+        let parameters = neurons
+            .iter()
+            .flat_map(|neuron| std::iter::once(neuron.bias).chain(neuron.weights.iter().copied()))
+            .collect();
+
+        Self {
+            inputs,
+            parameters,
+            activation,
+        }
     }
 
-    /// Create a new Layer with randomly chosen neurons
+    /// Create a new Layer with neurons randomly chosen by the initializer
     pub fn random(
         prng: &mut dyn rand::RngCore,
         input_neurons: usize,
         output_neurons: usize,
+        activation: Activation,
+        initializer: Initializer,
     ) -> Layer {
         let neurons = (0..output_neurons)
-            .map(|_| Neuron::random(prng, input_neurons))
+            .map(|_| {
+                let (bias, weights) =
+                    initializer.neuron(prng, input_neurons, output_neurons, input_neurons);
+
+                Neuron::new(bias, weights)
+            })
             .collect();
 
-        Layer { neurons }
+        Layer::with_activation(neurons, activation)
+    }
+
+    /// Create a new Layer, taking each neuron's bias and weights in turn from the provided iterator
+    pub fn from_weights(
+        input_neurons: usize,
+        output_neurons: usize,
+        activation: Activation,
+        weights: &mut dyn Iterator<Item = f32>,
+    ) -> Layer {
+        let neurons = (0..output_neurons)
+            .map(|_| Neuron::from_weights(input_neurons, weights))
+            .collect();
+
+        Layer::with_activation(neurons, activation)
     }
 
     pub fn propagate(&self, inputs: Vec<f32>) -> Vec<f32> {
-        // This can be re-written using .map()
-        // Using the .iter() method also implicitely calls Vec::with_capacity() which is nice
-        //let mut outputs = Vec::with_capacity(self.neurons.len());
-        //for neuron in &self.neurons {
-        //    let output = neuron.propagate(&inputs);
-        //    outputs.push(output);
-        //}
-        //outputs
-
-        self.neurons
-            .iter()
-            .map(|neuron| neuron.propagate(&inputs))
+        let mut outputs = vec![0.0; self.outputs()];
+        self.propagate_into(&inputs, &mut outputs);
+
+        outputs
+    }
+
+    /// Propagate the inputs, writing the output of each neuron into the provided slice instead of allocating a new Vec
+    pub fn propagate_into(&self, inputs: &[f32], outputs: &mut [f32]) {
+        // There should always be an equal number of inputs and weights (as the weights modify each input)
+        assert_eq!(inputs.len(), self.inputs);
+        assert_eq!(outputs.len(), self.outputs());
+
+        for (output, row) in outputs.iter_mut().zip(self.rows()) {
+            // Take each input, multiply it by the corresponding weight, sum all the results together, and finally add the bias
+            let sum = inputs
+                .iter()
+                .zip(&row[1..])
+                .map(|(input, weight)| input * weight)
+                .sum::<f32>();
+
+            *output = self.activation.apply(row[0] + sum);
+        }
+    }
+
+    /// Propagate a whole batch of inputs laid out one after another, writing each set of outputs one after another
+    pub fn propagate_batch_into(&self, inputs: &[f32], outputs: &mut [f32]) {
+        assert_eq!(inputs.len() % self.inputs, 0);
+        assert_eq!(inputs.len() / self.inputs * self.outputs(), outputs.len());
+
+        for (inputs, outputs) in inputs
+            .chunks_exact(self.inputs)
+            .zip(outputs.chunks_exact_mut(self.outputs()))
+        {
+            self.propagate_into(inputs, outputs);
+        }
+    }
+
+    /// Each neuron's bias followed by its weights, one neuron at a time
+    pub(crate) fn rows(&self) -> std::slice::ChunksExact<'_, f32> {
+        self.parameters.chunks_exact(self.inputs + 1)
+    }
+
+    /// Every bias and weight in the layer, in the same order as Network::weights()
+    pub fn parameters(&self) -> &[f32] {
+        &self.parameters
+    }
+
+    pub(crate) fn parameters_mut(&mut self) -> &mut [f32] {
+        &mut self.parameters
+    }
+
+    /// Copy out each neuron (the layer doesn't store them separately)
+    pub fn neurons(&self) -> Vec<Neuron> {
+        self.rows()
+            .map(|row| Neuron::new(row[0], row[1..].to_vec()))
             .collect()
     }
 
-    pub fn neurons(&self) -> &[Neuron] {
-        &self.neurons
+    /// How many inputs each neuron takes
+    pub fn inputs(&self) -> usize {
+        self.inputs
+    }
+
+    /// How many neurons (and so outputs) the layer has
+    pub fn outputs(&self) -> usize {
+        self.parameters.len() / (self.inputs + 1)
+    }
+
+    pub fn activation(&self) -> Activation {
+        self.activation
     }
 }
 
@@ -59,10 +203,10 @@ mod tests {
         let mut prng = ChaCha8Rng::from_seed(Default::default());
 
         // Roll a new random Layer with 3 input Neurons and 2 output Neurons
-        let layer = Layer::random(&mut prng, 3, 2);
+        let layer = Layer::random(&mut prng, 3, 2, Activation::Relu, Initializer::default());
 
         // Collect together the biases of each neuron in the layer
-        let actual_biases: Vec<f32> = layer.neurons.iter().map(|neuron| neuron.bias).collect();
+        let actual_biases: Vec<f32> = layer.neurons().iter().map(|neuron| neuron.bias).collect();
 
         // Given the default random seed, these should be the biases of the neurons in our layer
         let expected_biases = vec![-0.6255188, 0.5238807];
@@ -71,8 +215,8 @@ mod tests {
         approx::assert_relative_eq!(actual_biases.as_slice(), expected_biases.as_slice());
 
         // Collect together all the weights of each neuron in the layer
-        let actual_weights: Vec<&[f32]> = layer
-            .neurons
+        let neurons = layer.neurons();
+        let actual_weights: Vec<&[f32]> = neurons
             .iter()
             .map(|neuron| neuron.weights.as_slice())
             .collect();
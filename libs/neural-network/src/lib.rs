@@ -1,13 +1,19 @@
+pub mod activation;
+pub mod graph;
 mod layer;
 mod neuron;
+pub mod recurrent;
 pub mod topology;
+pub mod trace;
+pub mod training;
 
-use layer::Layer;
+pub use layer::Layer;
+pub use neuron::Neuron;
 use topology::LayerTopology;
 
-use std::iter::once;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Network {
     layers: Vec<Layer>,
 }
@@ -25,7 +31,15 @@ impl Network {
     pub fn random(prng: &mut dyn rand::RngCore, layers: &[LayerTopology]) -> Network {
         let built_layers = layers
             .windows(2)
-            .map(|layers| Layer::random(prng, layers[0].neurons, layers[1].neurons))
+            .map(|layers| {
+                Layer::random(
+                    prng,
+                    layers[0].neurons,
+                    layers[1].neurons,
+                    layers[1].activation,
+                    layers[1].initializer,
+                )
+            })
             .collect::<Vec<Layer>>();
 
         Network {
@@ -33,42 +47,136 @@ impl Network {
         }
     }
 
+    /// Rebuild a Network with the specified layers from a flat list of weights (e.g. a Chromosome)
+    /// The weights must be in the same order as the ones returned by Network::weights()
+    pub fn from_weights(
+        layers: &[LayerTopology],
+        weights: impl IntoIterator<Item = f32>,
+    ) -> Network {
+        let mut weights = weights.into_iter();
+
+        let built_layers = layers
+            .windows(2)
+            .map(|layers| {
+                Layer::from_weights(
+                    layers[0].neurons,
+                    layers[1].neurons,
+                    layers[1].activation,
+                    &mut weights,
+                )
+            })
+            .collect();
+
+        // Leftover weights mean the layers don't match whatever the weights came from
+        assert!(weights.next().is_none(), "Too many weights for the layers!");
+
+        Network {
+            layers: built_layers,
+        }
+    }
+
     pub fn propagate(&self, inputs: Vec<f32>) -> Vec<f32> {
-        // Could inputs be &[f32] instead of Vec<f32> ???
         // For each layer in self.layers, set inputs to the result of calling layer.propogate(inputs)
+        // This allocates a new Vec for every layer - use propagate_into() to avoid that when propagating over and over again
         self.layers
             .iter()
             .fold(inputs, |inputs, layer| layer.propagate(inputs))
     }
 
+    /// Propagate the inputs, writing the outputs into the provided slice
+    /// The values passed between layers are kept in the scratch buffers, so once they've grown big enough this never allocates
+    pub fn propagate_into(&self, inputs: &[f32], outputs: &mut [f32], scratch: &mut Scratch) {
+        self.propagate_batch(inputs, outputs, scratch);
+    }
+
+    /// Propagate a whole batch of inputs laid out one after another (e.g. the eyes of every AI player in a generation), writing each set of outputs one after another
+    pub fn propagate_batch(&self, inputs: &[f32], outputs: &mut [f32], scratch: &mut Scratch) {
+        let batch = inputs.len() / self.inputs();
+        assert_eq!(batch * self.inputs(), inputs.len());
+        assert_eq!(batch * self.outputs(), outputs.len());
+
+        let (last, hidden) = self.layers.split_last().expect("A network needs layers!");
+        let Scratch { current, next } = scratch;
+
+        let mut layer_inputs = inputs;
+        for layer in hidden {
+            next.resize(batch * layer.outputs(), 0.0);
+            layer.propagate_batch_into(layer_inputs, next);
+
+            std::mem::swap(current, next);
+            layer_inputs = current;
+        }
+
+        last.propagate_batch_into(layer_inputs, outputs);
+    }
+
     pub fn layers(&self) -> &[Layer] {
         &self.layers
     }
 
+    /// Return every bias and weight in the network, one neuron at a time with each bias coming before its weights
     pub fn weights(&self) -> impl Iterator<Item = f32> + '_ {
         self.layers
             .iter()
-            .flat_map(|layer| layer.neurons.iter())
-            .flat_map(|neuron| once(neuron.bias).chain(neuron.weights.iter().cloned()))
+            .flat_map(|layer| layer.parameters().iter().copied())
+    }
+
+    /// Overwrite every bias and weight in place, taking them in the same order as the ones returned by Network::weights()
+    pub fn set_weights(&mut self, weights: &[f32]) {
+        let count: usize = self
+            .layers
+            .iter()
+            .map(|layer| layer.parameters().len())
+            .sum();
+        assert_eq!(
+            weights.len(),
+            count,
+            "Wrong number of weights for the network!"
+        );
+
+        let mut offset = 0;
+        for layer in self.layers.iter_mut() {
+            let parameters = layer.parameters_mut();
+            parameters.copy_from_slice(&weights[offset..offset + parameters.len()]);
+            offset += parameters.len();
+        }
+    }
+
+    /// How many inputs the network takes
+    pub fn inputs(&self) -> usize {
+        self.layers[0].inputs()
+    }
+
+    /// How many outputs the network produces
+    pub fn outputs(&self) -> usize {
+        self.layers[self.layers.len() - 1].outputs()
     }
 }
 
+/// Reusable buffers for the values passed between the layers of a Network
+/// Keep one of these around and pass it to Network::propagate_into() every time, rather than allocating new buffers on every call
+#[derive(Clone, Debug, Default)]
+pub struct Scratch {
+    current: Vec<f32>,
+    next: Vec<f32>,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use rand::SeedableRng;
     use rand_chacha::ChaCha8Rng;
 
-    use neuron::Neuron;
+    use activation::Activation;
 
     #[test]
     fn random_network_creation() {
         // Seed a ChaCha8Rng for a predictable "random" number to use for testing
         let mut prng = ChaCha8Rng::from_seed(Default::default());
 
-        let layer1 = LayerTopology { neurons: 3 };
-        let layer2 = LayerTopology { neurons: 2 };
-        let layer3 = LayerTopology { neurons: 1 };
+        let layer1 = LayerTopology::new(3, Activation::Relu);
+        let layer2 = LayerTopology::new(2, Activation::Relu);
+        let layer3 = LayerTopology::new(1, Activation::Tanh);
 
         // Roll a new Network with randomly chosen Neuron values in each layer
         let network = Network::random(&mut prng, &[layer1, layer2, layer3]);
@@ -79,49 +187,47 @@ mod tests {
         assert_eq!(network.layers.len(), 2);
 
         // The first layer should have 2 neurons
-        assert_eq!(network.layers[0].neurons.len(), 2);
+        assert_eq!(network.layers[0].neurons().len(), 2);
 
         // The second layer should have 1 neuron
-        assert_eq!(network.layers[1].neurons.len(), 1);
+        assert_eq!(network.layers[1].neurons().len(), 1);
+
+        // Each layer should use the activation function of the LayerTopology that describes its outputs
+        assert_eq!(network.layers[0].activation(), Activation::Relu);
+        assert_eq!(network.layers[1].activation(), Activation::Tanh);
+
+        let first = network.layers[0].neurons();
+        let second = network.layers[1].neurons();
 
         // Check the bias of the first neuron of the first layer
-        approx::assert_relative_eq!(network.layers[0].neurons[0].bias, -0.6255188);
+        approx::assert_relative_eq!(first[0].bias, -0.6255188);
 
         // Check the number of weights for the first neuron of the first layer (this is the same as the number of inputs it takes)
-        assert_eq!(network.layers[0].neurons[0].weights.len(), 3);
+        assert_eq!(first[0].weights.len(), 3);
 
         // Check the weights of the first neuron of the first layer
         let expected_weights = vec![0.67383957, 0.8181262, 0.26284897];
-        approx::assert_relative_eq!(
-            network.layers[0].neurons[0].weights.as_slice(),
-            expected_weights.as_slice()
-        );
+        approx::assert_relative_eq!(first[0].weights.as_slice(), expected_weights.as_slice());
 
         // Check the bias of the second neuron of the first layer
-        approx::assert_relative_eq!(network.layers[0].neurons[1].bias, 0.5238807);
+        approx::assert_relative_eq!(first[1].bias, 0.5238807);
 
         // Check the number of weights for the second neuron of the first layer (this is the same as the number of inputs it takes)
-        assert_eq!(network.layers[0].neurons[1].weights.len(), 3);
+        assert_eq!(first[1].weights.len(), 3);
 
         // Check the weights of the first neuron of the first layer
         let expected_weights = vec![-0.53516835, 0.069369674, -0.7648182];
-        approx::assert_relative_eq!(
-            network.layers[0].neurons[1].weights.as_slice(),
-            expected_weights.as_slice()
-        );
+        approx::assert_relative_eq!(first[1].weights.as_slice(), expected_weights.as_slice());
 
         // Check the bias of the only neuron of the second layer
-        approx::assert_relative_eq!(network.layers[1].neurons[0].bias, -0.102499366);
+        approx::assert_relative_eq!(second[0].bias, -0.102499366);
 
         // Check the number of weights for the only neuron of the second layer (this is the same as the number of inputs it takes)
-        assert_eq!(network.layers[1].neurons[0].weights.len(), 2);
+        assert_eq!(second[0].weights.len(), 2);
 
         // Check the weights of the only neuron of the second layer
         let expected_weights = vec![-0.48879617, -0.19277132];
-        approx::assert_relative_eq!(
-            network.layers[1].neurons[0].weights.as_slice(),
-            expected_weights.as_slice()
-        );
+        approx::assert_relative_eq!(second[0].weights.as_slice(), expected_weights.as_slice());
     }
 
     #[test]
@@ -167,4 +273,63 @@ mod tests {
 
         approx::assert_relative_eq!(weights.as_slice(), expected.as_slice());
     }
+
+    #[test]
+    fn network_from_weights() {
+        // Seed a ChaCha8Rng for a predictable "random" number to use for testing
+        let mut prng = ChaCha8Rng::from_seed(Default::default());
+
+        let topology = [
+            LayerTopology::new(3, Activation::Relu),
+            LayerTopology::new(2, Activation::Relu),
+            LayerTopology::new(1, Activation::Tanh),
+        ];
+
+        // Flattening a network into weights and back again should give the same network
+        let network = Network::random(&mut prng, &topology);
+        let rebuilt = Network::from_weights(&topology, network.weights());
+
+        let expected: Vec<f32> = network.weights().collect();
+        let actual: Vec<f32> = rebuilt.weights().collect();
+
+        approx::assert_relative_eq!(actual.as_slice(), expected.as_slice());
+        assert_eq!(rebuilt.layers[1].activation(), Activation::Tanh);
+
+        // ...and it should still think the same way
+        let inputs = vec![0.5, -0.6, 0.7];
+        let expected = network.propagate(inputs.clone());
+        let actual = rebuilt.propagate(inputs);
+
+        approx::assert_relative_eq!(actual.as_slice(), expected.as_slice());
+    }
+
+    #[test]
+    fn propagation_without_allocating() {
+        // Seed a ChaCha8Rng for a predictable "random" number to use for testing
+        let mut prng = ChaCha8Rng::from_seed(Default::default());
+
+        let topology = [
+            LayerTopology::new(3, Activation::Relu),
+            LayerTopology::new(4, Activation::Tanh),
+            LayerTopology::new(2, Activation::Linear),
+        ];
+        let network = Network::random(&mut prng, &topology);
+        let mut scratch = Scratch::default();
+
+        // Propagating into a slice should give exactly the same outputs as allocating them
+        let inputs = [0.5, -0.6, 0.7];
+        let mut outputs = [0.0; 2];
+        network.propagate_into(&inputs, &mut outputs, &mut scratch);
+
+        assert_eq!(outputs.to_vec(), network.propagate(inputs.to_vec()));
+
+        // ...and so should propagating a whole batch at once, with the same scratch buffers
+        let batch = [0.5, -0.6, 0.7, 0.1, 0.2, 0.3, -1.0, 0.0, 1.0];
+        let mut outputs = [0.0; 6];
+        network.propagate_batch(&batch, &mut outputs, &mut scratch);
+
+        for (inputs, outputs) in batch.chunks(3).zip(outputs.chunks(2)) {
+            assert_eq!(outputs.to_vec(), network.propagate(inputs.to_vec()));
+        }
+    }
 }
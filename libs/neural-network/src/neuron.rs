@@ -1,8 +1,10 @@
 use core::f32;
 
+use crate::activation::Activation;
 use rand::Rng;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Neuron {
     pub(crate) bias: f32,
     pub(crate) weights: Vec<f32>,
@@ -32,8 +34,25 @@ impl Neuron {
         Neuron { bias, weights }
     }
 
+    /// Create a new Neuron, taking its bias and then its weights from the provided iterator
+    pub fn from_weights(output_size: usize, weights: &mut dyn Iterator<Item = f32>) -> Neuron {
+        let bias = weights.next().expect("Not enough weights for the bias!");
+
+        let weights = (0..output_size)
+            .map(|_| weights.next().expect("Not enough weights for the neuron!"))
+            .collect();
+
+        Neuron { bias, weights }
+    }
+
     /// Combine the inputs and propogate the output
     pub fn propagate(&self, inputs: &[f32]) -> f32 {
+        // Return whichever is the bigger of the weighted sum or 0.0
+        Activation::Relu.apply(self.weighted_sum(inputs))
+    }
+
+    /// Combine the inputs and the bias, without applying any activation function
+    pub fn weighted_sum(&self, inputs: &[f32]) -> f32 {
         // There should always be an equal number of inputs and weights (as the weights modify each input)
         assert_eq!(inputs.len(), self.weights.len());
 
@@ -56,11 +75,8 @@ impl Neuron {
             .map(|(input, weight)| input * weight)
             .sum::<f32>();
 
-        //output += self.bias;
-        //output.max(0.0) // Return the whatever is the largest of output and 0.0
-
-        // Finally, add the bias to the sum, and return whichever is the bigger of that value or 0.0
-        (self.bias + output).max(0.0)
+        // Finally, add the bias to the sum
+        self.bias + output
     }
 
     pub fn bias(&self) -> f32 {
@@ -0,0 +1,482 @@
+// Recurrent networks
+// Every hidden neuron also gets fed what the layer output last time it propagated, so the network can remember things between steps (e.g. how far the ball moved since the last frame)
+// The weights are all kept in the layers, while the memory lives in a separate State, so the same network can be shared by anything that needs its own memory
+
+use serde::{Deserialize, Serialize};
+
+use std::str::FromStr;
+
+use crate::activation::Activation;
+use crate::layer::Layer;
+use crate::topology::{Initializer, LayerTopology};
+
+/// How a recurrent layer combines its inputs with its memory
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Cell {
+    /// A simple (Elman) RNN: h = activation(bias + W.x + U.h)
+    Elman,
+    /// A gated recurrent unit, which learns when to keep its memory and when to overwrite it
+    Gru,
+}
+
+impl Cell {
+    /// How many sets of neurons each output needs - a GRU has an update gate and a reset gate as well as the candidate output
+    fn gates(&self) -> usize {
+        match self {
+            Self::Elman => 1,
+            Self::Gru => 3,
+        }
+    }
+}
+
+impl FromStr for Cell {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "elman" | "rnn" => Ok(Self::Elman),
+            "gru" => Ok(Self::Gru),
+            _ => Err(format!(
+                "Unknown recurrent cell '{}' (expected elman or gru)",
+                s
+            )),
+        }
+    }
+}
+
+/// A fully connected layer that also feeds its previous outputs back in to itself
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RecurrentLayer {
+    inputs: usize,
+    outputs: usize,
+    cell: Cell,
+    activation: Activation,
+    /// Each neuron's bias, then its weights for the inputs, then its weights for the previous outputs, one neuron after another
+    /// A GRU has all of its update gate neurons first, then its reset gate neurons, then its candidate neurons
+    parameters: Vec<f32>,
+}
+
+impl RecurrentLayer {
+    /// Create a new RecurrentLayer with biases and weights randomly chosen by the initializer
+    /// The previous outputs count as inputs too, as far as the initializer is concerned
+    pub fn random(
+        prng: &mut dyn rand::RngCore,
+        cell: Cell,
+        input_neurons: usize,
+        output_neurons: usize,
+        activation: Activation,
+        initializer: Initializer,
+    ) -> Self {
+        let fan_in = input_neurons + output_neurons;
+        let parameters = (0..cell.gates() * output_neurons)
+            .flat_map(|_| {
+                let (bias, weights) = initializer.neuron(prng, fan_in, output_neurons, fan_in);
+
+                std::iter::once(bias).chain(weights)
+            })
+            .collect();
+
+        Self::new(cell, input_neurons, output_neurons, activation, parameters)
+    }
+
+    /// Create a new RecurrentLayer, taking every bias and weight in turn from the provided iterator
+    pub fn from_weights(
+        cell: Cell,
+        input_neurons: usize,
+        output_neurons: usize,
+        activation: Activation,
+        weights: &mut dyn Iterator<Item = f32>,
+    ) -> Self {
+        let count = Self::parameter_count(cell, input_neurons, output_neurons);
+        let parameters: Vec<f32> = weights.take(count).collect();
+        assert_eq!(parameters.len(), count, "Not enough weights for the layer!");
+
+        Self::new(cell, input_neurons, output_neurons, activation, parameters)
+    }
+
+    fn new(
+        cell: Cell,
+        inputs: usize,
+        outputs: usize,
+        activation: Activation,
+        parameters: Vec<f32>,
+    ) -> Self {
+        assert!(inputs > 0 && outputs > 0);
+
+        Self {
+            inputs,
+            outputs,
+            cell,
+            activation,
+            parameters,
+        }
+    }
+
+    /// How many biases and weights a layer of this shape needs
+    fn parameter_count(cell: Cell, inputs: usize, outputs: usize) -> usize {
+        cell.gates() * outputs * (1 + inputs + outputs)
+    }
+
+    /// Propagate the inputs, updating the layer's memory (which is also its output) in place
+    /// The gates buffer is only used to hold values part way through, so it can be reused every time
+    pub fn step(&self, inputs: &[f32], hidden: &mut [f32], gates: &mut Vec<f32>) {
+        assert_eq!(inputs.len(), self.inputs);
+        assert_eq!(hidden.len(), self.outputs);
+
+        let mut rows = self.parameters.chunks_exact(1 + self.inputs + self.outputs);
+        gates.resize(self.cell.gates() * self.outputs, 0.0);
+
+        match self.cell {
+            Cell::Elman => {
+                // Every neuron needs to see the old memory, so nothing can be overwritten until they're all done
+                for (gate, row) in gates.iter_mut().zip(rows) {
+                    *gate = self
+                        .activation
+                        .apply(weighted_sum(row, self.inputs, inputs, hidden));
+                }
+
+                hidden.copy_from_slice(gates);
+            }
+            Cell::Gru => {
+                let (update, rest) = gates.split_at_mut(self.outputs);
+                let (reset, candidate) = rest.split_at_mut(self.outputs);
+
+                // How much of the old memory to keep
+                for (gate, row) in update.iter_mut().zip(rows.by_ref()) {
+                    *gate = sigmoid(weighted_sum(row, self.inputs, inputs, hidden));
+                }
+
+                // How much of the old memory the candidate gets to see (kept here already multiplied by the memory)
+                for ((gate, row), old) in reset.iter_mut().zip(rows.by_ref()).zip(hidden.iter()) {
+                    *gate = sigmoid(weighted_sum(row, self.inputs, inputs, hidden)) * old;
+                }
+
+                // What the memory would become if it were overwritten completely
+                for (gate, row) in candidate.iter_mut().zip(rows) {
+                    *gate = self
+                        .activation
+                        .apply(weighted_sum(row, self.inputs, inputs, reset));
+                }
+
+                for ((old, update), candidate) in
+                    hidden.iter_mut().zip(update.iter()).zip(candidate.iter())
+                {
+                    *old = (1.0 - update) * candidate + update * *old;
+                }
+            }
+        }
+    }
+
+    /// The weights each output gives the layer's inputs (not its memory), one output at a time
+    /// For a GRU these are the candidate's weights, since that's what decides what gets written into the memory
+    pub fn input_weights(&self) -> impl Iterator<Item = &[f32]> + '_ {
+        let row_length = 1 + self.inputs + self.outputs;
+        let candidates = (self.cell.gates() - 1) * self.outputs * row_length;
+
+        self.parameters[candidates..]
+            .chunks_exact(row_length)
+            .map(move |row| &row[1..=self.inputs])
+    }
+
+    /// Every bias and weight in the layer, in the same order as RecurrentNetwork::weights()
+    pub fn parameters(&self) -> &[f32] {
+        &self.parameters
+    }
+
+    pub fn cell(&self) -> Cell {
+        self.cell
+    }
+
+    /// How many inputs each neuron takes (not counting the previous outputs fed back in)
+    pub fn inputs(&self) -> usize {
+        self.inputs
+    }
+
+    /// How many outputs the layer has, which is also how much it can remember
+    pub fn outputs(&self) -> usize {
+        self.outputs
+    }
+
+    pub fn activation(&self) -> Activation {
+        self.activation
+    }
+}
+
+/// Add the bias to the inputs and memory multiplied by their weights
+fn weighted_sum(row: &[f32], inputs_count: usize, inputs: &[f32], hidden: &[f32]) -> f32 {
+    let (input_weights, hidden_weights) = row[1..].split_at(inputs_count);
+
+    let inputs: f32 = inputs
+        .iter()
+        .zip(input_weights)
+        .map(|(input, weight)| input * weight)
+        .sum();
+    let hidden: f32 = hidden
+        .iter()
+        .zip(hidden_weights)
+        .map(|(hidden, weight)| hidden * weight)
+        .sum();
+
+    row[0] + inputs + hidden
+}
+
+/// Squash x into 0..=1, for the GRU gates
+fn sigmoid(x: f32) -> f32 {
+    1.0 / (1.0 + (-x).exp())
+}
+
+/// What a RecurrentNetwork remembers from one step to the next
+/// This starts off (and resets back to) all zeros, as if nothing had been seen yet
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct State {
+    hidden: Vec<Vec<f32>>,
+    gates: Vec<f32>,
+}
+
+impl State {
+    /// Forget everything (e.g. when a new ball gets served)
+    pub fn reset(&mut self) {
+        for hidden in self.hidden.iter_mut() {
+            hidden.iter_mut().for_each(|value| *value = 0.0);
+        }
+    }
+
+    /// What each recurrent layer output last time, which is what it will remember next time
+    pub fn hidden(&self) -> &[Vec<f32>] {
+        &self.hidden
+    }
+}
+
+/// One or more recurrent layers, followed by an ordinary fully connected layer that reads out the final memory
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RecurrentNetwork {
+    recurrent: Vec<RecurrentLayer>,
+    readout: Layer,
+}
+
+impl RecurrentNetwork {
+    /// Generate a new RecurrentNetwork with randomly selected biases and weights
+    /// Just like Network::random(), the first LayerTopology is the inputs - every other one except the last becomes a recurrent layer
+    pub fn random(prng: &mut dyn rand::RngCore, cell: Cell, layers: &[LayerTopology]) -> Self {
+        assert!(
+            layers.len() >= 3,
+            "A recurrent network needs a hidden layer!"
+        );
+
+        let (last, rest) = layers.split_last().unwrap();
+
+        let recurrent = rest
+            .windows(2)
+            .map(|layers| {
+                RecurrentLayer::random(
+                    prng,
+                    cell,
+                    layers[0].neurons,
+                    layers[1].neurons,
+                    layers[1].activation,
+                    layers[1].initializer,
+                )
+            })
+            .collect();
+
+        let readout = Layer::random(
+            prng,
+            rest[rest.len() - 1].neurons,
+            last.neurons,
+            last.activation,
+            last.initializer,
+        );
+
+        Self { recurrent, readout }
+    }
+
+    /// Rebuild a RecurrentNetwork with the specified layers from a flat list of weights (e.g. a Chromosome)
+    /// The weights must be in the same order as the ones returned by RecurrentNetwork::weights()
+    pub fn from_weights(
+        cell: Cell,
+        layers: &[LayerTopology],
+        weights: impl IntoIterator<Item = f32>,
+    ) -> Self {
+        assert!(
+            layers.len() >= 3,
+            "A recurrent network needs a hidden layer!"
+        );
+
+        let mut weights = weights.into_iter();
+        let (last, rest) = layers.split_last().unwrap();
+
+        let recurrent = rest
+            .windows(2)
+            .map(|layers| {
+                RecurrentLayer::from_weights(
+                    cell,
+                    layers[0].neurons,
+                    layers[1].neurons,
+                    layers[1].activation,
+                    &mut weights,
+                )
+            })
+            .collect();
+
+        let readout = Layer::from_weights(
+            rest[rest.len() - 1].neurons,
+            last.neurons,
+            last.activation,
+            &mut weights,
+        );
+
+        // Leftover weights mean the layers don't match whatever the weights came from
+        assert!(weights.next().is_none(), "Too many weights for the layers!");
+
+        Self { recurrent, readout }
+    }
+
+    /// Create a blank memory the right shape for this network
+    pub fn state(&self) -> State {
+        State {
+            hidden: self
+                .recurrent
+                .iter()
+                .map(|layer| vec![0.0; layer.outputs()])
+                .collect(),
+            gates: Vec::new(),
+        }
+    }
+
+    /// Propagate the inputs, remembering whatever the network needs to in the provided state
+    pub fn propagate(&self, inputs: &[f32], state: &mut State) -> Vec<f32> {
+        let mut outputs = vec![0.0; self.outputs()];
+        self.propagate_into(inputs, &mut outputs, state);
+
+        outputs
+    }
+
+    /// Propagate the inputs, writing the outputs into the provided slice instead of allocating a new Vec
+    pub fn propagate_into(&self, inputs: &[f32], outputs: &mut [f32], state: &mut State) {
+        assert_eq!(
+            state.hidden.len(),
+            self.recurrent.len(),
+            "The state belongs to a different network!"
+        );
+
+        for (index, layer) in self.recurrent.iter().enumerate() {
+            // Each layer reads what the layer before it has just output
+            let (before, after) = state.hidden.split_at_mut(index);
+            let layer_inputs = before.last().map_or(inputs, |hidden| hidden.as_slice());
+
+            layer.step(layer_inputs, &mut after[0], &mut state.gates);
+        }
+
+        self.readout
+            .propagate_into(state.hidden.last().unwrap(), outputs);
+    }
+
+    pub fn recurrent_layers(&self) -> &[RecurrentLayer] {
+        &self.recurrent
+    }
+
+    pub fn readout(&self) -> &Layer {
+        &self.readout
+    }
+
+    pub fn inputs(&self) -> usize {
+        self.recurrent[0].inputs()
+    }
+
+    pub fn outputs(&self) -> usize {
+        self.readout.outputs()
+    }
+
+    /// Return every bias and weight in the network, layer by layer, so it can be evolved just like a Network
+    pub fn weights(&self) -> impl Iterator<Item = f32> + '_ {
+        self.recurrent
+            .iter()
+            .flat_map(|layer| layer.parameters().iter().copied())
+            .chain(self.readout.parameters().iter().copied())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    fn topology(hidden: usize) -> [LayerTopology; 3] {
+        [
+            LayerTopology::new(3, Activation::Relu),
+            LayerTopology::new(hidden, Activation::Tanh),
+            LayerTopology::new(2, Activation::Linear),
+        ]
+    }
+
+    #[test]
+    fn elman_memory() {
+        // One linear neuron that adds its input to half of what it remembers, read out as-is
+        let layers = [
+            LayerTopology::new(1, Activation::Relu),
+            LayerTopology::new(1, Activation::Linear),
+            LayerTopology::new(1, Activation::Linear),
+        ];
+        let network =
+            RecurrentNetwork::from_weights(Cell::Elman, &layers, vec![0.0, 1.0, 0.5, 0.0, 1.0]);
+        let mut state = network.state();
+
+        approx::assert_relative_eq!(network.propagate(&[1.0], &mut state)[0], 1.0);
+        approx::assert_relative_eq!(network.propagate(&[0.0], &mut state)[0], 0.5);
+        approx::assert_relative_eq!(network.propagate(&[0.0], &mut state)[0], 0.25);
+
+        // Once the state is reset, it's as if the first input was never seen
+        state.reset();
+        approx::assert_relative_eq!(network.propagate(&[0.0], &mut state)[0], 0.0);
+    }
+
+    #[test]
+    fn weights_round_trip() {
+        // Seed a ChaCha8Rng for a predictable "random" number to use for testing
+        let mut prng = ChaCha8Rng::from_seed(Default::default());
+
+        for (cell, gates) in [(Cell::Elman, 1), (Cell::Gru, 3)] {
+            let network = RecurrentNetwork::random(&mut prng, cell, &topology(4));
+
+            // Each hidden neuron (and gate) has a bias, 3 input weights and 4 recurrent weights, then the readout has a bias and 4 weights per output
+            let weights: Vec<f32> = network.weights().collect();
+            assert_eq!(weights.len(), gates * 4 * (1 + 3 + 4) + 2 * (1 + 4));
+
+            let rebuilt = RecurrentNetwork::from_weights(cell, &topology(4), weights.clone());
+            assert_eq!(rebuilt.weights().collect::<Vec<f32>>(), weights);
+
+            // Both copies should respond the same way to the same sequence of inputs
+            let mut state = network.state();
+            let mut rebuilt_state = rebuilt.state();
+            for inputs in [[0.5, -0.5, 0.1], [0.2, 0.3, -0.9]].iter() {
+                let outputs = network.propagate(inputs, &mut state);
+                let rebuilt_outputs = rebuilt.propagate(inputs, &mut rebuilt_state);
+                approx::assert_relative_eq!(outputs.as_slice(), rebuilt_outputs.as_slice());
+            }
+        }
+    }
+
+    #[test]
+    fn gru_memory() {
+        // Seed a ChaCha8Rng for a predictable "random" number to use for testing
+        let mut prng = ChaCha8Rng::from_seed(Default::default());
+
+        let network = RecurrentNetwork::random(&mut prng, Cell::Gru, &topology(4));
+        let mut state = network.state();
+
+        // The same inputs should give a different response once something has been remembered
+        let first = network.propagate(&[0.5, -0.5, 0.1], &mut state);
+        let second = network.propagate(&[0.5, -0.5, 0.1], &mut state);
+        assert!(first.iter().zip(&second).any(|(a, b)| (a - b).abs() > 1e-6));
+
+        // Every remembered value is a blend of tanh outputs, so it stays within -1..=1
+        assert!(state.hidden()[0]
+            .iter()
+            .all(|value| (-1.0..=1.0).contains(value)));
+
+        state.reset();
+        let again = network.propagate(&[0.5, -0.5, 0.1], &mut state);
+        approx::assert_relative_eq!(again.as_slice(), first.as_slice());
+    }
+}
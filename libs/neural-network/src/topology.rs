@@ -1,4 +1,190 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use std::str::FromStr;
+
+use crate::activation::Activation;
+
 #[derive(Debug)]
 pub struct LayerTopology {
     pub neurons: usize,
+    pub activation: Activation,
+    /// How this layer's biases and weights get picked when a network is randomly generated (this is ignored for the input layer)
+    pub initializer: Initializer,
+}
+
+impl LayerTopology {
+    /// Describe a layer with the specified number of neurons and activation function, initialized the same way networks always have been
+    pub fn new(neurons: usize, activation: Activation) -> Self {
+        Self {
+            neurons,
+            activation,
+            initializer: Initializer::default(),
+        }
+    }
+
+    /// Use the specified initializer for this layer
+    pub fn with_initializer(self, initializer: Initializer) -> Self {
+        Self {
+            initializer,
+            ..self
+        }
+    }
+}
+
+/// Where a layer's random weights get drawn from
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Distribution {
+    /// Uniformly from -range..=range, however wide the layer is
+    Uniform(f32),
+    /// Xavier/Glorot: uniformly from ±sqrt(6 / (inputs + outputs)), which keeps the variance steady through tanh and linear layers
+    Xavier,
+    /// He/Kaiming: uniformly from ±sqrt(6 / inputs), which makes up for ReLU cutting off half of everything
+    He,
+}
+
+/// How a layer's biases and weights get picked when a network is randomly generated
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Initializer {
+    pub weights: Distribution,
+    /// Start every bias at 0.0, instead of drawing them from the same distribution as the weights
+    pub zero_bias: bool,
+}
+
+impl Default for Initializer {
+    /// Biases and weights all drawn from -1.0..=1.0
+    fn default() -> Self {
+        Self::uniform(1.0)
+    }
+}
+
+impl Initializer {
+    pub fn uniform(range: f32) -> Self {
+        Self {
+            weights: Distribution::Uniform(range),
+            zero_bias: false,
+        }
+    }
+
+    pub fn xavier() -> Self {
+        Self {
+            weights: Distribution::Xavier,
+            zero_bias: false,
+        }
+    }
+
+    pub fn he() -> Self {
+        Self {
+            weights: Distribution::He,
+            zero_bias: false,
+        }
+    }
+
+    /// Start every bias at 0.0
+    pub fn with_zero_bias(self) -> Self {
+        Self {
+            zero_bias: true,
+            ..self
+        }
+    }
+
+    /// How far either side of zero the weights can be for a layer with the specified number of inputs and outputs
+    pub fn range(&self, inputs: usize, outputs: usize) -> f32 {
+        match self.weights {
+            Distribution::Uniform(range) => range,
+            Distribution::Xavier => (6.0 / (inputs + outputs) as f32).sqrt(),
+            Distribution::He => (6.0 / inputs as f32).sqrt(),
+        }
+    }
+
+    /// Pick a neuron's bias, followed by each of its weights
+    /// This always takes the bias first so the same seed keeps giving the same networks
+    pub fn neuron(
+        &self,
+        prng: &mut dyn rand::RngCore,
+        inputs: usize,
+        outputs: usize,
+        weights: usize,
+    ) -> (f32, Vec<f32>) {
+        let range = self.range(inputs, outputs);
+
+        let bias = if self.zero_bias {
+            0.0
+        } else {
+            prng.gen_range(-range..=range)
+        };
+
+        let weights = (0..weights)
+            .map(|_| prng.gen_range(-range..=range))
+            .collect();
+
+        (bias, weights)
+    }
+}
+
+impl FromStr for Initializer {
+    type Err = String;
+
+    /// Parse uniform (or uniform:<range>), xavier or he, with an optional +zero-bias on the end
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, zero_bias) = match s.strip_suffix("+zero-bias") {
+            Some(name) => (name, true),
+            None => (s, false),
+        };
+
+        let initializer = match name {
+            "uniform" => Self::uniform(1.0),
+            "xavier" | "glorot" => Self::xavier(),
+            "he" | "kaiming" => Self::he(),
+            _ => match name.strip_prefix("uniform:").map(str::parse::<f32>) {
+                Some(Ok(range)) if range > 0.0 => Self::uniform(range),
+                _ => {
+                    return Err(format!(
+                        "Unknown initializer '{}' (expected uniform, uniform:<range>, xavier or he, optionally followed by +zero-bias)",
+                        s
+                    ))
+                }
+            },
+        };
+
+        Ok(Self {
+            zero_bias,
+            ..initializer
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    #[test]
+    fn initializers() {
+        // Seed a ChaCha8Rng for a predictable "random" number to use for testing
+        let mut prng = ChaCha8Rng::from_seed(Default::default());
+
+        approx::assert_relative_eq!(Initializer::uniform(0.5).range(10, 10), 0.5);
+        approx::assert_relative_eq!(Initializer::xavier().range(4, 2), 1.0);
+        approx::assert_relative_eq!(Initializer::he().range(6, 100), 1.0);
+
+        // Wider layers get smaller weights
+        let (_, weights) = Initializer::he().neuron(&mut prng, 600, 1, 600);
+        assert!(weights.iter().all(|weight| weight.abs() <= 0.1));
+
+        let (bias, _) = Initializer::xavier()
+            .with_zero_bias()
+            .neuron(&mut prng, 3, 3, 3);
+        approx::assert_relative_eq!(bias, 0.0);
+
+        assert_eq!("uniform".parse(), Ok(Initializer::default()));
+        assert_eq!("uniform:0.25".parse(), Ok(Initializer::uniform(0.25)));
+        assert_eq!(
+            "he+zero-bias".parse(),
+            Ok(Initializer::he().with_zero_bias())
+        );
+        assert!("uniform:-1".parse::<Initializer>().is_err());
+        assert!("lecun".parse::<Initializer>().is_err());
+    }
 }
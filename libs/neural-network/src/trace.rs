@@ -0,0 +1,155 @@
+// Activation traces
+// Everything that happened inside a network while it propagated one set of inputs, for debugging, drawing, backpropagation, or spotting neurons that have stopped doing anything
+
+use crate::activation::Activation;
+use crate::layer::Layer;
+use crate::Network;
+
+/// What one layer worked out while propagating
+#[derive(Clone, Debug, PartialEq)]
+pub struct LayerTrace {
+    /// Each neuron's weighted sum of its inputs plus its bias, before the activation function
+    pub pre_activations: Vec<f32>,
+    /// Each neuron's output, after the activation function
+    pub post_activations: Vec<f32>,
+}
+
+/// What every layer in a network worked out while propagating one set of inputs
+#[derive(Clone, Debug, PartialEq)]
+pub struct Trace {
+    pub inputs: Vec<f32>,
+    pub layers: Vec<LayerTrace>,
+}
+
+impl Trace {
+    /// What the network output (the post-activations of the last layer)
+    pub fn outputs(&self) -> &[f32] {
+        &self.layers[self.layers.len() - 1].post_activations
+    }
+
+    /// What went into the specified layer (the network's inputs for the first layer, or the outputs of the layer before)
+    pub fn layer_inputs(&self, layer: usize) -> &[f32] {
+        match layer {
+            0 => &self.inputs,
+            _ => &self.layers[layer - 1].post_activations,
+        }
+    }
+}
+
+impl Layer {
+    /// Propagate the inputs, keeping hold of each neuron's weighted sum as well as its output
+    pub fn propagate_with_trace(&self, inputs: &[f32]) -> LayerTrace {
+        assert_eq!(inputs.len(), self.inputs());
+
+        let pre_activations: Vec<f32> = self
+            .rows()
+            .map(|row| {
+                row[0]
+                    + inputs
+                        .iter()
+                        .zip(&row[1..])
+                        .map(|(input, weight)| input * weight)
+                        .sum::<f32>()
+            })
+            .collect();
+
+        let post_activations = pre_activations
+            .iter()
+            .map(|sum| self.activation().apply(*sum))
+            .collect();
+
+        LayerTrace {
+            pre_activations,
+            post_activations,
+        }
+    }
+}
+
+impl Network {
+    /// Propagate the inputs just like Network::propagate(), but return every layer's pre- and post-activation values along with the outputs
+    pub fn propagate_with_trace(&self, inputs: Vec<f32>) -> Trace {
+        let mut layers: Vec<LayerTrace> = Vec::with_capacity(self.layers().len());
+
+        for layer in self.layers() {
+            let trace = match layers.last() {
+                Some(previous) => layer.propagate_with_trace(&previous.post_activations),
+                None => layer.propagate_with_trace(&inputs),
+            };
+
+            layers.push(trace);
+        }
+
+        Trace { inputs, layers }
+    }
+
+    /// Find every ReLU neuron that outputs nothing for all of the provided inputs, as (layer, neuron) pairs
+    /// A dead ReLU has a flat slope too, so backpropagation can't bring it back to life - only mutation can
+    pub fn dead_neurons(&self, inputs: &[Vec<f32>]) -> Vec<(usize, usize)> {
+        let traces: Vec<Trace> = inputs
+            .iter()
+            .map(|inputs| self.propagate_with_trace(inputs.clone()))
+            .collect();
+
+        self.layers()
+            .iter()
+            .enumerate()
+            .filter(|(_, layer)| layer.activation() == Activation::Relu)
+            .flat_map(|(index, layer)| {
+                let traces = &traces;
+
+                (0..layer.outputs())
+                    .filter(move |neuron| {
+                        traces
+                            .iter()
+                            .all(|trace| trace.layers[index].pre_activations[*neuron] <= 0.0)
+                    })
+                    .map(move |neuron| (index, neuron))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::neuron::Neuron;
+
+    #[test]
+    fn network_trace() {
+        // The second hidden neuron has a large negative bias, so no (small) inputs can ever switch it on
+        let layer1 = Layer::with_activation(
+            vec![
+                Neuron::new(0.1, vec![0.5, -0.5]),
+                Neuron::new(-10.0, vec![0.2, 0.3]),
+            ],
+            Activation::Relu,
+        );
+        let layer2 =
+            Layer::with_activation(vec![Neuron::new(0.0, vec![1.0, 1.0])], Activation::Tanh);
+        let network = Network::new(vec![layer1, layer2]);
+
+        let trace = network.propagate_with_trace(vec![1.0, -1.0]);
+
+        assert_eq!(trace.layers.len(), 2);
+        approx::assert_relative_eq!(
+            trace.layers[0].pre_activations.as_slice(),
+            [1.1, -10.1].as_ref()
+        );
+        approx::assert_relative_eq!(
+            trace.layers[0].post_activations.as_slice(),
+            [1.1, 0.0].as_ref()
+        );
+        approx::assert_relative_eq!(trace.layers[1].pre_activations.as_slice(), [1.1].as_ref());
+        approx::assert_relative_eq!(
+            trace.layer_inputs(1),
+            trace.layers[0].post_activations.as_slice()
+        );
+
+        // The trace should always end up with the same outputs as an ordinary propagation
+        let outputs = network.propagate(vec![1.0, -1.0]);
+        approx::assert_relative_eq!(trace.outputs(), outputs.as_slice());
+
+        let inputs = vec![vec![1.0, -1.0], vec![0.5, 0.5], vec![-1.0, 1.0]];
+        assert_eq!(network.dead_neurons(&inputs), vec![(0, 1)]);
+    }
+}
@@ -0,0 +1,462 @@
+// Gradient-based training
+// Rather than evolving a population of networks, these nudge the weights of a single network towards producing known outputs for known inputs (e.g. copying what a scripted bot or a human would have done)
+
+use rand::seq::SliceRandom;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+
+use std::iter::once;
+
+use crate::Network;
+
+/// One example to learn from: the inputs to feed the network, and the outputs it should have produced for them
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Sample {
+    pub inputs: Vec<f32>,
+    pub targets: Vec<f32>,
+}
+
+impl Sample {
+    pub fn new(inputs: Vec<f32>, targets: Vec<f32>) -> Self {
+        Self { inputs, targets }
+    }
+}
+
+/// How to measure how wrong the outputs of a network are
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Loss {
+    /// The mean of the squared differences between each output and its target - for outputs that are numbers in their own right
+    Mse,
+    /// Treats the outputs as the scores of a set of choices, turns them into probabilities with a softmax, and measures how surprised that is by the targets - for picking one of several choices
+    CrossEntropy,
+}
+
+impl Loss {
+    /// How wrong the specified outputs are
+    pub fn loss(&self, outputs: &[f32], targets: &[f32]) -> f32 {
+        assert_eq!(outputs.len(), targets.len());
+
+        match self {
+            Self::Mse => {
+                outputs
+                    .iter()
+                    .zip(targets)
+                    .map(|(output, target)| (output - target).powi(2))
+                    .sum::<f32>()
+                    / outputs.len() as f32
+            }
+            Self::CrossEntropy => softmax(outputs)
+                .iter()
+                .zip(targets)
+                .map(|(probability, target)| -target * probability.max(f32::MIN_POSITIVE).ln())
+                .sum(),
+        }
+    }
+
+    /// How much the loss would change for a small change in each of the outputs
+    pub fn gradient(&self, outputs: &[f32], targets: &[f32]) -> Vec<f32> {
+        assert_eq!(outputs.len(), targets.len());
+
+        match self {
+            Self::Mse => outputs
+                .iter()
+                .zip(targets)
+                .map(|(output, target)| 2.0 * (output - target) / outputs.len() as f32)
+                .collect(),
+            // The softmax and the log cancel out nicely, as long as the targets add up to 1.0
+            Self::CrossEntropy => softmax(outputs)
+                .iter()
+                .zip(targets)
+                .map(|(probability, target)| probability - target)
+                .collect(),
+        }
+    }
+}
+
+/// Turn a set of scores into probabilities that add up to 1.0, where the biggest score gets the biggest probability
+pub fn softmax(scores: &[f32]) -> Vec<f32> {
+    // Subtracting the biggest score first stops exp() from overflowing, and doesn't change the result
+    let max = scores.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+    let exps: Vec<f32> = scores.iter().map(|score| (score - max).exp()).collect();
+    let total: f32 = exps.iter().sum();
+
+    exps.iter().map(|exp| exp / total).collect()
+}
+
+/// Decides how far to move each weight of a network, given which way the loss goes down
+pub trait Optimizer {
+    /// Update the specified parameters using their gradients (both in the same order as Network::weights())
+    fn step(&mut self, parameters: &mut [f32], gradients: &[f32]);
+}
+
+/// Plain stochastic gradient descent: move every weight against its gradient by the same fraction
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Sgd {
+    learning_rate: f32,
+}
+
+impl Sgd {
+    pub fn new(learning_rate: f32) -> Self {
+        assert!(learning_rate > 0.0);
+
+        Self { learning_rate }
+    }
+}
+
+impl Optimizer for Sgd {
+    fn step(&mut self, parameters: &mut [f32], gradients: &[f32]) {
+        assert_eq!(parameters.len(), gradients.len());
+
+        for (parameter, gradient) in parameters.iter_mut().zip(gradients) {
+            *parameter -= self.learning_rate * gradient;
+        }
+    }
+}
+
+/// Adam (Kingma & Ba, "Adam: A Method for Stochastic Optimization")
+/// Keeps a running average of each weight's gradient and of its square, so weights with steady gradients move quickly and weights with noisy ones move carefully
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Adam {
+    learning_rate: f32,
+    beta1: f32,
+    beta2: f32,
+    epsilon: f32,
+    /// Running average of each gradient
+    m: Vec<f32>,
+    /// Running average of each squared gradient
+    v: Vec<f32>,
+    /// How many steps have been taken, to correct the running averages for starting off at zero
+    t: i32,
+}
+
+impl Adam {
+    /// Create a new Adam optimizer with the usual settings from the paper
+    pub fn new(learning_rate: f32) -> Self {
+        Self::with_betas(learning_rate, 0.9, 0.999)
+    }
+
+    /// Create a new Adam optimizer that forgets old gradients (beta1) and squared gradients (beta2) at the specified rates
+    pub fn with_betas(learning_rate: f32, beta1: f32, beta2: f32) -> Self {
+        assert!(learning_rate > 0.0);
+        assert!((0.0..1.0).contains(&beta1));
+        assert!((0.0..1.0).contains(&beta2));
+
+        Self {
+            learning_rate,
+            beta1,
+            beta2,
+            epsilon: 1e-8,
+            m: Vec::new(),
+            v: Vec::new(),
+            t: 0,
+        }
+    }
+}
+
+impl Optimizer for Adam {
+    fn step(&mut self, parameters: &mut [f32], gradients: &[f32]) {
+        assert_eq!(parameters.len(), gradients.len());
+
+        if self.m.len() != parameters.len() {
+            self.m = vec![0.0; parameters.len()];
+            self.v = vec![0.0; parameters.len()];
+            self.t = 0;
+        }
+
+        self.t += 1;
+        let m_correction = 1.0 - self.beta1.powi(self.t);
+        let v_correction = 1.0 - self.beta2.powi(self.t);
+
+        for ((parameter, gradient), (m, v)) in parameters
+            .iter_mut()
+            .zip(gradients)
+            .zip(self.m.iter_mut().zip(self.v.iter_mut()))
+        {
+            *m = self.beta1 * *m + (1.0 - self.beta1) * gradient;
+            *v = self.beta2 * *v + (1.0 - self.beta2) * gradient.powi(2);
+
+            let m_hat = *m / m_correction;
+            let v_hat = *v / v_correction;
+
+            *parameter -= self.learning_rate * m_hat / (v_hat.sqrt() + self.epsilon);
+        }
+    }
+}
+
+impl Network {
+    /// Work out the loss for a single sample, along with the gradient of that loss for every bias and weight (in the same order as Network::weights())
+    pub fn backprop(&self, sample: &Sample, loss: Loss) -> (f32, Vec<f32>) {
+        // Propagate forwards, keeping hold of what went into each layer and the weighted sums that came out, since the backwards pass needs them both
+        let trace = self.propagate_with_trace(sample.inputs.clone());
+        let outputs = trace.outputs();
+
+        let error = loss.loss(outputs, &sample.targets);
+
+        // Then go backwards, passing the blame for the loss back through each layer in turn
+        let mut blame = loss.gradient(outputs, &sample.targets);
+        let mut layer_gradients = Vec::with_capacity(self.layers.len());
+
+        for (index, layer) in self.layers.iter().enumerate().rev() {
+            let inputs = trace.layer_inputs(index);
+            let sums = &trace.layers[index].pre_activations;
+
+            // How much the loss changes for a small change in each neuron's weighted sum
+            let deltas: Vec<f32> = blame
+                .iter()
+                .zip(sums)
+                .map(|(blame, sum)| blame * layer.activation().derivative(*sum))
+                .collect();
+
+            let gradients: Vec<f32> = deltas
+                .iter()
+                .flat_map(|delta| once(*delta).chain(inputs.iter().map(move |input| delta * input)))
+                .collect();
+
+            // Each input to this layer gets the blame of every neuron it fed into, in proportion to the weight it fed in with
+            blame = (0..inputs.len())
+                .map(|idx| {
+                    layer
+                        .rows()
+                        .zip(&deltas)
+                        .map(|(row, delta)| row[1 + idx] * delta)
+                        .sum()
+                })
+                .collect();
+
+            layer_gradients.push(gradients);
+        }
+
+        let gradients = layer_gradients.into_iter().rev().flatten().collect();
+
+        (error, gradients)
+    }
+
+    /// Take a single optimizer step using the average gradient over a batch of samples, and return the average loss of the batch (from before the step)
+    pub fn train_batch(
+        &mut self,
+        batch: &[Sample],
+        loss: Loss,
+        optimizer: &mut dyn Optimizer,
+    ) -> f32 {
+        assert!(!batch.is_empty());
+
+        let mut total_error = 0.0;
+        let mut total_gradients: Vec<f32> = vec![0.0; self.weights().count()];
+
+        for sample in batch {
+            let (error, gradients) = self.backprop(sample, loss);
+
+            total_error += error;
+            for (total, gradient) in total_gradients.iter_mut().zip(&gradients) {
+                *total += gradient;
+            }
+        }
+
+        let size = batch.len() as f32;
+        for gradient in total_gradients.iter_mut() {
+            *gradient /= size;
+        }
+
+        let mut parameters: Vec<f32> = self.weights().collect();
+        optimizer.step(&mut parameters, &total_gradients);
+        self.set_weights(&parameters);
+
+        total_error / size
+    }
+
+    /// Go through every sample once (an epoch) in a random order, taking an optimizer step after each mini-batch of the specified size
+    /// Returns the average loss over the epoch
+    pub fn train_epoch(
+        &mut self,
+        prng: &mut dyn RngCore,
+        samples: &[Sample],
+        loss: Loss,
+        optimizer: &mut dyn Optimizer,
+        batch_size: usize,
+    ) -> f32 {
+        assert!(!samples.is_empty());
+        assert!(batch_size > 0);
+
+        let mut order: Vec<usize> = (0..samples.len()).collect();
+        order.shuffle(prng);
+
+        let mut total_error = 0.0;
+
+        for chunk in order.chunks(batch_size) {
+            let batch: Vec<Sample> = chunk.iter().map(|idx| samples[*idx].clone()).collect();
+
+            // Weight each batch's loss by its size, since the last one might be smaller than the rest
+            total_error += self.train_batch(&batch, loss, optimizer) * batch.len() as f32;
+        }
+
+        total_error / samples.len() as f32
+    }
+
+    /// The average loss over the specified samples, without changing the network
+    pub fn loss(&self, samples: &[Sample], loss: Loss) -> f32 {
+        assert!(!samples.is_empty());
+
+        samples
+            .iter()
+            .map(|sample| loss.loss(&self.propagate(sample.inputs.clone()), &sample.targets))
+            .sum::<f32>()
+            / samples.len() as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::activation::Activation;
+    use crate::topology::LayerTopology;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    fn topology(sizes: &[(usize, Activation)]) -> Vec<LayerTopology> {
+        sizes
+            .iter()
+            .map(|(neurons, activation)| LayerTopology::new(*neurons, *activation))
+            .collect()
+    }
+
+    fn xor() -> Vec<Sample> {
+        vec![
+            Sample::new(vec![0.0, 0.0], vec![0.0]),
+            Sample::new(vec![0.0, 1.0], vec![1.0]),
+            Sample::new(vec![1.0, 0.0], vec![1.0]),
+            Sample::new(vec![1.0, 1.0], vec![0.0]),
+        ]
+    }
+
+    #[test]
+    fn loss_functions() {
+        approx::assert_relative_eq!(Loss::Mse.loss(&[0.5, -1.0], &[1.0, 1.0]), 2.125);
+
+        let gradient = Loss::Mse.gradient(&[0.5, -1.0], &[1.0, 1.0]);
+        approx::assert_relative_eq!(gradient.as_slice(), [-0.5, -2.0].as_ref());
+
+        // Equal scores mean equal probabilities, so the loss is -ln(1/3) whichever choice was right
+        approx::assert_relative_eq!(
+            Loss::CrossEntropy.loss(&[2.0, 2.0, 2.0], &[0.0, 1.0, 0.0]),
+            3.0f32.ln()
+        );
+
+        let gradient = Loss::CrossEntropy.gradient(&[2.0, 2.0, 2.0], &[0.0, 1.0, 0.0]);
+        approx::assert_relative_eq!(
+            gradient.as_slice(),
+            [1.0 / 3.0, -2.0 / 3.0, 1.0 / 3.0].as_ref()
+        );
+
+        let probabilities = softmax(&[1000.0, 0.0]);
+        approx::assert_relative_eq!(probabilities.as_slice(), [1.0, 0.0].as_ref());
+    }
+
+    #[test]
+    fn backprop_matches_finite_differences() {
+        // Seed a ChaCha8Rng for a predictable "random" number to use for testing
+        let mut prng = ChaCha8Rng::from_seed(Default::default());
+
+        let topology = topology(&[
+            (3, Activation::Linear),
+            (4, Activation::Tanh),
+            (2, Activation::Linear),
+        ]);
+        let network = Network::random(&mut prng, &topology);
+        let sample = Sample::new(vec![0.5, -0.3, 0.8], vec![0.2, -0.7]);
+
+        for loss in [Loss::Mse, Loss::CrossEntropy].iter() {
+            let target_sample = if *loss == Loss::CrossEntropy {
+                Sample::new(sample.inputs.clone(), vec![0.0, 1.0])
+            } else {
+                sample.clone()
+            };
+
+            let (_, gradients) = network.backprop(&target_sample, *loss);
+            let weights: Vec<f32> = network.weights().collect();
+            assert_eq!(gradients.len(), weights.len());
+
+            // Nudge each weight a little each way, and check the loss changes as much as its gradient says it should
+            let epsilon = 1e-2;
+            for idx in 0..weights.len() {
+                let nudged = |by: f32| {
+                    let mut weights = weights.clone();
+                    weights[idx] += by;
+                    let outputs = Network::from_weights(&topology, weights)
+                        .propagate(target_sample.inputs.clone());
+                    loss.loss(&outputs, &target_sample.targets)
+                };
+
+                let expected = (nudged(epsilon) - nudged(-epsilon)) / (2.0 * epsilon);
+                approx::assert_abs_diff_eq!(gradients[idx], expected, epsilon = 1e-3);
+            }
+        }
+    }
+
+    #[test]
+    fn sgd_learns_xor() {
+        // Seed a ChaCha8Rng for a predictable "random" number to use for testing
+        let mut prng = ChaCha8Rng::seed_from_u64(42);
+
+        let topology = topology(&[
+            (2, Activation::Linear),
+            (4, Activation::Tanh),
+            (1, Activation::Linear),
+        ]);
+        let mut network = Network::random(&mut prng, &topology);
+        let mut optimizer = Sgd::new(0.1);
+        let samples = xor();
+
+        let before = network.loss(&samples, Loss::Mse);
+        for _ in 0..2000 {
+            network.train_epoch(&mut prng, &samples, Loss::Mse, &mut optimizer, 2);
+        }
+        let after = network.loss(&samples, Loss::Mse);
+
+        assert!(after < before);
+        assert!(after < 0.01);
+    }
+
+    #[test]
+    fn adam_learns_classification() {
+        // Seed a ChaCha8Rng for a predictable "random" number to use for testing
+        let mut prng = ChaCha8Rng::seed_from_u64(42);
+
+        // Pick which of 3 outputs should win depending on whether the input is low, middling or high, like a paddle choosing to move up, stay or go down
+        let samples: Vec<Sample> = (0..30)
+            .map(|idx| {
+                let input = idx as f32 / 29.0 * 2.0 - 1.0;
+                let target = if input < -0.33 {
+                    vec![1.0, 0.0, 0.0]
+                } else if input > 0.33 {
+                    vec![0.0, 0.0, 1.0]
+                } else {
+                    vec![0.0, 1.0, 0.0]
+                };
+
+                Sample::new(vec![input], target)
+            })
+            .collect();
+
+        let topology = topology(&[
+            (1, Activation::Linear),
+            (8, Activation::Tanh),
+            (3, Activation::Linear),
+        ]);
+        let mut network = Network::random(&mut prng, &topology);
+        let mut optimizer = Adam::new(0.05);
+
+        for _ in 0..300 {
+            network.train_epoch(&mut prng, &samples, Loss::CrossEntropy, &mut optimizer, 8);
+        }
+
+        // Every sample should now get the right choice
+        for sample in &samples {
+            let outputs = network.propagate(sample.inputs.clone());
+            let choice = (0..3)
+                .max_by(|a, b| outputs[*a].partial_cmp(&outputs[*b]).unwrap())
+                .unwrap();
+
+            approx::assert_relative_eq!(sample.targets[choice], 1.0);
+        }
+    }
+}
@@ -1,38 +1,273 @@
 use rand::RngCore;
+use serde::{Deserialize, Serialize};
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
 
 use genetic_algorithm as ga;
 use neural_network as nn;
 
 use ga::chromosome::*;
+use ga::neat::{Genome, NodeKind};
+use nn::graph::{Edge, GraphNetwork, Node, NodeRole};
+use nn::recurrent::{Cell, RecurrentNetwork, State};
+use nn::training::{Optimizer, Sample};
 
+use super::decoder::*;
 use super::eye::*;
+use super::imitation::Record;
+use super::normalizer::*;
 use crate::settings::*;
 
-#[derive(Clone, Debug)]
+/// What shape of neural network brains get
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Topology {
+    /// Eye -> brain_neurons -> outputs, fully connected, with only the weights evolving
+    Fixed,
+    /// Grown by NEAT, starting from the eye connected straight to the outputs
+    Neat,
+    /// Eye -> brain_neurons -> outputs like Fixed, except the hidden neurons also remember what they output on the last tick (until the next serve)
+    Recurrent(Cell),
+}
+
+/// The neural network inside a brain
+/// This is untagged so brains saved before there was more than one kind of network still load
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Network {
+    Layered(nn::Network),
+    Graph(GraphNetwork),
+    Recurrent(RecurrentNetwork),
+}
+
+impl Network {
+    /// Get the layered network, if that's what this is
+    pub fn layered(&self) -> Option<&nn::Network> {
+        match self {
+            Self::Layered(network) => Some(network),
+            Self::Graph(_) | Self::Recurrent(_) => None,
+        }
+    }
+
+    /// Get the graph network, if that's what this is
+    pub fn graph(&self) -> Option<&GraphNetwork> {
+        match self {
+            Self::Layered(_) | Self::Recurrent(_) => None,
+            Self::Graph(network) => Some(network),
+        }
+    }
+
+    /// Get the recurrent network, if that's what this is
+    pub fn recurrent(&self) -> Option<&RecurrentNetwork> {
+        match self {
+            Self::Layered(_) | Self::Graph(_) => None,
+            Self::Recurrent(network) => Some(network),
+        }
+    }
+
+    /// Propagate the inputs (a recurrent network starts from a blank memory every time, as if a new ball had just been served)
+    pub fn propagate(&self, inputs: Vec<f32>) -> Vec<f32> {
+        match self {
+            Self::Layered(network) => network.propagate(inputs),
+            Self::Graph(network) => network.propagate(inputs),
+            Self::Recurrent(network) => network.propagate(&inputs, &mut network.state()),
+        }
+    }
+
+    /// Propagate the inputs into the provided slice, without allocating once the buffers are big enough (for layered and recurrent networks)
+    /// A recurrent network remembers what it needs to in the memory, which gets replaced if it's the wrong shape for the network
+    pub fn propagate_into(
+        &self,
+        inputs: &[f32],
+        outputs: &mut [f32],
+        scratch: &mut nn::Scratch,
+        memory: &mut State,
+    ) {
+        match self {
+            Self::Layered(network) => network.propagate_into(inputs, outputs, scratch),
+            Self::Graph(network) => outputs.copy_from_slice(&network.propagate(inputs.to_vec())),
+            Self::Recurrent(network) => {
+                if memory.hidden().len() != network.recurrent_layers().len() {
+                    *memory = network.state();
+                }
+
+                network.propagate_into(inputs, outputs, memory)
+            }
+        }
+    }
+
+    /// Return every weight and bias in the network
+    pub fn weights(&self) -> Box<dyn Iterator<Item = f32> + '_> {
+        match self {
+            Self::Layered(network) => Box::new(network.weights()),
+            Self::Graph(network) => Box::new(network.weights()),
+            Self::Recurrent(network) => Box::new(network.weights()),
+        }
+    }
+}
+
+/// Buffers a brain can reuse for thinking every tick, rather than allocating new ones each time
+#[derive(Clone, Debug, Default)]
+pub struct Thoughts {
+    inputs: Vec<f32>,
+    outputs: Vec<f32>,
+    scratch: nn::Scratch,
+    /// What a recurrent brain remembers from one tick to the next
+    memory: State,
+}
+
+impl Thoughts {
+    /// Forget everything a recurrent brain remembered (e.g. when a new ball gets served)
+    pub fn forget(&mut self) {
+        self.memory.reset();
+    }
+
+    /// What the brain last saw, after normalizing
+    pub fn inputs(&self) -> &[f32] {
+        &self.inputs
+    }
+
+    /// How the brain last responded
+    pub fn outputs(&self) -> &[f32] {
+        &self.outputs
+    }
+
+    pub fn memory(&self) -> &State {
+        &self.memory
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Brain {
-    network: nn::Network,
+    network: Network,
+    normalizer: Normalizer,
+    #[serde(default)]
+    decoder: Decoder,
 }
 
 impl Brain {
     /// Create a new brain with a random Neural Network
     pub fn random(config: &Config, rng: &mut dyn RngCore) -> Brain {
-        let network = nn::Network::random(rng, &Self::network_topology(config));
+        // The decoder won't know what to do with the wrong number of outputs
+        assert_eq!(config.outputs, config.decoder.outputs());
+
+        let network = match config.topology {
+            Topology::Recurrent(cell) => Network::Recurrent(RecurrentNetwork::random(
+                rng,
+                cell,
+                &Self::network_topology(config),
+            )),
+            _ => Network::Layered(nn::Network::random(rng, &Self::network_topology(config))),
+        };
 
-        Brain { network }
+        Brain {
+            network,
+            normalizer: Normalizer::new(config),
+            decoder: config.decoder,
+        }
+    }
+
+    /// Rebuild a brain from a Chromosome (e.g. one produced by the GeneticAlgorithm), scaling its inputs with the provided Normalizer
+    pub fn from_chromosome(
+        config: &Config,
+        normalizer: Normalizer,
+        chromosome: &Chromosome,
+    ) -> Brain {
+        let weights = chromosome.iter().copied();
+        let network = match config.topology {
+            Topology::Recurrent(cell) => Network::Recurrent(RecurrentNetwork::from_weights(
+                cell,
+                &Self::network_topology(config),
+                weights,
+            )),
+            _ => Network::Layered(nn::Network::from_weights(
+                &Self::network_topology(config),
+                weights,
+            )),
+        };
+
+        Brain {
+            network,
+            normalizer,
+            decoder: config.decoder,
+        }
+    }
+
+    /// Build a brain from a NEAT Genome, scaling its inputs with the provided Normalizer
+    /// Only the enabled connections make it into the network
+    pub fn from_genome(config: &Config, normalizer: Normalizer, genome: &Genome) -> Brain {
+        assert_eq!(config.outputs, config.decoder.outputs());
+
+        let nodes: Vec<Node> = genome
+            .nodes()
+            .iter()
+            .map(|node| {
+                let (role, activation) = match node.kind {
+                    NodeKind::Input => (NodeRole::Input, nn::activation::Activation::Linear),
+                    // A new hidden node starts off passing its input straight through, and tanh is close to that for small values (a ReLU would cut off anything negative)
+                    NodeKind::Hidden => (NodeRole::Hidden, nn::activation::Activation::Tanh),
+                    NodeKind::Output => (NodeRole::Output, config.decoder.activation()),
+                };
+
+                Node {
+                    id: node.id,
+                    role,
+                    bias: node.bias,
+                    activation,
+                }
+            })
+            .collect();
+
+        let edges: Vec<Edge> = genome
+            .connections()
+            .iter()
+            .filter(|connection| connection.enabled)
+            .map(|connection| Edge {
+                from: connection.from,
+                to: connection.to,
+                weight: connection.weight,
+            })
+            .collect();
+
+        Brain {
+            network: Network::Graph(GraphNetwork::new(&nodes, &edges)),
+            normalizer,
+            decoder: config.decoder,
+        }
+    }
+
+    /// Load a previously saved brain from a JSON file
+    pub fn load<P: AsRef<Path>>(path: P) -> std::io::Result<Brain> {
+        let reader = BufReader::new(File::open(path)?);
+
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    /// Save the brain (including how it scales its inputs) to a JSON file
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let writer = BufWriter::new(File::create(path)?);
+
+        Ok(serde_json::to_writer(writer, self)?)
     }
 
     /// Generate a neural network LayerTopology given the provided Config
     fn network_topology(config: &Config) -> [nn::topology::LayerTopology; 3] {
+        // A ReLU memory could keep growing for the whole rally, so recurrent neurons squash theirs with tanh instead
+        let hidden_activation = match config.topology {
+            Topology::Recurrent(_) => nn::activation::Activation::Tanh,
+            _ => nn::activation::Activation::Relu,
+        };
+
         [
-            nn::topology::LayerTopology {
-                neurons: config.eye_photoreceptors,
-            },
-            nn::topology::LayerTopology {
-                neurons: config.brain_neurons,
-            },
-            nn::topology::LayerTopology {
-                neurons: config.outputs,
-            },
+            nn::topology::LayerTopology::new(
+                config.eye_photoreceptors,
+                nn::activation::Activation::Relu, // Not used for the input layer
+            ),
+            nn::topology::LayerTopology::new(config.brain_neurons, hidden_activation)
+                .with_initializer(config.initializer),
+            nn::topology::LayerTopology::new(config.outputs, config.decoder.activation())
+                .with_initializer(config.initializer),
         ]
     }
 
@@ -40,13 +275,94 @@ impl Brain {
         self.network.weights().collect()
     }
 
+    /// A 64-bit FNV-1a hash of every weight and bias in the network, for telling brains apart (e.g. in training metrics)
+    /// It only depends on the weights, so it stays the same as the brain's normalizer learns
+    pub fn fingerprint(&self) -> u64 {
+        self.network
+            .weights()
+            .flat_map(|weight| weight.to_bits().to_le_bytes())
+            .fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+                (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+            })
+    }
+
     /// Get immutable borrow of the brain
-    pub fn network(&self) -> &nn::Network {
+    pub fn network(&self) -> &Network {
         &self.network
     }
 
-    pub fn step(&self, config: &Config, eye: &Eye) -> f32 {
-        let response = self.network.propagate(eye.photoreceptors.clone());
-        response[0]
+    /// Get immutable borrow of the input normalizer
+    pub fn normalizer(&self) -> &Normalizer {
+        &self.normalizer
+    }
+
+    /// Update the input normalizer with what the eye can currently see
+    pub fn observe(&mut self, eye: &Eye) {
+        self.normalizer.observe(&eye.photoreceptors);
+    }
+
+    /// Get the decoder that turns the network's response into a paddle move
+    pub fn decoder(&self) -> Decoder {
+        self.decoder
+    }
+
+    /// Turn recorded moves into samples this brain can learn from, scaling what was seen with its normalizer and encoding each move with its decoder
+    pub fn samples(&self, records: &[Record]) -> Vec<Sample> {
+        records
+            .iter()
+            .map(|record| {
+                Sample::new(
+                    self.normalizer.normalize(&record.vision),
+                    self.decoder.encode(record.movement, record.vision[0]),
+                )
+            })
+            .collect()
+    }
+
+    /// Take one pass over the samples with backpropagation, nudging the network towards producing the moves they were recorded making
+    /// Only fixed topology brains can be trained this way. Returns the average loss over the pass
+    pub fn train_epoch(
+        &mut self,
+        prng: &mut dyn RngCore,
+        samples: &[Sample],
+        optimizer: &mut dyn Optimizer,
+        batch_size: usize,
+    ) -> f32 {
+        let loss = self.decoder.loss();
+
+        match &mut self.network {
+            Network::Layered(network) => {
+                network.train_epoch(prng, samples, loss, optimizer, batch_size)
+            }
+            Network::Graph(_) => panic!("NEAT brains can't be trained with backpropagation!"),
+            Network::Recurrent(_) => {
+                panic!("Recurrent brains can't be trained with backpropagation!")
+            }
+        }
+    }
+
+    /// Think about what the eye can see, and return the raw response of the network
+    pub fn step(&self, config: &Config, eye: &Eye) -> Vec<f32> {
+        self.network
+            .propagate(self.normalizer.normalize(&eye.photoreceptors))
+    }
+
+    /// Think about what the eye can see just like Brain::step(), but using the provided buffers instead of allocating new ones
+    pub fn think<'a>(&self, eye: &Eye, thoughts: &'a mut Thoughts) -> &'a [f32] {
+        let Thoughts {
+            inputs,
+            outputs,
+            scratch,
+            memory,
+        } = thoughts;
+
+        inputs.resize(eye.photoreceptors.len(), 0.0);
+        self.normalizer.normalize_into(&eye.photoreceptors, inputs);
+
+        outputs.resize(self.decoder.outputs(), 0.0);
+        self.network
+            .propagate_into(inputs, outputs, scratch, memory);
+
+        outputs
     }
 }
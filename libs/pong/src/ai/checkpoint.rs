@@ -0,0 +1,47 @@
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+
+use super::trainer::*;
+
+/// A snapshot of a training run, taken between generations
+/// Along with the Trainer's state this holds the state of the RNG, so a resumed run carries on exactly as if it had never stopped
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub state: TrainerState,
+    pub prng: ChaCha8Rng,
+}
+
+impl Checkpoint {
+    /// Create a new Checkpoint of a Trainer and the RNG it's training with
+    pub fn new(trainer: &Trainer, prng: &ChaCha8Rng) -> Self {
+        Self {
+            state: trainer.state(),
+            prng: prng.clone(),
+        }
+    }
+
+    /// Load a checkpoint from a JSON file
+    pub fn load<P: AsRef<Path>>(path: P) -> std::io::Result<Checkpoint> {
+        let reader = BufReader::new(File::open(path)?);
+
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    /// Save the checkpoint to a JSON file
+    /// This writes to a temporary file first and then renames it, so being interrupted halfway through never leaves a broken checkpoint behind
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let path = path.as_ref();
+        let temporary = path.with_extension("tmp");
+
+        let mut writer = BufWriter::new(File::create(&temporary)?);
+        serde_json::to_writer(&mut writer, self)?;
+        writer.flush()?;
+        drop(writer);
+
+        std::fs::rename(temporary, path)
+    }
+}
@@ -0,0 +1,239 @@
+use ggez::graphics::Rect;
+use serde::{Deserialize, Serialize};
+
+use std::str::FromStr;
+
+use neural_network as nn;
+
+use nn::activation::Activation;
+use nn::training::Loss;
+
+use crate::settings::*;
+
+/// How the response of a brain's neural network gets turned into a paddle move
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Decoder {
+    /// Move up at full speed for a negative output, down for a positive one, and stay still for zero
+    #[default]
+    Sign,
+    /// Pick whichever of the 3 outputs is biggest: up, stay or down
+    Discrete,
+    /// Move at a speed proportional to a tanh output
+    Proportional,
+    /// Steer towards the Y position picked by a tanh output (-1.0 is the top of the screen, 1.0 the bottom)
+    TargetY,
+}
+
+impl Decoder {
+    /// How many outputs the neural network needs to produce for this decoder
+    pub fn outputs(&self) -> usize {
+        match self {
+            Self::Discrete => 3,
+            Self::Sign | Self::Proportional | Self::TargetY => 1,
+        }
+    }
+
+    /// The activation function the output layer of the neural network should use for this decoder
+    pub fn activation(&self) -> Activation {
+        match self {
+            // This is how all brains used to work, so keep it the same
+            Self::Sign => Activation::Relu,
+            // A ReLU would make it impossible to tell the outputs apart whenever they're all zero
+            Self::Discrete => Activation::Linear,
+            // These need to be able to go negative to move the paddle up
+            Self::Proportional | Self::TargetY => Activation::Tanh,
+        }
+    }
+
+    /// Turn the response of a neural network into how far to move the specified paddle
+    pub fn decode(&self, response: &[f32], paddle: Rect) -> f32 {
+        assert_eq!(response.len(), self.outputs());
+
+        match self {
+            Self::Sign => {
+                if response[0] < 0.0 {
+                    -PADDLE_SPEED
+                } else if response[0] > 0.0 {
+                    PADDLE_SPEED
+                } else {
+                    0.0
+                }
+            }
+            Self::Discrete => {
+                // If there's a tie, the first output wins
+                let (choice, _) = response.iter().enumerate().fold(
+                    (0, f32::NEG_INFINITY),
+                    |(best, best_output), (idx, &output)| {
+                        if output > best_output {
+                            (idx, output)
+                        } else {
+                            (best, best_output)
+                        }
+                    },
+                );
+
+                match choice {
+                    0 => -PADDLE_SPEED,
+                    1 => 0.0,
+                    _ => PADDLE_SPEED,
+                }
+            }
+            Self::Proportional => response[0].clamp(-1.0, 1.0) * PADDLE_SPEED,
+            Self::TargetY => {
+                let target = (response[0].clamp(-1.0, 1.0) + 1.0) / 2.0 * SCREEN_HEIGHT;
+
+                (target - paddle.center().y).clamp(-PADDLE_SPEED, PADDLE_SPEED)
+            }
+        }
+    }
+
+    /// The reverse of decode: the outputs a neural network should have produced to make the specified move with a paddle whose centre is at paddle_y
+    /// This is what a brain gets trained towards when it learns to copy another player
+    pub fn encode(&self, movement: f32, paddle_y: f32) -> Vec<f32> {
+        match self {
+            Self::Sign | Self::Proportional => vec![(movement / PADDLE_SPEED).clamp(-1.0, 1.0)],
+            Self::Discrete => {
+                // Round to whichever of the 3 choices is closest, since other players (e.g. the bots) don't always move at full speed
+                if movement < -PADDLE_SPEED / 2.0 {
+                    vec![1.0, 0.0, 0.0]
+                } else if movement > PADDLE_SPEED / 2.0 {
+                    vec![0.0, 0.0, 1.0]
+                } else {
+                    vec![0.0, 1.0, 0.0]
+                }
+            }
+            // Where the paddle ended up is the best guess at where it was heading
+            Self::TargetY => {
+                vec![((paddle_y + movement) / SCREEN_HEIGHT * 2.0 - 1.0).clamp(-1.0, 1.0)]
+            }
+        }
+    }
+
+    /// How to measure how far a network's outputs are from the ones encode asked for
+    pub fn loss(&self) -> Loss {
+        match self {
+            Self::Discrete => Loss::CrossEntropy,
+            Self::Sign | Self::Proportional | Self::TargetY => Loss::Mse,
+        }
+    }
+}
+
+impl FromStr for Decoder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sign" => Ok(Self::Sign),
+            "discrete" => Ok(Self::Discrete),
+            "proportional" => Ok(Self::Proportional),
+            "target-y" => Ok(Self::TargetY),
+            _ => Err(format!(
+                "Unknown decoder '{}' (expected sign, discrete, proportional or target-y)",
+                s
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paddle_at(y: f32) -> Rect {
+        Rect::new(
+            X_OFFSET,
+            y - PADDLE_HEIGHT / 2.0,
+            PADDLE_WIDTH,
+            PADDLE_HEIGHT,
+        )
+    }
+
+    #[test]
+    fn sign_decoder() {
+        let paddle = paddle_at(300.0);
+
+        approx::assert_relative_eq!(Decoder::Sign.decode(&[-0.2], paddle), -PADDLE_SPEED);
+        approx::assert_relative_eq!(Decoder::Sign.decode(&[0.0], paddle), 0.0);
+        approx::assert_relative_eq!(Decoder::Sign.decode(&[0.2], paddle), PADDLE_SPEED);
+    }
+
+    #[test]
+    fn discrete_decoder() {
+        let paddle = paddle_at(300.0);
+
+        approx::assert_relative_eq!(
+            Decoder::Discrete.decode(&[0.9, 0.1, -0.5], paddle),
+            -PADDLE_SPEED
+        );
+        approx::assert_relative_eq!(Decoder::Discrete.decode(&[0.0, 0.3, 0.2], paddle), 0.0);
+        approx::assert_relative_eq!(
+            Decoder::Discrete.decode(&[-1.0, -2.0, -0.5], paddle),
+            PADDLE_SPEED
+        );
+    }
+
+    #[test]
+    fn proportional_decoder() {
+        let paddle = paddle_at(300.0);
+
+        approx::assert_relative_eq!(
+            Decoder::Proportional.decode(&[-0.5], paddle),
+            -0.5 * PADDLE_SPEED
+        );
+        approx::assert_relative_eq!(Decoder::Proportional.decode(&[0.0], paddle), 0.0);
+        approx::assert_relative_eq!(Decoder::Proportional.decode(&[1.0], paddle), PADDLE_SPEED);
+    }
+
+    #[test]
+    fn encoding_round_trip() {
+        let paddle = paddle_at(300.0);
+
+        // Encoding a move and then decoding it again should give back the same move
+        for decoder in [
+            Decoder::Sign,
+            Decoder::Discrete,
+            Decoder::Proportional,
+            Decoder::TargetY,
+        ]
+        .iter()
+        {
+            for movement in [-PADDLE_SPEED, 0.0, PADDLE_SPEED].iter() {
+                let outputs = decoder.encode(*movement, paddle.center().y);
+                assert_eq!(outputs.len(), decoder.outputs());
+
+                approx::assert_relative_eq!(decoder.decode(&outputs, paddle), *movement);
+            }
+        }
+
+        // The discrete decoder can only move at full speed or not at all, so slower moves get rounded
+        assert_eq!(
+            Decoder::Discrete.encode(-0.6 * PADDLE_SPEED, 300.0),
+            vec![1.0, 0.0, 0.0]
+        );
+        assert_eq!(
+            Decoder::Discrete.encode(0.4 * PADDLE_SPEED, 300.0),
+            vec![0.0, 1.0, 0.0]
+        );
+    }
+
+    #[test]
+    fn target_y_decoder() {
+        // An output of 0.0 means "the middle of the screen", so a paddle that's already there should stay still
+        approx::assert_relative_eq!(
+            Decoder::TargetY.decode(&[0.0], paddle_at(SCREEN_HEIGHT / 2.0)),
+            0.0
+        );
+
+        // A target that's a long way off should be chased at full speed...
+        approx::assert_relative_eq!(
+            Decoder::TargetY.decode(&[-1.0], paddle_at(SCREEN_HEIGHT / 2.0)),
+            -PADDLE_SPEED
+        );
+
+        // ...but a target that's close should be approached gently
+        approx::assert_relative_eq!(
+            Decoder::TargetY.decode(&[0.0], paddle_at(SCREEN_HEIGHT / 2.0 - 3.0)),
+            3.0
+        );
+    }
+}
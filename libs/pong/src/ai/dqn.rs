@@ -0,0 +1,578 @@
+// Deep Q-learning (Mnih et al, "Human-level control through deep reinforcement learning")
+// Rather than evolving whole brains, a single network learns to predict how much reward each action will lead to from a given observation, by playing in PongEnvs and learning from its own experience with backpropagation
+
+use rand::{Rng, RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+use neural_network as nn;
+
+use nn::activation::Activation;
+use nn::topology::LayerTopology;
+use nn::training::{Adam, Loss, Sample};
+
+use crate::cli::DqnOpt;
+use crate::env::*;
+use crate::player::{Policy, Snapshot};
+use crate::settings::*;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DqnSettings {
+    /// How many neurons the hidden layer of the Q-network gets
+    pub hidden_neurons: usize,
+    /// How much future rewards are worth compared to the ones straight away
+    pub gamma: f32,
+    pub learning_rate: f32,
+    /// How many transitions to learn from at once
+    pub batch_size: usize,
+    /// How many transitions to remember, after which the oldest get forgotten
+    pub buffer_capacity: usize,
+    /// How many transitions to collect before learning anything
+    pub warmup: usize,
+    /// How many steps to go between copying the online network into the target network
+    pub target_update: usize,
+    /// The chance of picking a random action to start with...
+    pub epsilon_start: f32,
+    /// ...which gets lowered to this...
+    pub epsilon_end: f32,
+    /// ...over this many steps
+    pub epsilon_decay: usize,
+}
+
+impl Default for DqnSettings {
+    fn default() -> Self {
+        Self {
+            hidden_neurons: 32,
+            gamma: 0.99,
+            learning_rate: 0.001,
+            batch_size: 32,
+            buffer_capacity: 50_000,
+            warmup: 1_000,
+            target_update: 1_000,
+            epsilon_start: 1.0,
+            epsilon_end: 0.05,
+            epsilon_decay: 50_000,
+        }
+    }
+}
+
+/// One step of experience: what the agent saw, what it did, and what happened next
+#[derive(Clone, Debug, PartialEq)]
+pub struct Transition {
+    pub observation: Observation,
+    pub action: Action,
+    pub reward: f32,
+    pub next_observation: Observation,
+    /// Whether the episode really ended here (rather than being abandoned), so there's no more reward to come
+    pub terminal: bool,
+}
+
+/// A fixed-size memory of past transitions, which overwrites the oldest once it's full
+/// Learning from random old transitions rather than the latest few stops the network from chasing whatever's just happened
+#[derive(Clone, Debug)]
+pub struct ReplayBuffer {
+    capacity: usize,
+    transitions: Vec<Transition>,
+    next: usize,
+}
+
+impl ReplayBuffer {
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0);
+
+        Self {
+            capacity,
+            transitions: Vec::with_capacity(capacity),
+            next: 0,
+        }
+    }
+
+    /// Remember a transition, forgetting the oldest one if the buffer is full
+    pub fn push(&mut self, transition: Transition) {
+        if self.transitions.len() < self.capacity {
+            self.transitions.push(transition);
+        } else {
+            self.transitions[self.next] = transition;
+        }
+
+        self.next = (self.next + 1) % self.capacity;
+    }
+
+    /// Pick the specified number of transitions at random (the same one can be picked more than once)
+    pub fn sample(&self, prng: &mut dyn RngCore, count: usize) -> Vec<&Transition> {
+        assert!(!self.transitions.is_empty());
+
+        (0..count)
+            .map(|_| &self.transitions[prng.gen_range(0..self.transitions.len())])
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.transitions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transitions.is_empty()
+    }
+}
+
+/// Whichever action the network expects to lead to the most reward
+fn best_action(network: &nn::Network, observation: &[f32]) -> Action {
+    let values = network.propagate(observation.to_vec());
+
+    // If there's a tie, the first action wins
+    let (best, _) = values.iter().enumerate().fold(
+        (0, f32::NEG_INFINITY),
+        |(best, best_value), (idx, &value)| {
+            if value > best_value {
+                (idx, value)
+            } else {
+                (best, best_value)
+            }
+        },
+    );
+
+    Action::from_index(best)
+}
+
+/// A deep Q-learning agent that's still learning
+pub struct DqnAgent {
+    settings: DqnSettings,
+    /// The network that picks actions and gets trained
+    online: nn::Network,
+    /// A copy of the online network that only gets updated every so often, so the values it's trained towards don't keep moving
+    target: nn::Network,
+    optimizer: Adam,
+    buffer: ReplayBuffer,
+    steps: usize,
+    until_target_update: usize,
+}
+
+impl DqnAgent {
+    /// Create a new DqnAgent with a random Q-network
+    pub fn new(settings: DqnSettings, prng: &mut dyn RngCore) -> Self {
+        assert!(settings.batch_size > 0);
+        assert!(settings.target_update > 0);
+
+        let online = nn::Network::random(
+            prng,
+            &[
+                LayerTopology::new(SENSOR_RANGES.len(), Activation::Relu), // Not used for the input layer
+                LayerTopology::new(settings.hidden_neurons, Activation::Relu),
+                LayerTopology::new(Action::ALL.len(), Activation::Linear), // Q-values can be any size, and negative
+            ],
+        );
+
+        Self {
+            target: online.clone(),
+            online,
+            optimizer: Adam::new(settings.learning_rate),
+            buffer: ReplayBuffer::new(settings.buffer_capacity),
+            steps: 0,
+            until_target_update: settings.target_update,
+            settings,
+        }
+    }
+
+    /// The current chance of picking a random action instead of the best one, which goes down linearly as the agent gains experience
+    pub fn epsilon(&self) -> f32 {
+        let progress = match self.settings.epsilon_decay {
+            0 => 1.0,
+            decay => (self.steps as f32 / decay as f32).min(1.0),
+        };
+
+        self.settings.epsilon_start
+            + (self.settings.epsilon_end - self.settings.epsilon_start) * progress
+    }
+
+    /// Pick an action to explore with: usually the best one, but sometimes a random one
+    pub fn act(&self, prng: &mut dyn RngCore, observation: &[f32]) -> Action {
+        if prng.gen::<f32>() < self.epsilon() {
+            Action::from_index(prng.gen_range(0..Action::ALL.len()))
+        } else {
+            self.greedy(observation)
+        }
+    }
+
+    /// Pick whichever action looks best
+    pub fn greedy(&self, observation: &[f32]) -> Action {
+        best_action(&self.online, observation)
+    }
+
+    /// Remember a transition and, once there's enough experience, learn from a batch of old ones
+    /// Returns the loss of the batch if there was one
+    pub fn remember(&mut self, prng: &mut dyn RngCore, transition: Transition) -> Option<f32> {
+        self.buffer.push(transition);
+        self.steps += 1;
+
+        self.until_target_update -= 1;
+        if self.until_target_update == 0 {
+            self.target = self.online.clone();
+            self.until_target_update = self.settings.target_update;
+        }
+
+        if self.buffer.len() >= self.settings.warmup.max(1) {
+            Some(self.learn(prng))
+        } else {
+            None
+        }
+    }
+
+    /// Nudge the online network's value for each sampled action towards the reward it got, plus the discounted value the target network expects to follow
+    fn learn(&mut self, prng: &mut dyn RngCore) -> f32 {
+        let gamma = self.settings.gamma;
+
+        let samples: Vec<Sample> = self
+            .buffer
+            .sample(prng, self.settings.batch_size)
+            .iter()
+            .map(|transition| {
+                let future = if transition.terminal {
+                    0.0
+                } else {
+                    self.target
+                        .propagate(transition.next_observation.clone())
+                        .into_iter()
+                        .fold(f32::NEG_INFINITY, f32::max)
+                };
+
+                // Only the action that was actually taken has anything to learn, so the others are asked for what they already predict
+                let mut targets = self.online.propagate(transition.observation.clone());
+                targets[transition.action.index()] = transition.reward + gamma * future;
+
+                Sample::new(transition.observation.clone(), targets)
+            })
+            .collect();
+
+        self.online
+            .train_batch(&samples, Loss::Mse, &mut self.optimizer)
+    }
+
+    /// A copy of the agent that just plays, without exploring or learning any more
+    pub fn player(&self) -> DqnPlayer {
+        DqnPlayer {
+            network: self.online.clone(),
+            observer: Observer::default(),
+        }
+    }
+}
+
+/// Games being played by an agent, which carry on where they left off from one call of train() to the next
+/// That way an episode longer than a single training chunk (e.g. a long rally against the wall) still gets to finish, and counts towards the returns
+pub struct Rollout {
+    envs: VecEnv,
+    observations: Vec<Observation>,
+    returns: Vec<f32>,
+}
+
+impl Rollout {
+    /// Start every game in the VecEnv from scratch
+    pub fn new(mut envs: VecEnv, seed: u64) -> Self {
+        let observations = envs.reset(seed);
+        let returns = vec![0.0; envs.len()];
+
+        Self {
+            envs,
+            observations,
+            returns,
+        }
+    }
+
+    /// Play every game for (at least) the specified number of steps in total, learning as it goes
+    /// Returns the total reward of every episode that finished along the way
+    pub fn train(
+        &mut self,
+        agent: &mut DqnAgent,
+        steps: usize,
+        prng: &mut dyn RngCore,
+    ) -> Vec<f32> {
+        let mut finished = Vec::new();
+
+        let mut taken = 0;
+        while taken < steps {
+            let actions: Vec<Action> = self
+                .observations
+                .iter()
+                .map(|observation| agent.act(prng, observation))
+                .collect();
+
+            let results = self.envs.step(&actions);
+
+            for (idx, ((observation, reward, done, info), action)) in
+                results.into_iter().zip(actions).enumerate()
+            {
+                // A finished game has already started over, so what it saw at the end of the old episode is in the info
+                let next_observation = info
+                    .final_observation
+                    .unwrap_or_else(|| observation.clone());
+
+                agent.remember(
+                    prng,
+                    Transition {
+                        observation: std::mem::replace(&mut self.observations[idx], observation),
+                        action,
+                        reward,
+                        next_observation,
+                        terminal: done && !info.truncated,
+                    },
+                );
+
+                self.returns[idx] += reward;
+                if done {
+                    finished.push(self.returns[idx]);
+                    self.returns[idx] = 0.0;
+                }
+            }
+
+            taken += self.envs.len();
+        }
+
+        finished
+    }
+}
+
+/// A trained DQN agent, which always plays whichever action its network thinks is best
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DqnPlayer {
+    network: nn::Network,
+    /// Kept between ticks rather than saved, so the player doesn't have to build a new one every time it acts
+    #[serde(skip)]
+    observer: Observer,
+}
+
+impl DqnPlayer {
+    /// Load a previously saved agent from a JSON file
+    pub fn load<P: AsRef<Path>>(path: P) -> std::io::Result<DqnPlayer> {
+        let reader = BufReader::new(File::open(path)?);
+
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    /// Save the agent to a JSON file
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let writer = BufWriter::new(File::create(path)?);
+
+        Ok(serde_json::to_writer(writer, self)?)
+    }
+
+    /// Get immutable borrow of the Q-network
+    pub fn network(&self) -> &nn::Network {
+        &self.network
+    }
+
+    /// Pick whichever action looks best
+    pub fn action(&self, observation: &[f32]) -> Action {
+        best_action(&self.network, observation)
+    }
+}
+
+impl Policy for DqnPlayer {
+    fn act(&mut self, snapshot: &Snapshot) -> f32 {
+        let observation = self.observer.observe(snapshot);
+
+        best_action(&self.network, observation).movement()
+    }
+
+    fn name(&self) -> &'static str {
+        "DQN"
+    }
+}
+
+/// Train a DQN agent headlessly, then save it
+pub fn run(opts: &DqnOpt) -> std::io::Result<()> {
+    let mut prng = ChaCha8Rng::seed_from_u64(opts.seed);
+
+    let settings = DqnSettings {
+        hidden_neurons: opts.hidden,
+        gamma: opts.gamma,
+        learning_rate: opts.learning_rate,
+        batch_size: opts.batch_size,
+        buffer_capacity: opts.buffer,
+        target_update: opts.target_update,
+        epsilon_decay: opts.epsilon_decay,
+        ..DqnSettings::default()
+    };
+
+    let mut agent = DqnAgent::new(settings, &mut prng);
+    let envs = VecEnv::new(
+        opts.envs,
+        &EnvSettings {
+            opponent: match opts.opponent {
+                Some(bot) => Opponent::Bot(bot),
+                None => Opponent::Wall,
+            },
+            ..EnvSettings::default()
+        },
+    );
+    let mut rollout = Rollout::new(envs, prng.gen());
+
+    // Train in chunks so there's something to look at while it goes
+    let mut trained = 0;
+    while trained < opts.steps {
+        let chunk = opts.report_every.max(1).min(opts.steps - trained);
+        let returns = rollout.train(&mut agent, chunk, &mut prng);
+        trained += chunk;
+
+        let average = if returns.is_empty() {
+            0.0
+        } else {
+            returns.iter().sum::<f32>() / returns.len() as f32
+        };
+
+        log::warn!(
+            "Step {}: {} episodes, avg return {:.3}, epsilon {:.3}",
+            trained,
+            returns.len(),
+            average,
+            agent.epsilon()
+        );
+    }
+
+    log::warn!("Saving DQN agent to {:?}", &opts.out);
+    agent.player().save(&opts.out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transition(action: Action, reward: f32) -> Transition {
+        Transition {
+            observation: vec![0.5; SENSOR_RANGES.len()],
+            action,
+            reward,
+            next_observation: vec![0.5; SENSOR_RANGES.len()],
+            terminal: true,
+        }
+    }
+
+    #[test]
+    fn replay_buffer() {
+        let mut prng = ChaCha8Rng::from_seed(Default::default());
+        let mut buffer = ReplayBuffer::new(3);
+
+        for reward in 0..5 {
+            buffer.push(transition(Action::Stay, reward as f32));
+        }
+
+        // Only the 3 newest transitions should be left
+        assert_eq!(buffer.len(), 3);
+        let mut rewards: Vec<f32> = buffer.transitions.iter().map(|t| t.reward).collect();
+        rewards.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(rewards, vec![2.0, 3.0, 4.0]);
+
+        let sampled = buffer.sample(&mut prng, 10);
+        assert_eq!(sampled.len(), 10);
+        assert!(sampled.iter().all(|t| t.reward >= 2.0));
+    }
+
+    #[test]
+    fn epsilon_schedule() {
+        let mut prng = ChaCha8Rng::from_seed(Default::default());
+        let mut agent = DqnAgent::new(
+            DqnSettings {
+                warmup: 1_000,
+                epsilon_decay: 10,
+                ..DqnSettings::default()
+            },
+            &mut prng,
+        );
+
+        approx::assert_relative_eq!(agent.epsilon(), 1.0);
+
+        for _ in 0..5 {
+            assert!(agent
+                .remember(&mut prng, transition(Action::Stay, 0.0))
+                .is_none());
+        }
+        approx::assert_relative_eq!(agent.epsilon(), 0.525);
+
+        for _ in 0..10 {
+            agent.remember(&mut prng, transition(Action::Stay, 0.0));
+        }
+        approx::assert_relative_eq!(agent.epsilon(), 0.05);
+    }
+
+    #[test]
+    fn learns_the_best_action() {
+        // Seed a ChaCha8Rng for a predictable "random" number to use for testing
+        let mut prng = ChaCha8Rng::seed_from_u64(42);
+        let mut agent = DqnAgent::new(
+            DqnSettings {
+                hidden_neurons: 8,
+                learning_rate: 0.01,
+                batch_size: 8,
+                warmup: 10,
+                target_update: 10,
+                ..DqnSettings::default()
+            },
+            &mut prng,
+        );
+
+        // Moving down is always worth a point, and nothing else is worth anything
+        for step in 0..600 {
+            let action = Action::from_index(step % Action::ALL.len());
+            let reward = if action == Action::Down { 1.0 } else { 0.0 };
+
+            agent.remember(&mut prng, transition(action, reward));
+        }
+
+        let observation = vec![0.5; SENSOR_RANGES.len()];
+        assert_eq!(agent.greedy(&observation), Action::Down);
+
+        // The values should have settled on the rewards, since every transition was the last of its episode
+        let values = agent.online.propagate(observation.clone());
+        approx::assert_abs_diff_eq!(values[Action::Down.index()], 1.0, epsilon = 0.1);
+        approx::assert_abs_diff_eq!(values[Action::Up.index()], 0.0, epsilon = 0.1);
+
+        // ...and the finished player should agree
+        assert_eq!(agent.player().action(&observation), Action::Down);
+    }
+
+    #[test]
+    fn training_in_environments() {
+        let mut prng = ChaCha8Rng::seed_from_u64(42);
+        let mut agent = DqnAgent::new(
+            DqnSettings {
+                warmup: 50,
+                ..DqnSettings::default()
+            },
+            &mut prng,
+        );
+        let envs = VecEnv::new(
+            4,
+            &EnvSettings {
+                max_frames: 100,
+                ..EnvSettings::default()
+            },
+        );
+        let mut rollout = Rollout::new(envs, 0);
+
+        // Every rally gets cut short at 100 frames, so there should be at least 4 finished episodes in 400 steps
+        let returns = rollout.train(&mut agent, 400, &mut prng);
+
+        assert!(returns.len() >= 4);
+        assert_eq!(agent.buffer.len(), 400);
+        assert!(agent.epsilon() < 1.0);
+
+        // Episodes carry on from one chunk to the next, so chunks shorter than an episode still finish them
+        let mut rollout = Rollout::new(
+            VecEnv::new(
+                1,
+                &EnvSettings {
+                    max_frames: 100,
+                    ..EnvSettings::default()
+                },
+            ),
+            0,
+        );
+        let finished: usize = (0..10)
+            .map(|_| rollout.train(&mut agent, 30, &mut prng).len())
+            .sum();
+        assert!(finished >= 2);
+    }
+}
@@ -1,7 +1,6 @@
 use ggez::graphics::Rect;
 
 use crate::core::Ball;
-use crate::settings::Config;
 use crate::settings::*;
 
 #[derive(Clone, Debug)]
@@ -17,24 +16,40 @@ impl Eye {
     }
 
     pub fn from_vision(vision: &[f32]) -> Self {
-        log::warn!("New eye from vision: {:?}", vision);
+        log::debug!("New eye from vision: {:?}", vision);
         Self {
             photoreceptors: vision.to_owned(),
         }
     }
 
     pub fn step(&self, config: &Config, paddle: Rect, ball: &Ball) -> Self {
-        let mut vision: Vec<f32> = vec![0.0; config.eye_photoreceptors];
+        let mut eye = Self {
+            photoreceptors: vec![0.0; config.eye_photoreceptors],
+        };
+        eye.look(paddle, ball);
+
+        eye
+    }
+
+    /// Update what the eye can see in place, so an AI player can keep using the same eye every tick without allocating
+    pub fn look(&mut self, paddle: Rect, ball: &Ball) {
+        let vision = &mut self.photoreceptors;
 
         // Our 5 eye_photoreceptors are: Paddle Y, Ball X, Ball Y, Ball VX, Ball VY
-        vision[0] = paddle.center().y / (SCREEN_HEIGHT - paddle.h);
-        vision[1] = ball.rect.center().x / (SCREEN_WIDTH - ball.rect.w);
-        vision[2] = ball.rect.center().y / (SCREEN_HEIGHT - ball.rect.h);
+        // These are the raw values - it's up to the brain's Normalizer to scale them
+        vision[0] = paddle.center().y;
+        vision[1] = ball.rect.center().x;
+        vision[2] = ball.rect.center().y;
         vision[3] = ball.vel.x;
         vision[4] = ball.vel.y;
 
-        log::info!("vision: {:?}", &vision);
+        // A paddle on the right sees everything mirrored, as if it were playing on the left
+        // This way the same brain can play on either side of the table (e.g. during self-play)
+        if paddle.center().x > SCREEN_WIDTH / 2.0 {
+            vision[1] = SCREEN_WIDTH - vision[1];
+            vision[3] = -vision[3];
+        }
 
-        Self::from_vision(&vision)
+        log::debug!("vision: {:?}", &vision);
     }
 }
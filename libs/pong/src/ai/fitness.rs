@@ -0,0 +1,167 @@
+use serde::{Deserialize, Serialize};
+
+use crate::settings::*;
+use crate::sim::Performance;
+
+/// No AI ever scores less than this, as the roulette wheel can't pick from a population that all scored zero
+pub const MIN_FITNESS: f32 = 0.001;
+
+/// How the population is ranked when picking parents for the next generation
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Ranking {
+    /// Roulette wheel selection on the weighted sum of the objectives
+    Weighted,
+    /// NSGA-II style tournaments on Pareto fronts, using every objective with a non-zero weight
+    Pareto,
+}
+
+/// How much each part of an AI's performance counts towards its fitness
+/// Each objective is averaged over the serves, frames or misses played (and the penalties are negative) before it gets weighted
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FitnessWeights {
+    /// Returns made per serve
+    pub returns: f32,
+    /// How long the rallies last, as a fraction of MAX_RALLY_FRAMES
+    pub rally_length: f32,
+    /// Penalty for how far the paddle was from the ball when it missed, as a fraction of the screen height
+    pub miss_distance: f32,
+    /// Penalty for how much the paddle moves each frame, as a fraction of PADDLE_SPEED
+    pub energy: f32,
+    /// How long the AI lasts before missing, as a fraction of MAX_RALLY_FRAMES
+    pub survival: f32,
+    /// The fraction of points won (against the wall this only rewards missing less often, as the wall never misses)
+    pub wins: f32,
+}
+
+impl Default for FitnessWeights {
+    fn default() -> Self {
+        Self {
+            returns: 1.0,
+            rally_length: 0.0,
+            miss_distance: 0.5, // Near misses are better than wild ones
+            energy: 0.1,        // Discourage jittering about, but not too much
+            survival: 0.0,
+            wins: 1.0,
+        }
+    }
+}
+
+impl FitnessWeights {
+    /// Turn a performance into its scaled objectives, in the same order as the weights (bigger is always better)
+    pub fn objectives(performance: &Performance) -> [f32; 6] {
+        let serves = performance.serves.max(1) as f32;
+        let frames = performance.frames.max(1) as f32;
+
+        let returns = performance.returns as f32 / serves;
+        let rally_length = (performance.frames as f32 / serves / MAX_RALLY_FRAMES as f32).min(1.0);
+        let miss_distance = match performance.misses {
+            0 => 0.0,
+            misses => -performance.miss_distance / misses as f32 / SCREEN_HEIGHT,
+        };
+        let energy = -performance.movement / frames / PADDLE_SPEED;
+        let survival = match performance.misses {
+            0 => 1.0,
+            misses => {
+                (performance.frames as f32 / misses as f32 / MAX_RALLY_FRAMES as f32).min(1.0)
+            }
+        };
+        let wins = win_rate(performance.points, performance.points + performance.misses);
+
+        [returns, rally_length, miss_distance, energy, survival, wins]
+    }
+
+    fn weights(&self) -> [f32; 6] {
+        [
+            self.returns,
+            self.rally_length,
+            self.miss_distance,
+            self.energy,
+            self.survival,
+            self.wins,
+        ]
+    }
+
+    /// Combine every objective into a single fitness score, for the roulette wheel
+    pub fn fitness(&self, performance: &Performance) -> f32 {
+        let fitness: f32 = Self::objectives(performance)
+            .iter()
+            .zip(&self.weights())
+            .map(|(objective, weight)| objective * weight)
+            .sum();
+
+        fitness.max(MIN_FITNESS)
+    }
+
+    /// Only the objectives with a non-zero weight, for Pareto ranking
+    /// The size of a weight makes no difference here, but a negative weight flips the objective around
+    pub fn weighted_objectives(&self, performance: &Performance) -> Vec<f32> {
+        Self::objectives(performance)
+            .iter()
+            .zip(&self.weights())
+            .filter(|(_, weight)| **weight != 0.0)
+            .map(|(objective, weight)| objective * weight.signum())
+            .collect()
+    }
+}
+
+/// The fraction of points won, smoothed so that a player that hasn't played yet scores 0.5
+/// This also stops a brand new population that never scores from all ending up with the same fitness
+fn win_rate(won: usize, played: usize) -> f32 {
+    (won as f32 + 1.0) / (played as f32 + 2.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn smoothed_win_rate() {
+        approx::assert_relative_eq!(win_rate(0, 0), 0.5);
+        approx::assert_relative_eq!(win_rate(8, 8), 0.9);
+        approx::assert_relative_eq!(win_rate(0, 8), 0.1);
+    }
+
+    #[test]
+    fn shaped_fitness() {
+        let performance = Performance {
+            serves: 2,
+            returns: 6,
+            misses: 2,
+            points: 0,
+            frames: 1000,
+            miss_distance: 120.0,
+            movement: 800.0,
+        };
+
+        let objectives = FitnessWeights::objectives(&performance);
+        let expected = [
+            3.0,                             // 6 returns over 2 serves
+            500.0 / MAX_RALLY_FRAMES as f32, // 1000 frames over 2 serves
+            -60.0 / SCREEN_HEIGHT,           // 120 pixels over 2 misses
+            -0.8 / PADDLE_SPEED,             // 800 pixels over 1000 frames
+            500.0 / MAX_RALLY_FRAMES as f32, // 1000 frames over 2 misses
+            1.0 / 4.0,                       // No points won out of 2
+        ];
+        approx::assert_relative_eq!(objectives.as_ref(), expected.as_ref());
+
+        // Only returns count with these weights
+        let returns_only = FitnessWeights {
+            returns: 1.0,
+            rally_length: 0.0,
+            miss_distance: 0.0,
+            energy: 0.0,
+            survival: 0.0,
+            wins: 0.0,
+        };
+        approx::assert_relative_eq!(returns_only.fitness(&performance), 3.0);
+        assert_eq!(returns_only.weighted_objectives(&performance), vec![3.0]);
+
+        // A penalty on its own can't push the fitness below the minimum
+        let energy_only = FitnessWeights {
+            returns: 0.0,
+            energy: 1.0,
+            ..returns_only
+        };
+        approx::assert_relative_eq!(energy_only.fitness(&performance), MIN_FITNESS);
+    }
+}
@@ -0,0 +1,208 @@
+// Champion gauntlet
+// Puts a saved brain through the same seeded serves against the wall and every scripted bot, so brains from different training runs can be compared on equal footing
+
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+use std::fs::File;
+use std::io::BufWriter;
+
+use super::player::AiPlayer;
+use crate::bots::BotKind;
+use crate::cli::EvaluateOpt;
+use crate::env::Opponent;
+use crate::player::Policy;
+use crate::settings::*;
+use crate::sim::*;
+
+/// How many standard deviations either side of the hit rate the confidence interval covers (95%)
+const CONFIDENCE_Z: f32 = 1.96;
+
+/// How a player got on against one opponent in the gauntlet
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GauntletResult {
+    pub opponent: String,
+    pub serves: usize,
+    /// How many times the player returned the ball
+    pub hits: usize,
+    /// How many times the ball got past the player
+    pub misses: usize,
+    /// The fraction of the balls that reached the player that it returned
+    pub hit_rate: f32,
+    /// The 95% Wilson score interval around the hit rate
+    pub hit_rate_interval: (f32, f32),
+    /// How many times the ball got past the opponent
+    pub points_won: usize,
+    pub points_lost: usize,
+    /// How many frames each serve lasted on average
+    pub average_rally: f32,
+}
+
+impl GauntletResult {
+    /// Summarise a player's performance against the named opponent
+    pub fn new(opponent: &str, performance: &Performance) -> Self {
+        let (hit_rate, hit_rate_interval) = wilson_interval(
+            performance.returns,
+            performance.returns + performance.misses,
+        );
+
+        Self {
+            opponent: opponent.to_string(),
+            serves: performance.serves,
+            hits: performance.returns,
+            misses: performance.misses,
+            hit_rate,
+            hit_rate_interval,
+            points_won: performance.points,
+            points_lost: performance.misses,
+            average_rally: performance.frames as f32 / performance.serves.max(1) as f32,
+        }
+    }
+}
+
+/// Everyone the gauntlet plays against: the wall, then each of the scripted bots
+pub fn opponents() -> Vec<Opponent> {
+    std::iter::once(Opponent::Wall)
+        .chain(BotKind::ALL.iter().map(|&bot| Opponent::Bot(bot)))
+        .collect()
+}
+
+/// Play the player (on the left) against one opponent for the specified number of serves
+/// The serves (and the bot's own randomness) only depend on the seed, so every player faces exactly the same ones
+pub fn play_opponent(
+    player: &mut dyn Policy,
+    opponent: Opponent,
+    serves: usize,
+    seed: u64,
+) -> Performance {
+    let mut prng = ChaCha8Rng::seed_from_u64(seed);
+
+    match opponent {
+        Opponent::Wall => play_wall(player, serves, &mut prng).left,
+        Opponent::Bot(bot) => {
+            let mut bot = bot.build(&mut ChaCha8Rng::seed_from_u64(seed));
+            play_match(player, bot.as_mut(), serves, &mut prng).left
+        }
+    }
+}
+
+/// Play the player against every opponent, returning a result for each of them followed by one for all of them put together
+pub fn run_gauntlet(player: &mut dyn Policy, serves: usize, seed: u64) -> Vec<GauntletResult> {
+    let mut overall = Performance::default();
+
+    let mut results: Vec<GauntletResult> = opponents()
+        .into_iter()
+        .map(|opponent| {
+            let performance = play_opponent(player, opponent, serves, seed);
+            overall.merge(&performance);
+
+            GauntletResult::new(&opponent.to_string(), &performance)
+        })
+        .collect();
+
+    results.push(GauntletResult::new("overall", &overall));
+
+    results
+}
+
+/// The fraction of the trials that were successes, and the 95% Wilson score interval around it
+/// Unlike the usual normal approximation, this stays inside 0..1 and still makes sense when nearly every trial succeeds (or fails)
+pub fn wilson_interval(successes: usize, trials: usize) -> (f32, (f32, f32)) {
+    if trials == 0 {
+        return (0.0, (0.0, 1.0));
+    }
+
+    let n = trials as f32;
+    let p = successes as f32 / n;
+    let z2 = CONFIDENCE_Z * CONFIDENCE_Z;
+
+    let denominator = 1.0 + z2 / n;
+    let centre = (p + z2 / (2.0 * n)) / denominator;
+    let spread = CONFIDENCE_Z * (p * (1.0 - p) / n + z2 / (4.0 * n * n)).sqrt() / denominator;
+
+    (p, ((centre - spread).max(0.0), (centre + spread).min(1.0)))
+}
+
+/// Evaluate a saved brain with the options from the command line, logging how it did and optionally saving the results
+pub fn run(opts: &EvaluateOpt) -> std::io::Result<()> {
+    let mut player = AiPlayer::load(&Config::default(), &opts.brain)?;
+
+    log::warn!(
+        "Evaluating {:?} over {} serves per opponent (seed {})",
+        &opts.brain,
+        opts.serves,
+        opts.seed
+    );
+
+    let results = run_gauntlet(&mut player, opts.serves, opts.seed);
+
+    for result in &results {
+        log::warn!(
+            "{:>9}: hit rate {:.1}% (95% CI {:.1}-{:.1}%), points won {}, lost {}, average rally {:.1} frames",
+            result.opponent,
+            result.hit_rate * 100.0,
+            result.hit_rate_interval.0 * 100.0,
+            result.hit_rate_interval.1 * 100.0,
+            result.points_won,
+            result.points_lost,
+            result.average_rally
+        );
+    }
+
+    match &opts.out {
+        Some(path) => {
+            log::warn!("Saving results to {:?}", path);
+            let writer = BufWriter::new(File::create(path)?);
+
+            Ok(serde_json::to_writer_pretty(writer, &results)?)
+        }
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bots::TrackerBot;
+
+    #[test]
+    fn confidence_interval() {
+        let (rate, (low, high)) = wilson_interval(8, 10);
+        approx::assert_relative_eq!(rate, 0.8);
+        approx::assert_relative_eq!(low, 0.4902, epsilon = 1e-3);
+        approx::assert_relative_eq!(high, 0.9433, epsilon = 1e-3);
+
+        // A perfect record still leaves some doubt, but never goes past 1
+        let (rate, (low, high)) = wilson_interval(20, 20);
+        approx::assert_relative_eq!(rate, 1.0);
+        assert!(low < 1.0);
+        approx::assert_relative_eq!(high, 1.0);
+
+        assert_eq!(wilson_interval(0, 0), (0.0, (0.0, 1.0)));
+    }
+
+    #[test]
+    fn gauntlet() {
+        // A tracker can't miss, so it should return everything it gets to
+        let results = run_gauntlet(&mut TrackerBot::new(PADDLE_SPEED), 3, 42);
+
+        assert_eq!(results.len(), BotKind::ALL.len() + 2);
+        assert_eq!(results[0].opponent, "wall");
+        assert_eq!(results.last().unwrap().opponent, "overall");
+        for result in &results {
+            assert_eq!(result.misses, 0);
+            assert_eq!(result.points_lost, 0);
+        }
+        assert_eq!(results.last().unwrap().serves, 3 * (BotKind::ALL.len() + 1));
+
+        // The same seed gives the same serves, so a brain always gets the same results
+        let config = Config::default();
+        let brain = AiPlayer::random(&config, &mut ChaCha8Rng::seed_from_u64(42))
+            .brain()
+            .clone();
+        let first = run_gauntlet(&mut AiPlayer::from_brain(&config, brain.clone()), 3, 7);
+        let second = run_gauntlet(&mut AiPlayer::from_brain(&config, brain), 3, 7);
+        assert_eq!(first, second);
+    }
+}
@@ -0,0 +1,366 @@
+// Behaviour cloning
+// Rather than evolving brains from scratch, these record what another player (a human, or one of the scripted bots) saw and did each tick, then train a brain with backpropagation to do the same
+
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, LineWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use neural_network as nn;
+
+use nn::training::{Adam, Optimizer, Sgd};
+
+use super::brain::*;
+use super::decoder::Decoder;
+use super::eye::*;
+use crate::cli::ImitateOpt;
+use crate::player::{Policy, Snapshot};
+use crate::settings::*;
+use crate::sim::play_wall;
+
+/// What a player could see on one tick, and how far they moved their paddle in response
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Record {
+    /// The raw eye photoreceptors (i.e. before any normalizing), mirrored for a paddle on the right just like an AI would see them
+    pub vision: Vec<f32>,
+    pub movement: f32,
+}
+
+impl Record {
+    /// Record what the paddle in the snapshot could see, along with the move its player made
+    pub fn new(config: &Config, snapshot: &Snapshot, movement: f32) -> Self {
+        let eye = Eye::new(config).step(config, snapshot.paddle, &snapshot.ball);
+
+        Self {
+            vision: eye.photoreceptors,
+            movement,
+        }
+    }
+}
+
+/// Writes Records out as they happen, one JSON object per line
+/// Nothing is held back in memory, so a game that gets closed suddenly still keeps everything recorded up to that point
+pub struct Recorder<W: Write> {
+    writer: W,
+    config: Config,
+    count: usize,
+}
+
+impl Recorder<LineWriter<File>> {
+    /// Start recording to the specified file, adding to the end of it if it already exists (so several sessions can go into one dataset)
+    pub fn create<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+
+        Ok(Self::new(LineWriter::new(file)))
+    }
+}
+
+impl<W: Write> Recorder<W> {
+    /// Start recording to the provided writer
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            config: Config::default(),
+            count: 0,
+        }
+    }
+
+    /// Record what the paddle in the snapshot could see, along with the move its player made
+    pub fn record(&mut self, snapshot: &Snapshot, movement: f32) -> std::io::Result<()> {
+        let record = Record::new(&self.config, snapshot, movement);
+
+        serde_json::to_writer(&mut self.writer, &record)?;
+        writeln!(self.writer)?;
+        self.count += 1;
+
+        Ok(())
+    }
+
+    /// How many Records have been written so far
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Stop recording and get the writer back
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> std::fmt::Debug for Recorder<W> {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(fmt, "Recorder {{ count: {:?} }}", self.count)
+    }
+}
+
+/// A collection of Records to learn from
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Dataset {
+    records: Vec<Record>,
+}
+
+impl Dataset {
+    /// Load a dataset saved by a Recorder
+    pub fn load<P: AsRef<Path>>(path: P) -> std::io::Result<Dataset> {
+        Self::read(BufReader::new(File::open(path)?))
+    }
+
+    /// Read a dataset written by a Recorder, skipping any blank lines
+    pub fn read<R: BufRead>(reader: R) -> std::io::Result<Dataset> {
+        let mut records = Vec::new();
+
+        for line in reader.lines() {
+            let line = line?;
+
+            if !line.trim().is_empty() {
+                records.push(serde_json::from_str(&line)?);
+            }
+        }
+
+        Ok(Dataset { records })
+    }
+
+    /// Record a policy playing against the wall for the specified number of serves (e.g. to copy one of the scripted bots)
+    pub fn from_policy(policy: &mut dyn Policy, serves: usize, prng: &mut dyn RngCore) -> Dataset {
+        let mut watched = Watched {
+            policy,
+            config: Config::default(),
+            records: Vec::new(),
+        };
+
+        play_wall(&mut watched, serves, prng);
+
+        Dataset {
+            records: watched.records,
+        }
+    }
+
+    /// Add the records from another dataset to the end of this one
+    pub fn extend(&mut self, other: Dataset) {
+        self.records.extend(other.records);
+    }
+
+    pub fn records(&self) -> &[Record] {
+        &self.records
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+}
+
+/// Plays exactly like the policy it wraps, while writing down everything it sees and does
+struct Watched<'a> {
+    policy: &'a mut dyn Policy,
+    config: Config,
+    records: Vec<Record>,
+}
+
+impl Policy for Watched<'_> {
+    fn act(&mut self, snapshot: &Snapshot) -> f32 {
+        let movement = self.policy.act(snapshot);
+        self.records
+            .push(Record::new(&self.config, snapshot, movement));
+
+        movement
+    }
+
+    fn name(&self) -> &'static str {
+        self.policy.name()
+    }
+
+    fn reset(&mut self) {
+        self.policy.reset()
+    }
+}
+
+/// Which gradient descent optimizer to train with
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OptimizerKind {
+    Sgd,
+    Adam,
+}
+
+impl OptimizerKind {
+    /// Create a new optimizer of this kind
+    pub fn build(&self, learning_rate: f32) -> Box<dyn Optimizer> {
+        match self {
+            Self::Sgd => Box::new(Sgd::new(learning_rate)),
+            Self::Adam => Box::new(Adam::new(learning_rate)),
+        }
+    }
+}
+
+impl FromStr for OptimizerKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sgd" => Ok(Self::Sgd),
+            "adam" => Ok(Self::Adam),
+            _ => Err(format!("Unknown optimizer '{}' (expected sgd or adam)", s)),
+        }
+    }
+}
+
+/// Train a new brain to copy the moves in the dataset, returning the average loss of each epoch
+pub fn imitate(
+    config: &Config,
+    dataset: &Dataset,
+    optimizer: &mut dyn Optimizer,
+    epochs: usize,
+    batch_size: usize,
+    prng: &mut dyn RngCore,
+) -> (Brain, Vec<f32>) {
+    assert!(!dataset.is_empty());
+
+    let mut brain = Brain::random(config, prng);
+
+    // A running normalizer needs to have seen everything before it can scale any of it
+    for record in dataset.records() {
+        brain.observe(&Eye::from_vision(&record.vision));
+    }
+
+    let samples = brain.samples(dataset.records());
+    let losses = (0..epochs)
+        .map(|_| brain.train_epoch(prng, &samples, optimizer, batch_size))
+        .collect();
+
+    (brain, losses)
+}
+
+/// Train a brain to copy recorded games and/or a scripted bot headlessly, then save it
+pub fn run(opts: &ImitateOpt) -> std::io::Result<()> {
+    let decoder = crate::cli::get_decoder();
+
+    // The sign decoder's ReLU output can never go negative, so a brain using it could never learn to move up
+    if decoder == Decoder::Sign {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "The sign decoder can't copy moves up - pass --decoder discrete, proportional or target-y",
+        ));
+    }
+
+    let config = Config::default().with_decoder(decoder);
+    let mut prng = ChaCha8Rng::seed_from_u64(opts.seed);
+
+    let mut dataset = Dataset::default();
+
+    for path in &opts.dataset {
+        let recorded = Dataset::load(path)?;
+        log::warn!("Loaded {} records from {:?}", recorded.len(), path);
+        dataset.extend(recorded);
+    }
+
+    if let Some(bot) = opts.bot {
+        let mut bot = bot.build(&mut prng);
+        let watched = Dataset::from_policy(&mut bot, opts.serves, &mut prng);
+        log::warn!(
+            "Recorded {} moves from the {} bot",
+            watched.len(),
+            bot.name()
+        );
+        dataset.extend(watched);
+    }
+
+    if dataset.is_empty() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "There's nothing to imitate - pass at least one non-empty --dataset, or a --bot",
+        ));
+    }
+
+    let mut optimizer = opts.optimizer.build(opts.learning_rate);
+    let (brain, losses) = imitate(
+        &config,
+        &dataset,
+        optimizer.as_mut(),
+        opts.epochs,
+        opts.batch_size,
+        &mut prng,
+    );
+
+    for (epoch, loss) in losses.iter().enumerate() {
+        log::warn!("Epoch {}: loss {:.4}", epoch + 1, loss);
+    }
+
+    log::warn!("Saving imitation brain to {:?}", &opts.out);
+    brain.save(&opts.out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::decoder::Decoder;
+    use crate::bots::BotKind;
+    use crate::sim::Table;
+
+    #[test]
+    fn recording_round_trip() {
+        let mut prng = ChaCha8Rng::from_seed(Default::default());
+        let table = Table::new(false, &mut prng);
+
+        let mut recorder = Recorder::new(Vec::new());
+        recorder
+            .record(&table.left_snapshot(), -PADDLE_SPEED)
+            .unwrap();
+        recorder.record(&table.right_snapshot(), 0.0).unwrap();
+        assert_eq!(recorder.count(), 2);
+
+        let written = recorder.into_inner();
+        let dataset = Dataset::read(written.as_slice()).unwrap();
+
+        // Both paddles should see the ball as being the same distance away, since the right paddle sees everything mirrored
+        assert_eq!(dataset.len(), 2);
+        approx::assert_relative_eq!(
+            dataset.records()[0].vision[1],
+            dataset.records()[1].vision[1]
+        );
+        approx::assert_relative_eq!(dataset.records()[0].movement, -PADDLE_SPEED);
+        approx::assert_relative_eq!(dataset.records()[1].movement, 0.0);
+    }
+
+    #[test]
+    fn imitating_a_bot() {
+        // Seed a ChaCha8Rng for a predictable "random" number to use for testing
+        let mut prng = ChaCha8Rng::seed_from_u64(42);
+
+        let config = Config::default().with_decoder(Decoder::Discrete);
+        let mut bot = BotKind::Predictor.build(&mut prng);
+        let mut dataset = Dataset::from_policy(&mut bot, 1, &mut prng);
+
+        // The predictor never misses, so the rally only stops at MAX_RALLY_FRAMES - a few returns' worth is plenty
+        assert_eq!(dataset.len(), MAX_RALLY_FRAMES);
+        dataset.records.truncate(2000);
+
+        let mut optimizer = Adam::new(0.01);
+        let (brain, losses) = imitate(&config, &dataset, &mut optimizer, 10, 32, &mut prng);
+
+        assert_eq!(losses.len(), 10);
+        assert!(losses[9] < losses[0]);
+
+        // The copy should make the same choice as the bot most of the time
+        let samples = brain.samples(dataset.records());
+        let choice = |outputs: &[f32]| {
+            (0..outputs.len())
+                .max_by(|a, b| outputs[*a].partial_cmp(&outputs[*b]).unwrap())
+                .unwrap()
+        };
+        let agreements = samples
+            .iter()
+            .filter(|sample| {
+                choice(&brain.network().propagate(sample.inputs.clone())) == choice(&sample.targets)
+            })
+            .count();
+
+        assert!(agreements as f32 / samples.len() as f32 > 0.8);
+    }
+}
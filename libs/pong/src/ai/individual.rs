@@ -1,32 +1,43 @@
 use genetic_algorithm as ga;
 
 use ga::chromosome::*;
+use ga::evaluation::{Evaluable, Evaluation};
 use ga::individual::Individual;
 
-use super::player::*;
+use super::brain::*;
+use super::fitness::*;
+use crate::sim::Performance;
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct AiIndividual {
     pub chromosome: Chromosome,
     pub fitness: f32,
+    pub objectives: Vec<f32>,
+    pub evaluated: bool,
 }
 
 impl AiIndividual {
-    pub fn new(ai_player: &AiPlayer) -> Self {
-        log::warn!("Creating new AiIndividual from an AiPlayer...");
+    /// Create a new AiIndividual from a Brain, scoring how well it played using the provided FitnessWeights
+    pub fn new(brain: &Brain, performance: &Performance, weights: &FitnessWeights) -> Self {
         Self {
-            chromosome: ai_player.brain.to_chromosome(),
-            fitness: ai_player.score as f32,
+            chromosome: brain.to_chromosome(),
+            fitness: weights.fitness(performance),
+            objectives: weights.weighted_objectives(performance),
+            evaluated: true,
         }
     }
 }
 
 impl Individual for AiIndividual {
+    type Gene = f32;
+
     fn create(chromosome: Chromosome) -> Self {
-        log::warn!("Creating new AiIndividual from a Chromosome...");
+        log::debug!("Creating new AiIndividual from a Chromosome...");
         Self {
             chromosome,
             fitness: 0.0,
+            objectives: Vec::new(),
+            evaluated: false,
         }
     }
 
@@ -37,4 +48,20 @@ impl Individual for AiIndividual {
     fn fitness(&self) -> f32 {
         self.fitness
     }
+
+    fn objectives(&self) -> Vec<f32> {
+        self.objectives.clone()
+    }
+
+    fn is_evaluated(&self) -> bool {
+        self.evaluated
+    }
+}
+
+impl Evaluable for AiIndividual {
+    fn set_evaluation(&mut self, evaluation: Evaluation) {
+        self.fitness = evaluation.fitness;
+        self.objectives = evaluation.objectives;
+        self.evaluated = true;
+    }
 }
@@ -0,0 +1,249 @@
+// Network introspection
+// Lays a brain's network out as columns of nodes joined by weighted edges, with the value every node had on the last tick, so it can be drawn over the game
+
+use ggez::graphics::Rect;
+use ggez::mint::Point2;
+
+use std::collections::HashMap;
+
+use neural_network as nn;
+
+use nn::graph::{GraphNetwork, NodeRole};
+
+use super::brain::*;
+
+/// A weighted connection between two nodes in a Diagram, each given as (column, row)
+#[derive(Clone, Debug, PartialEq)]
+pub struct DiagramEdge {
+    pub from: (usize, usize),
+    pub to: (usize, usize),
+    pub weight: f32,
+}
+
+/// A brain's network laid out one column at a time from the inputs to the outputs, with the value of every node
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagram {
+    pub columns: Vec<Vec<f32>>,
+    pub edges: Vec<DiagramEdge>,
+}
+
+impl Diagram {
+    /// Lay out the brain's network, taking the value of every node from what it thought about last
+    /// A recurrent network only shows the weights from each layer's inputs, since every node's memory feeds back into itself anyway
+    pub fn new(brain: &Brain, thoughts: &Thoughts) -> Self {
+        let inputs = thoughts.inputs().to_vec();
+
+        match brain.network() {
+            Network::Layered(network) => {
+                let trace = network.propagate_with_trace(inputs);
+                let columns = std::iter::once(trace.inputs)
+                    .chain(trace.layers.into_iter().map(|layer| layer.post_activations))
+                    .collect();
+
+                let edges = network
+                    .layers()
+                    .iter()
+                    .enumerate()
+                    .flat_map(|(column, layer)| {
+                        layer_edges(
+                            column,
+                            layer
+                                .neurons()
+                                .into_iter()
+                                .map(|neuron| neuron.weights().to_vec()),
+                        )
+                    })
+                    .collect();
+
+                Self { columns, edges }
+            }
+            Network::Recurrent(network) => {
+                let memory = thoughts.memory().hidden();
+
+                let mut columns = vec![inputs];
+                for (index, layer) in network.recurrent_layers().iter().enumerate() {
+                    // Nothing is remembered until the brain has thought about something
+                    columns.push(match memory.get(index) {
+                        Some(hidden) => hidden.clone(),
+                        None => vec![0.0; layer.outputs()],
+                    });
+                }
+                columns.push(thoughts.outputs().to_vec());
+
+                let readout = network.readout().neurons();
+                let edges = network
+                    .recurrent_layers()
+                    .iter()
+                    .enumerate()
+                    .flat_map(|(column, layer)| {
+                        layer_edges(
+                            column,
+                            layer.input_weights().map(|weights| weights.to_vec()),
+                        )
+                    })
+                    .chain(layer_edges(
+                        network.recurrent_layers().len(),
+                        readout.iter().map(|neuron| neuron.weights().to_vec()),
+                    ))
+                    .collect();
+
+                Self { columns, edges }
+            }
+            Network::Graph(network) => Self::graph(network, inputs),
+        }
+    }
+
+    /// Lay out a graph network with every node one column further along than the furthest node feeding into it, and all the outputs in the last column
+    fn graph(network: &GraphNetwork, inputs: Vec<f32>) -> Self {
+        let nodes = network.nodes();
+        let values = network.activations(inputs);
+        let edges = network.edges();
+
+        let index: HashMap<usize, usize> = nodes
+            .iter()
+            .enumerate()
+            .map(|(idx, node)| (node.id, idx))
+            .collect();
+
+        // The nodes are already in an order where everything feeding into a node comes before it
+        let mut depths = vec![0; nodes.len()];
+        for (idx, node) in nodes.iter().enumerate() {
+            if node.role != NodeRole::Input {
+                depths[idx] = edges
+                    .iter()
+                    .filter(|edge| edge.to == node.id)
+                    .map(|edge| depths[index[&edge.from]] + 1)
+                    .max()
+                    .unwrap_or(1);
+            }
+        }
+
+        let last = nodes
+            .iter()
+            .zip(&depths)
+            .filter(|(node, _)| node.role == NodeRole::Hidden)
+            .map(|(_, depth)| depth + 1)
+            .max()
+            .unwrap_or(1);
+
+        let mut columns = vec![Vec::new(); last + 1];
+        let mut positions = Vec::with_capacity(nodes.len());
+        for ((node, depth), value) in nodes.iter().zip(&depths).zip(values) {
+            let column = match node.role {
+                NodeRole::Output => last,
+                _ => *depth,
+            };
+
+            positions.push((column, columns[column].len()));
+            columns[column].push(value);
+        }
+
+        let edges = edges
+            .iter()
+            .map(|edge| DiagramEdge {
+                from: positions[index[&edge.from]],
+                to: positions[index[&edge.to]],
+                weight: edge.weight,
+            })
+            .collect();
+
+        Self { columns, edges }
+    }
+
+    /// Where to draw every node so the diagram fills the area, with the columns spread out evenly from left to right
+    pub fn positions(&self, area: Rect) -> Vec<Vec<Point2<f32>>> {
+        let count = self.columns.len() as f32;
+
+        self.columns
+            .iter()
+            .enumerate()
+            .map(|(column, values)| {
+                let x = area.x + area.w * (column as f32 + 0.5) / count;
+
+                (0..values.len())
+                    .map(|row| Point2 {
+                        x,
+                        y: area.y + area.h * (row as f32 + 0.5) / values.len() as f32,
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+/// Connect every node in a column to every node in the next one, given the weights each node in the next column gives the column before it
+fn layer_edges(
+    column: usize,
+    weights: impl Iterator<Item = Vec<f32>>,
+) -> impl Iterator<Item = DiagramEdge> {
+    weights.enumerate().flat_map(move |(to, weights)| {
+        weights
+            .into_iter()
+            .enumerate()
+            .map(move |(from, weight)| DiagramEdge {
+                from: (column, from),
+                to: (column + 1, to),
+                weight,
+            })
+    })
+}
+
+/// What a player is thinking about right now, for drawing over the game
+#[derive(Clone, Debug, PartialEq)]
+pub struct Inspection {
+    pub diagram: Diagram,
+    /// How far the player decided to move its paddle
+    pub movement: f32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::player::AiPlayer;
+    use crate::player::Policy;
+    use crate::settings::*;
+    use crate::sim::Table;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    #[test]
+    fn layered_diagram() {
+        // Seed a ChaCha8Rng for a predictable "random" number to use for testing
+        let mut prng = ChaCha8Rng::from_seed(Default::default());
+
+        let config = Config::default();
+        let mut ai_player = AiPlayer::random(&config, &mut prng);
+
+        // Nothing to show until the player has thought about something
+        assert!(ai_player.inspect().is_none());
+
+        let table = Table::new(true, &mut prng);
+        let movement = ai_player.act(&table.left_snapshot());
+        let inspection = ai_player.inspect().unwrap();
+        let diagram = &inspection.diagram;
+
+        approx::assert_relative_eq!(inspection.movement, movement);
+
+        // Eye -> hidden -> output, fully connected
+        let sizes: Vec<usize> = diagram.columns.iter().map(Vec::len).collect();
+        assert_eq!(sizes, vec![5, config.brain_neurons, 1]);
+        assert_eq!(
+            diagram.edges.len(),
+            5 * config.brain_neurons + config.brain_neurons
+        );
+
+        // The output node should show exactly what the brain responded with
+        let snapshot = table.left_snapshot();
+        let eye = crate::ai::eye::Eye::new(&config).step(&config, snapshot.paddle, &snapshot.ball);
+        let response = ai_player.brain.step(&config, &eye);
+        approx::assert_relative_eq!(diagram.columns[2].as_slice(), response.as_slice());
+
+        // Every node should be drawn inside the area
+        let area = Rect::new(10.0, 20.0, 300.0, 200.0);
+        assert!(diagram
+            .positions(area)
+            .iter()
+            .flatten()
+            .all(|point| area.contains(*point)));
+    }
+}
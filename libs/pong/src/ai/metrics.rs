@@ -0,0 +1,379 @@
+// Training metrics
+// Writes out how every generation (and every individual in it) did while training, as CSV or newline-delimited JSON, so runs can be analysed afterwards
+
+use serde::{Deserialize, Serialize};
+
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use genetic_algorithm::statistics::Statistics;
+
+use super::brain::Brain;
+use super::trainer::Trainer;
+
+/// Which kind of file the metrics are written to
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    /// Comma separated values, with a header row
+    Csv,
+    /// One JSON object per line
+    Ndjson,
+}
+
+impl Format {
+    /// Work out the format from a file's extension: .csv, or .ndjson/.jsonl/.json
+    pub fn from_path<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let extension = path
+            .as_ref()
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_lowercase);
+
+        match extension.as_deref() {
+            Some("csv") => Ok(Self::Csv),
+            Some("ndjson") | Some("jsonl") | Some("json") => Ok(Self::Ndjson),
+            _ => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!(
+                    "Unknown metrics format for {:?} (expected a .csv, .ndjson or .jsonl file)",
+                    path.as_ref()
+                ),
+            )),
+        }
+    }
+}
+
+/// How one generation did
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GenerationMetrics {
+    pub generation: usize,
+    pub min_fitness: f32,
+    pub avg_fitness: f32,
+    pub max_fitness: f32,
+    pub species: usize,
+    /// How strongly the next generation was mutated (see Trainer::mutation_rate())
+    pub mutation_rate: f32,
+    /// How many seconds it took to evaluate and breed the generation
+    pub wall_time: f64,
+    /// How many seconds have passed since training started (or was resumed)
+    pub elapsed: f64,
+    /// The fingerprint of the champion's weights, in hex
+    pub champion: String,
+}
+
+impl GenerationMetrics {
+    const CSV_HEADER: &'static str = "generation,min_fitness,avg_fitness,max_fitness,species,mutation_rate,wall_time,elapsed,champion";
+
+    /// Collect the metrics for the generation the Trainer has just evolved
+    pub fn new(
+        trainer: &Trainer,
+        statistics: &Statistics,
+        wall_time: Duration,
+        elapsed: Duration,
+    ) -> Self {
+        Self {
+            generation: trainer.generation(),
+            min_fitness: statistics.min_fitness(),
+            avg_fitness: statistics.avg_fitness(),
+            max_fitness: statistics.max_fitness(),
+            species: trainer.species_count(),
+            mutation_rate: trainer.mutation_rate(),
+            wall_time: wall_time.as_secs_f64(),
+            elapsed: elapsed.as_secs_f64(),
+            champion: trainer.champion().map(fingerprint).unwrap_or_default(),
+        }
+    }
+
+    fn csv_row(&self) -> String {
+        format!(
+            "{},{},{},{},{},{},{},{},{}",
+            self.generation,
+            self.min_fitness,
+            self.avg_fitness,
+            self.max_fitness,
+            self.species,
+            self.mutation_rate,
+            self.wall_time,
+            self.elapsed,
+            self.champion
+        )
+    }
+}
+
+/// How one individual in a generation did
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct IndividualMetrics {
+    pub generation: usize,
+    /// Where the individual was in the population
+    pub individual: usize,
+    pub fitness: f32,
+    /// The fingerprint of the individual's weights, in hex (matching the champion's, for whichever individual that was)
+    pub brain: String,
+}
+
+impl IndividualMetrics {
+    const CSV_HEADER: &'static str = "generation,individual,fitness,brain";
+
+    /// Pair up each brain that was evaluated with the fitness it got
+    pub fn generation(generation: usize, brains: &[Brain], fitnesses: &[f32]) -> Vec<Self> {
+        brains
+            .iter()
+            .zip(fitnesses)
+            .enumerate()
+            .map(|(individual, (brain, fitness))| Self {
+                generation,
+                individual,
+                fitness: *fitness,
+                brain: fingerprint(brain),
+            })
+            .collect()
+    }
+
+    fn csv_row(&self) -> String {
+        format!(
+            "{},{},{},{}",
+            self.generation, self.individual, self.fitness, self.brain
+        )
+    }
+}
+
+/// A brain's fingerprint as a hex string
+fn fingerprint(brain: &Brain) -> String {
+    format!("{:016x}", brain.fingerprint())
+}
+
+/// Writes the metrics of each generation to one writer, and the metrics of every individual to another
+/// Everything is flushed after each generation, so a run that gets interrupted still keeps what it's written
+pub struct MetricsWriter<W: Write> {
+    format: Format,
+    generations: W,
+    individuals: W,
+}
+
+impl MetricsWriter<BufWriter<File>> {
+    /// Start writing the generations' metrics to the specified file, and the individuals' to a file next to it with "-individuals" added to the name
+    /// The format is picked from the file's extension. When carrying on from a checkpoint of the specified generation, the files are kept but any rows for later generations (written after the checkpoint was saved) are dropped, and the CSV headers are only written to empty files
+    pub fn create<P: AsRef<Path>>(path: P, resumed: Option<usize>) -> std::io::Result<Self> {
+        let path = path.as_ref();
+        let format = Format::from_path(path)?;
+
+        let open = |path: &Path| -> std::io::Result<(BufWriter<File>, bool)> {
+            if let Some(generation) = resumed {
+                if path.exists() {
+                    let contents = std::fs::read_to_string(path)?;
+                    std::fs::write(path, rows_until(&contents, format, generation))?;
+                }
+            }
+
+            let file = OpenOptions::new()
+                .create(true)
+                .write(true)
+                .append(resumed.is_some())
+                .truncate(resumed.is_none())
+                .open(path)?;
+            let empty = file.metadata()?.len() == 0;
+
+            Ok((BufWriter::new(file), empty))
+        };
+
+        let (generations, generations_empty) = open(path)?;
+        let (individuals, individuals_empty) = open(&individuals_path(path))?;
+
+        let mut writer = Self {
+            format,
+            generations,
+            individuals,
+        };
+        writer.write_headers(generations_empty, individuals_empty)?;
+
+        Ok(writer)
+    }
+}
+
+/// Keep the lines of a metrics file up to and including the specified generation, along with anything that isn't a row (e.g. a CSV header)
+fn rows_until(contents: &str, format: Format, generation: usize) -> String {
+    contents
+        .lines()
+        .filter(|line| {
+            let row = match format {
+                Format::Csv => line
+                    .split(',')
+                    .next()
+                    .and_then(|field| field.parse::<u64>().ok()),
+                Format::Ndjson => serde_json::from_str::<serde_json::Value>(line)
+                    .ok()
+                    .and_then(|row| row["generation"].as_u64()),
+            };
+
+            !matches!(row, Some(row) if row > generation as u64)
+        })
+        .map(|line| format!("{}\n", line))
+        .collect()
+}
+
+impl<W: Write> MetricsWriter<W> {
+    /// Start writing to the provided writers
+    pub fn new(format: Format, generations: W, individuals: W) -> std::io::Result<Self> {
+        let mut writer = Self {
+            format,
+            generations,
+            individuals,
+        };
+        writer.write_headers(true, true)?;
+
+        Ok(writer)
+    }
+
+    fn write_headers(&mut self, generations: bool, individuals: bool) -> std::io::Result<()> {
+        if self.format == Format::Csv {
+            if generations {
+                writeln!(self.generations, "{}", GenerationMetrics::CSV_HEADER)?;
+            }
+            if individuals {
+                writeln!(self.individuals, "{}", IndividualMetrics::CSV_HEADER)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Write out a generation's metrics, along with those of every individual in it
+    pub fn write(
+        &mut self,
+        generation: &GenerationMetrics,
+        individuals: &[IndividualMetrics],
+    ) -> std::io::Result<()> {
+        match self.format {
+            Format::Csv => {
+                writeln!(self.generations, "{}", generation.csv_row())?;
+                for individual in individuals {
+                    writeln!(self.individuals, "{}", individual.csv_row())?;
+                }
+            }
+            Format::Ndjson => {
+                serde_json::to_writer(&mut self.generations, generation)?;
+                writeln!(self.generations)?;
+                for individual in individuals {
+                    serde_json::to_writer(&mut self.individuals, individual)?;
+                    writeln!(self.individuals)?;
+                }
+            }
+        }
+
+        self.generations.flush()?;
+        self.individuals.flush()
+    }
+
+    /// Stop writing, and get the writers back
+    pub fn into_inner(self) -> (W, W) {
+        (self.generations, self.individuals)
+    }
+}
+
+impl<W: Write> std::fmt::Debug for MetricsWriter<W> {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(fmt, "MetricsWriter {{ format: {:?} }}", self.format)
+    }
+}
+
+/// Where the individuals' metrics go for a given metrics file, e.g. metrics.csv -> metrics-individuals.csv
+pub fn individuals_path<P: AsRef<Path>>(path: P) -> PathBuf {
+    let path = path.as_ref();
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let mut name = format!("{}-individuals", stem);
+    if let Some(extension) = path.extension() {
+        name.push('.');
+        name.push_str(&extension.to_string_lossy());
+    }
+
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::trainer::{small_config, Arena};
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    #[test]
+    fn metrics_formats() {
+        assert_eq!(Format::from_path("run.csv").unwrap(), Format::Csv);
+        assert_eq!(Format::from_path("run.NDJSON").unwrap(), Format::Ndjson);
+        assert!(Format::from_path("run.txt").is_err());
+        assert_eq!(
+            individuals_path("runs/metrics.csv"),
+            PathBuf::from("runs/metrics-individuals.csv")
+        );
+
+        // Seed a ChaCha8Rng for a predictable "random" number to use for testing
+        let mut prng = ChaCha8Rng::seed_from_u64(42);
+        let mut trainer = Trainer::new(small_config(), Arena::Wall, &mut prng);
+
+        let brains = trainer.population().to_vec();
+        let statistics = trainer.evolve(&mut prng);
+        let generation = GenerationMetrics::new(
+            &trainer,
+            &statistics,
+            Duration::from_millis(1500),
+            Duration::from_secs(3),
+        );
+        let individuals =
+            IndividualMetrics::generation(trainer.generation(), &brains, trainer.fitnesses());
+
+        assert_eq!(generation.generation, 1);
+        approx::assert_relative_eq!(generation.wall_time, 1.5);
+        assert_eq!(individuals.len(), 4);
+
+        // The champion is one of the individuals that was evaluated
+        assert!(individuals
+            .iter()
+            .any(|individual| individual.brain == generation.champion));
+
+        // CSV gets a header and then one row per generation or individual
+        let mut csv = MetricsWriter::new(Format::Csv, Vec::new(), Vec::new()).unwrap();
+        csv.write(&generation, &individuals).unwrap();
+        let (generations, individual_rows) = csv.into_inner();
+        let generations = String::from_utf8(generations).unwrap();
+        let individual_rows = String::from_utf8(individual_rows).unwrap();
+
+        assert_eq!(generations.lines().count(), 2);
+        assert!(generations.starts_with("generation,min_fitness"));
+        assert_eq!(individual_rows.lines().count(), 5);
+
+        // Newline-delimited JSON reads back into the same metrics
+        let mut ndjson = MetricsWriter::new(Format::Ndjson, Vec::new(), Vec::new()).unwrap();
+        ndjson.write(&generation, &individuals).unwrap();
+        let (generations, individual_rows) = ndjson.into_inner();
+
+        let read: GenerationMetrics = serde_json::from_slice(&generations).unwrap();
+        assert_eq!(read, generation);
+        assert_eq!(
+            String::from_utf8(individual_rows).unwrap().lines().count(),
+            4
+        );
+    }
+
+    #[test]
+    fn resumed_metrics() {
+        // Rows for generations after the checkpoint get dropped, so they aren't written twice
+        let csv = "generation,individual,fitness,brain\n1,0,0.5,ab\n2,0,0.7,cd\n3,0,0.9,ef\n";
+        assert_eq!(
+            rows_until(csv, Format::Csv, 2),
+            "generation,individual,fitness,brain\n1,0,0.5,ab\n2,0,0.7,cd\n"
+        );
+
+        let ndjson = "{\"generation\":1}\n{\"generation\":2}\n{\"generation\":3}\n";
+        assert_eq!(
+            rows_until(ndjson, Format::Ndjson, 1),
+            "{\"generation\":1}\n"
+        );
+        assert_eq!(rows_until(ndjson, Format::Ndjson, 3), ndjson);
+    }
+}
@@ -1,4 +1,15 @@
 pub mod brain;
-mod eye;
+pub mod checkpoint;
+pub mod decoder;
+pub mod dqn;
+pub(crate) mod eye;
+pub mod fitness;
+pub mod gauntlet;
+pub mod imitation;
 mod individual;
+pub mod inspect;
+pub mod metrics;
+pub mod normalizer;
 pub mod player;
+pub mod tournament;
+pub mod trainer;
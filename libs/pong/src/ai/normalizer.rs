@@ -0,0 +1,159 @@
+use serde::{Deserialize, Serialize};
+
+use crate::settings::*;
+
+/// Which kind of Normalizer a Config should build for its brains
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Normalization {
+    /// Scale each input to -1..=1 using the fixed SENSOR_RANGES from the settings
+    Fixed,
+    /// Standardise each input using the running mean and variance seen during training
+    Running,
+}
+
+/// Rescales the raw eye photoreceptor values before they are fed into a neural network
+/// This is stored inside the Brain, so a saved brain always sees inputs scaled the same way it was trained with
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Normalizer {
+    Fixed {
+        ranges: Vec<(f32, f32)>,
+    },
+    Running {
+        count: u64,
+        mean: Vec<f32>,
+        m2: Vec<f32>,
+    },
+}
+
+impl Normalizer {
+    /// Create a new Normalizer of the kind selected in the provided Config
+    pub fn new(config: &Config) -> Self {
+        match config.normalization {
+            Normalization::Fixed => {
+                // There's no sensible way to guess the range of a photoreceptor we don't know about
+                assert_eq!(config.eye_photoreceptors, SENSOR_RANGES.len());
+
+                Self::fixed(&SENSOR_RANGES)
+            }
+            Normalization::Running => Self::running(config.eye_photoreceptors),
+        }
+    }
+
+    /// Create a new Normalizer that maps each input from the specified (min, max) range into -1..=1
+    pub fn fixed(ranges: &[(f32, f32)]) -> Self {
+        assert!(ranges.iter().all(|(min, max)| max > min));
+
+        Self::Fixed {
+            ranges: ranges.to_vec(),
+        }
+    }
+
+    /// Create a new Normalizer that hasn't seen any inputs yet
+    pub fn running(inputs: usize) -> Self {
+        Self::Running {
+            count: 0,
+            mean: vec![0.0; inputs],
+            m2: vec![0.0; inputs],
+        }
+    }
+
+    /// Update the running statistics with a new set of inputs (this does nothing for a Fixed Normalizer)
+    pub fn observe(&mut self, inputs: &[f32]) {
+        if let Self::Running { count, mean, m2 } = self {
+            assert_eq!(inputs.len(), mean.len());
+
+            // Welford's online algorithm, so we never need to keep hold of the old inputs
+            *count += 1;
+
+            for ((input, mean), m2) in inputs.iter().zip(mean.iter_mut()).zip(m2.iter_mut()) {
+                let delta = input - *mean;
+                *mean += delta / *count as f32;
+                *m2 += delta * (input - *mean);
+            }
+        }
+    }
+
+    /// Rescale the provided inputs
+    pub fn normalize(&self, inputs: &[f32]) -> Vec<f32> {
+        let mut outputs = vec![0.0; inputs.len()];
+        self.normalize_into(inputs, &mut outputs);
+
+        outputs
+    }
+
+    /// Rescale the provided inputs, writing them into the provided slice instead of allocating a new Vec
+    pub fn normalize_into(&self, inputs: &[f32], outputs: &mut [f32]) {
+        assert_eq!(inputs.len(), outputs.len());
+
+        match self {
+            Self::Fixed { ranges } => {
+                assert_eq!(inputs.len(), ranges.len());
+
+                for ((output, input), (min, max)) in outputs.iter_mut().zip(inputs).zip(ranges) {
+                    *output = 2.0 * (input - min) / (max - min) - 1.0;
+                }
+            }
+            Self::Running { count, mean, m2 } => {
+                assert_eq!(inputs.len(), mean.len());
+
+                for (((output, input), mean), m2) in
+                    outputs.iter_mut().zip(inputs).zip(mean).zip(m2)
+                {
+                    *output = (input - mean) / std_dev(*count, *m2);
+                }
+            }
+        }
+    }
+}
+
+/// Return the standard deviation of an input seen count times so far (this is always 1.0 until at least 2 inputs have been seen)
+fn std_dev(count: u64, m2: f32) -> f32 {
+    match count {
+        0 | 1 => 1.0,
+        // Stop inputs that never change from blowing up to infinity
+        _ => (m2 / (count - 1) as f32).sqrt().max(f32::EPSILON),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed_normalization() {
+        let normalizer = Normalizer::fixed(&[(0.0, 600.0), (-3.0, 3.0)]);
+
+        // The bottom, middle and top of each range should end up at -1.0, 0.0 and 1.0
+        let bottom = normalizer.normalize(&[0.0, -3.0]);
+        let middle = normalizer.normalize(&[300.0, 0.0]);
+        let top = normalizer.normalize(&[600.0, 3.0]);
+
+        approx::assert_relative_eq!(bottom.as_slice(), [-1.0, -1.0].as_ref());
+        approx::assert_relative_eq!(middle.as_slice(), [0.0, 0.0].as_ref());
+        approx::assert_relative_eq!(top.as_slice(), [1.0, 1.0].as_ref());
+
+        // Observing inputs should have no effect on a fixed normalizer
+        let mut observed = normalizer.clone();
+        observed.observe(&[1000.0, 1000.0]);
+        assert_eq!(observed, normalizer);
+    }
+
+    #[test]
+    fn running_normalization() {
+        let mut normalizer = Normalizer::running(2);
+
+        // Before seeing anything, inputs should pass through untouched
+        let untouched = normalizer.normalize(&[5.0, -2.0]);
+        approx::assert_relative_eq!(untouched.as_slice(), [5.0, -2.0].as_ref());
+
+        // The first input has a mean of 4.0 and a sample std dev of 2.0, the second never changes
+        for input in &[[2.0, 7.0], [4.0, 7.0], [6.0, 7.0]] {
+            normalizer.observe(input);
+        }
+
+        let normalized = normalizer.normalize(&[8.0, 7.0]);
+
+        approx::assert_relative_eq!(normalized[0], 2.0);
+        approx::assert_relative_eq!(normalized[1], 0.0);
+    }
+}
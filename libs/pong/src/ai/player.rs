@@ -1,17 +1,22 @@
 use rand::RngCore;
 
-use crate::player::Move;
+use std::path::Path;
+
+use crate::player::Policy;
 use crate::player::Snapshot;
 use crate::settings::*;
 
 use super::brain::*;
 use super::eye::*;
+use super::inspect::*;
 
 pub struct AiPlayer {
     pub(crate) brain: Brain,
     pub(crate) eye: Eye,
-    pub(crate) config: Config,
-    pub(crate) score: i16,
+    pub(crate) thoughts: Thoughts,
+    /// How far the paddle was moved on the last tick
+    pub(crate) movement: f32,
+    pub(crate) learning: bool,
 }
 
 impl AiPlayer {
@@ -22,11 +27,41 @@ impl AiPlayer {
         AiPlayer {
             brain,
             eye: Eye::new(config),
-            config: config.clone(),
-            score: 0,
+            thoughts: Thoughts::default(),
+            movement: 0.0,
+            learning: false,
+        }
+    }
+
+    /// Create a new AI player that thinks with the provided brain
+    pub fn from_brain(config: &Config, brain: Brain) -> AiPlayer {
+        AiPlayer {
+            brain,
+            eye: Eye::new(config),
+            thoughts: Thoughts::default(),
+            movement: 0.0,
+            learning: false,
         }
     }
 
+    /// Create a new AI player using a brain previously saved with Brain::save()
+    pub fn load<P: AsRef<Path>>(config: &Config, path: P) -> std::io::Result<AiPlayer> {
+        log::warn!("Loading AI player brain from {:?}", path.as_ref());
+        let brain = Brain::load(path)?;
+
+        Ok(AiPlayer::from_brain(config, brain))
+    }
+
+    /// Get immutable borrow of the brain
+    pub fn brain(&self) -> &Brain {
+        &self.brain
+    }
+
+    /// Whether the brain should keep updating its input statistics as it plays (i.e. while training)
+    pub fn set_learning(&mut self, learning: bool) {
+        self.learning = learning;
+    }
+
     fn random_move() -> f32 {
         use rand::Rng;
 
@@ -41,35 +76,53 @@ impl AiPlayer {
         }
     }
 
-    pub fn step(&self, _snapshot: &Snapshot) -> f32 {
+    /// Look at the game, think about it, and return how far to move the paddle
+    pub fn step(&mut self, _snapshot: &Snapshot) -> f32 {
         // Break out the paddle and ball from the snapshot of game state
         let paddle = _snapshot.paddle;
-        let ball = _snapshot.ball.clone();
+        let ball = &_snapshot.ball;
 
         // First, check what we can see
-        let eye = self.eye.step(&self.config, paddle, &ball);
+        self.eye.look(paddle, ball);
+
+        // If we're still training, keep track of the range of things we've seen
+        if self.learning {
+            self.brain.observe(&self.eye);
+        }
 
         // Second, think about it
-        self.brain.step(&self.config, &eye)
+        let response = self.brain.think(&self.eye, &mut self.thoughts);
+
+        // Finally, decide how far to move the paddle
+        self.movement = self.brain.decoder().decode(response, paddle);
+        self.movement
     }
 }
 
-impl Move for AiPlayer {
-    fn make_move(&self, ctx: &mut ggez::Context, _snapshot: &Snapshot) -> f32 {
-        let desired_move = self.step(_snapshot);
-
-        if desired_move < 0.0 {
-            -PADDLE_SPEED
-        } else if desired_move > 0.0 {
-            PADDLE_SPEED
-        } else {
-            0.0
-        }
+impl Policy for AiPlayer {
+    fn act(&mut self, snapshot: &Snapshot) -> f32 {
+        self.step(snapshot)
     }
 
     fn name(&self) -> &'static str {
         "AI"
     }
+
+    fn reset(&mut self) {
+        self.thoughts.forget();
+    }
+
+    fn inspect(&self) -> Option<Inspection> {
+        // Nothing to show until the first tick
+        if self.thoughts.inputs().is_empty() {
+            return None;
+        }
+
+        Some(Inspection {
+            diagram: Diagram::new(&self.brain, &self.thoughts),
+            movement: self.movement,
+        })
+    }
 }
 
 #[cfg(test)]
@@ -90,17 +143,30 @@ mod tests {
         let ai_player = AiPlayer::random(&config, &mut prng);
 
         // A default config produces 2 layers...
-        assert_eq!(ai_player.brain.network().layers().len(), 2);
+        assert_eq!(
+            ai_player.brain.network().layered().unwrap().layers().len(),
+            2
+        );
 
         // ...with 15 neurons in the first layer
-        assert_eq!(ai_player.brain.network().layers()[0].neurons().len(), 15);
+        assert_eq!(
+            ai_player.brain.network().layered().unwrap().layers()[0]
+                .neurons()
+                .len(),
+            15
+        );
 
         // ...and 1 neuron in the second layer
-        assert_eq!(ai_player.brain.network().layers()[1].neurons().len(), 1);
+        assert_eq!(
+            ai_player.brain.network().layered().unwrap().layers()[1]
+                .neurons()
+                .len(),
+            1
+        );
 
         // Check the bias of the first neuron of the first layer
         approx::assert_relative_eq!(
-            ai_player.brain.network().layers()[0].neurons()[0].bias(),
+            ai_player.brain.network().layered().unwrap().layers()[0].neurons()[0].bias(),
             -0.6255188
         );
 
@@ -122,17 +188,39 @@ mod tests {
             0.5295007,
             0.4402212,
         ];
-        approx::assert_relative_eq!(
-            ai_player.brain.network().layers()[1].neurons()[0].weights(),
-            expected_weights.as_slice()
-        );
+        let neurons = ai_player.brain.network().layered().unwrap().layers()[1].neurons();
+        approx::assert_relative_eq!(neurons[0].weights(), expected_weights.as_slice());
 
         // Check the number of weights for the second neuron of the first layer (this is the same as the number of inputs it takes)
         assert_eq!(
-            ai_player.brain.network().layers()[0].neurons()[1]
+            ai_player.brain.network().layered().unwrap().layers()[0].neurons()[1]
                 .weights()
                 .len(),
             5
         );
     }
+
+    #[test]
+    fn recurrent_ai_player() {
+        // Seed a ChaCha8Rng for a predictable "random" number to use for testing
+        let mut prng = ChaCha8Rng::from_seed(Default::default());
+
+        let config = Config {
+            topology: Topology::Recurrent(neural_network::recurrent::Cell::Gru),
+            ..Config::default().with_decoder(crate::ai::decoder::Decoder::Proportional)
+        };
+        let mut ai_player = AiPlayer::random(&config, &mut prng);
+
+        let table = crate::sim::Table::new(true, &mut prng);
+        let snapshot = table.left_snapshot();
+
+        // Seeing exactly the same thing twice in a row should still get a different response, since the player remembers the first time
+        let first = ai_player.act(&snapshot);
+        let second = ai_player.act(&snapshot);
+        assert!((first - second).abs() > 1e-6);
+
+        // A new serve wipes the memory, so it's as if the first time never happened
+        ai_player.reset();
+        approx::assert_relative_eq!(ai_player.act(&snapshot), first);
+    }
 }
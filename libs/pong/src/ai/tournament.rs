@@ -0,0 +1,413 @@
+// Round-robin tournament
+// Plays every saved brain in a directory (and the scripted bots) against every other over seeded two player matches, and ranks them with Elo ratings
+
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+use super::brain::Brain;
+use super::player::AiPlayer;
+use crate::bots::BotKind;
+use crate::cli::TournamentOpt;
+use crate::player::Policy;
+use crate::settings::*;
+use crate::sim::play_match;
+
+/// The rating every entrant starts the tournament with
+pub const INITIAL_RATING: f32 = 1500.0;
+
+/// Someone taking part in the tournament
+#[derive(Clone, Debug)]
+pub enum Entrant {
+    /// A saved brain, named after the file it was loaded from
+    Brain {
+        name: String,
+        brain: Brain,
+    },
+    Bot(BotKind),
+}
+
+impl Entrant {
+    pub fn name(&self) -> String {
+        match self {
+            Self::Brain { name, .. } => name.clone(),
+            Self::Bot(bot) => bot.to_string(),
+        }
+    }
+
+    /// Create a fresh player for a match (the human-like bots take their own randomness from the provided PRNG, which shouldn't be the one the serves come from)
+    fn build(&self, config: &Config, prng: &mut ChaCha8Rng) -> Box<dyn Policy> {
+        match self {
+            Self::Brain { brain, .. } => Box::new(AiPlayer::from_brain(config, brain.clone())),
+            Self::Bot(bot) => bot.build(prng),
+        }
+    }
+}
+
+/// Load every brain saved in a directory, in order of their file names
+/// JSON files that aren't brains (e.g. checkpoints, or gauntlet results) get skipped with a warning
+pub fn load_brains<P: AsRef<Path>>(directory: P) -> std::io::Result<Vec<Entrant>> {
+    let mut paths: Vec<_> = std::fs::read_dir(directory)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<std::io::Result<_>>()?;
+    paths.retain(|path| path.extension().and_then(|extension| extension.to_str()) == Some("json"));
+    paths.sort();
+
+    let mut entrants = Vec::new();
+    for path in paths {
+        match Brain::load(&path) {
+            Ok(brain) => entrants.push(Entrant::Brain {
+                name: path
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+                brain,
+            }),
+            Err(e) => log::warn!("Skipping {:?}, which isn't a brain: {}", path, e),
+        }
+    }
+
+    Ok(entrants)
+}
+
+/// Stop adjusting the ratings once none of them moves by more than this in a pass
+const RATING_TOLERANCE: f32 = 1e-3;
+
+/// Give up adjusting the ratings after this many passes, even if they're still moving
+const MAX_RATING_PASSES: usize = 10_000;
+
+/// Elo ratings, worked out from every match result at once
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Elo {
+    /// How far a rating moves on each pass, for an entrant that did a whole match better than expected in every match
+    pub k_factor: f32,
+}
+
+impl Default for Elo {
+    fn default() -> Self {
+        Self { k_factor: 32.0 }
+    }
+}
+
+impl Elo {
+    /// The score a player with the first rating is expected to get against one with the second (1 = always wins, 0 = always loses)
+    pub fn expected(rating: f32, opponent: f32) -> f32 {
+        1.0 / (1.0 + 10.0_f32.powf((opponent - rating) / 400.0))
+    }
+
+    /// Rate the specified number of entrants from a list of (first, second, the score the first got) results
+    /// Every rating is moved towards what its results say it should be, all at the same time, over and over until they settle, so unlike updating after each match in turn the order of the results makes no difference
+    /// Each entrant also counts as having drawn once with someone rated INITIAL_RATING, so one that won (or lost) everything still gets a finite rating
+    pub fn rate(&self, entrants: usize, results: &[(usize, usize, f32)]) -> Vec<f32> {
+        let mut ratings = vec![INITIAL_RATING; entrants];
+
+        for _ in 0..MAX_RATING_PASSES {
+            let mut surprises: Vec<f32> = ratings
+                .iter()
+                .map(|rating| 0.5 - Self::expected(*rating, INITIAL_RATING))
+                .collect();
+            let mut matches = vec![1; entrants];
+
+            for &(first, second, score) in results {
+                let surprise = score - Self::expected(ratings[first], ratings[second]);
+
+                surprises[first] += surprise;
+                surprises[second] -= surprise;
+                matches[first] += 1;
+                matches[second] += 1;
+            }
+
+            let mut largest: f32 = 0.0;
+            for ((rating, surprise), matches) in ratings.iter_mut().zip(surprises).zip(matches) {
+                let change = self.k_factor * surprise / matches as f32;
+
+                *rating += change;
+                largest = largest.max(change.abs());
+            }
+
+            if largest < RATING_TOLERANCE {
+                break;
+            }
+        }
+
+        // Only the differences between ratings mean anything, so keep them averaging where everyone started
+        let shift = INITIAL_RATING - ratings.iter().sum::<f32>() / entrants.max(1) as f32;
+        ratings.iter().map(|rating| rating + shift).collect()
+    }
+}
+
+/// Where one entrant finished, and how they got there
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Standing {
+    pub name: String,
+    pub rating: f32,
+    pub wins: usize,
+    pub draws: usize,
+    pub losses: usize,
+    pub points_for: usize,
+    pub points_against: usize,
+}
+
+impl Standing {
+    fn new(name: String) -> Self {
+        Self {
+            name,
+            rating: INITIAL_RATING,
+            wins: 0,
+            draws: 0,
+            losses: 0,
+            points_for: 0,
+            points_against: 0,
+        }
+    }
+
+    fn record(&mut self, points_for: usize, points_against: usize) {
+        self.points_for += points_for;
+        self.points_against += points_against;
+
+        match points_for.cmp(&points_against) {
+            std::cmp::Ordering::Greater => self.wins += 1,
+            std::cmp::Ordering::Equal => self.draws += 1,
+            std::cmp::Ordering::Less => self.losses += 1,
+        }
+    }
+}
+
+/// How the tournament is played
+#[derive(Clone, Debug, PartialEq)]
+pub struct TournamentSettings {
+    /// How many matches each pair of entrants plays, taking turns on the left
+    pub matches: usize,
+    /// How many serves each match lasts
+    pub serves: usize,
+    /// The serves of match n are seeded with seed + n, so every pairing faces the same ones
+    pub seed: u64,
+    pub elo: Elo,
+}
+
+impl Default for TournamentSettings {
+    fn default() -> Self {
+        Self {
+            matches: 10,
+            serves: 10,
+            seed: 42,
+            elo: Elo::default(),
+        }
+    }
+}
+
+/// Play every pair of entrants against each other, and return the leaderboard, best rated first
+/// A match is scored by the share of its points each player won (a match where nobody scored is a draw), and the ratings come from all of the scores at once
+pub fn run_tournament(entrants: &[Entrant], settings: &TournamentSettings) -> Vec<Standing> {
+    let config = Config::default();
+    let mut standings: Vec<Standing> = entrants
+        .iter()
+        .map(|entrant| Standing::new(entrant.name()))
+        .collect();
+    let mut results = Vec::new();
+
+    for first in 0..entrants.len() {
+        for second in first + 1..entrants.len() {
+            for round in 0..settings.matches {
+                let seed = settings.seed.wrapping_add(round as u64);
+                let mut prng = ChaCha8Rng::seed_from_u64(seed);
+
+                // Swap sides every match, in case either side has an advantage
+                let (left, right) = if round % 2 == 0 {
+                    (first, second)
+                } else {
+                    (second, first)
+                };
+                // The players get their own randomness, so the serves don't depend on who's playing
+                let mut left_player =
+                    entrants[left].build(&config, &mut ChaCha8Rng::seed_from_u64(seed));
+                let mut right_player =
+                    entrants[right].build(&config, &mut ChaCha8Rng::seed_from_u64(seed));
+
+                let result = play_match(
+                    left_player.as_mut(),
+                    right_player.as_mut(),
+                    settings.serves,
+                    &mut prng,
+                );
+
+                standings[left].record(result.points_left, result.points_right);
+                standings[right].record(result.points_right, result.points_left);
+
+                let points = result.points_left + result.points_right;
+                let score = match points {
+                    0 => 0.5,
+                    points => result.points_left as f32 / points as f32,
+                };
+
+                results.push((left, right, score));
+            }
+        }
+    }
+
+    let ratings = settings.elo.rate(entrants.len(), &results);
+    for (standing, rating) in standings.iter_mut().zip(ratings) {
+        standing.rating = rating;
+    }
+
+    standings.sort_by(|a, b| {
+        b.rating
+            .partial_cmp(&a.rating)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    standings
+}
+
+/// Run a tournament with the options from the command line, logging the leaderboard and optionally saving it
+pub fn run(opts: &TournamentOpt) -> std::io::Result<()> {
+    let mut entrants = load_brains(&opts.brains)?;
+    if !opts.no_bots {
+        entrants.extend(BotKind::ALL.iter().map(|&bot| Entrant::Bot(bot)));
+    }
+
+    if entrants.len() < 2 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "A tournament needs at least two entrants",
+        ));
+    }
+
+    log::warn!(
+        "Playing a round-robin tournament between {} entrants, {} matches of {} serves per pairing",
+        entrants.len(),
+        opts.matches,
+        opts.serves
+    );
+
+    let standings = run_tournament(
+        &entrants,
+        &TournamentSettings {
+            matches: opts.matches,
+            serves: opts.serves,
+            seed: opts.seed,
+            elo: Elo {
+                k_factor: opts.k_factor,
+            },
+        },
+    );
+
+    log::warn!(
+        "{:>4}  {:<24} {:>7} {:>5} {:>5} {:>5} {:>7} {:>7}",
+        "Rank",
+        "Name",
+        "Rating",
+        "Won",
+        "Drawn",
+        "Lost",
+        "For",
+        "Against"
+    );
+    for (rank, standing) in standings.iter().enumerate() {
+        log::warn!(
+            "{:>4}  {:<24} {:>7.1} {:>5} {:>5} {:>5} {:>7} {:>7}",
+            rank + 1,
+            standing.name,
+            standing.rating,
+            standing.wins,
+            standing.draws,
+            standing.losses,
+            standing.points_for,
+            standing.points_against
+        );
+    }
+
+    match &opts.out {
+        Some(path) => {
+            log::warn!("Saving leaderboard to {:?}", path);
+            let writer = BufWriter::new(File::create(path)?);
+
+            Ok(serde_json::to_writer_pretty(writer, &standings)?)
+        }
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn elo_ratings() {
+        approx::assert_relative_eq!(Elo::expected(1500.0, 1500.0), 0.5);
+        approx::assert_relative_eq!(Elo::expected(1900.0, 1500.0), 10.0 / 11.0);
+
+        // Nobody has played anyone, or everyone drew, so nobody moves
+        assert_eq!(Elo::default().rate(2, &[]), vec![INITIAL_RATING; 2]);
+        let ratings = Elo::default().rate(2, &[(0, 1, 0.5), (1, 0, 0.5)]);
+        approx::assert_relative_eq!(ratings[0], INITIAL_RATING, epsilon = 1e-2);
+        approx::assert_relative_eq!(ratings[1], INITIAL_RATING, epsilon = 1e-2);
+
+        // Someone who beat everyone is rated highest, but not infinitely high
+        let results = [
+            (0, 1, 1.0),
+            (0, 2, 1.0),
+            (1, 2, 0.7),
+            (2, 1, 0.4),
+            (2, 0, 0.0),
+        ];
+        let ratings = Elo::default().rate(3, &results);
+        assert!(ratings[0] > ratings[1] && ratings[1] > ratings[2]);
+        assert!(ratings[0].is_finite());
+        approx::assert_relative_eq!(
+            ratings.iter().sum::<f32>(),
+            3.0 * INITIAL_RATING,
+            epsilon = 1e-2
+        );
+
+        // The order the matches were played in makes no difference
+        let mut reversed = results.to_vec();
+        reversed.reverse();
+        for (rating, reversed) in ratings.iter().zip(Elo::default().rate(3, &reversed)) {
+            approx::assert_relative_eq!(*rating, reversed, epsilon = 1e-2);
+        }
+    }
+
+    #[test]
+    fn round_robin() {
+        let mut prng = ChaCha8Rng::seed_from_u64(42);
+        let entrants = vec![
+            Entrant::Brain {
+                name: "random".to_string(),
+                brain: AiPlayer::random(&Config::default(), &mut prng)
+                    .brain()
+                    .clone(),
+            },
+            Entrant::Bot(BotKind::Easy),
+            Entrant::Bot(BotKind::Predictor),
+        ];
+        let settings = TournamentSettings {
+            matches: 2,
+            serves: 3,
+            ..TournamentSettings::default()
+        };
+
+        let standings = run_tournament(&entrants, &settings);
+
+        // Everyone plays two matches against each of the other two
+        assert_eq!(standings.len(), 3);
+        for standing in &standings {
+            assert_eq!(standing.wins + standing.draws + standing.losses, 4);
+        }
+
+        // Ratings only move between entrants, so the average stays where it started
+        let total: f32 = standings.iter().map(|standing| standing.rating).sum();
+        approx::assert_relative_eq!(total, 3.0 * INITIAL_RATING, epsilon = 1e-2);
+
+        // A bot that can't miss can't be rated below the others
+        assert_eq!(standings[0].name, "predictor");
+        assert_eq!(standings[0].points_against, 0);
+
+        // The matches are seeded, so the tournament always finishes the same way
+        assert_eq!(run_tournament(&entrants, &settings), standings);
+    }
+}
@@ -0,0 +1,1450 @@
+use rand::seq::SliceRandom;
+use rand::{Rng, RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+use std::collections::VecDeque;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use genetic_algorithm as ga;
+
+use ga::crossover::UniformCrossover;
+use ga::es::{CmaEs, EvolutionStrategy, Nes, Replacement};
+use ga::evaluation::{Evaluation, MemoizingEvaluator};
+use ga::individual::Individual;
+use ga::islands::{self, Archipelago, Migration};
+use ga::mutation::GaussianMutation;
+use ga::neat::{Genome, Neat, NeatSettings};
+use ga::optimizer::Optimizer;
+use ga::pareto::ParetoRanking;
+use ga::selection::{ParetoTournamentSelection, RouletteWheelSelection, SelectionMethod};
+use ga::speciation::{Speciation, SpeciationSettings};
+use ga::statistics::Statistics;
+use ga::GeneticAlgorithm;
+use neural_network::topology::Initializer;
+
+use super::brain::*;
+use super::checkpoint::Checkpoint;
+use super::decoder::Decoder;
+use super::fitness::*;
+use super::individual::*;
+use super::metrics::*;
+use super::normalizer::*;
+use super::player::*;
+use crate::cli::TrainOpt;
+use crate::settings::*;
+use crate::sim::*;
+
+/// Who the population plays against to work out how fit they are
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Arena {
+    /// Each AI plays on its own against a wall, and is rewarded for returning the ball
+    Wall,
+    /// The AIs play matches against each other (and against champions from earlier generations)
+    SelfPlay(SelfPlay),
+}
+
+/// How co-evolutionary self-play is organised
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SelfPlay {
+    /// Which members of the population play each other
+    pub pairing: Pairing,
+    /// How many past champions to keep around as opponents (0 turns the hall of fame off)
+    pub hall_of_fame_size: usize,
+    /// How many matches each AI plays against past champions every generation
+    pub hall_of_fame_matches: usize,
+}
+
+impl Default for SelfPlay {
+    fn default() -> Self {
+        Self {
+            pairing: Pairing::Sampled(5),
+            hall_of_fame_size: 10,
+            hall_of_fame_matches: 2,
+        }
+    }
+}
+
+/// Which members of the population play each other during self-play
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Pairing {
+    /// Everyone plays everyone else once
+    RoundRobin,
+    /// Everyone plays the specified number of randomly chosen opponents
+    Sampled(usize),
+}
+
+/// Which optimizer breeds each generation of a fixed-topology population
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Algorithm {
+    /// The GeneticAlgorithm, with roulette wheel (or Pareto tournament) selection, uniform crossover and Gaussian mutation
+    Genetic,
+    /// A (μ,λ) evolution strategy, breeding from the best quarter of the population
+    CommaEs,
+    /// A (μ+λ) evolution strategy, breeding from the best quarter of the population and the previous parents
+    PlusEs,
+    /// Covariance Matrix Adaptation Evolution Strategy
+    CmaEs,
+    /// OpenAI-style natural evolution strategy
+    Nes,
+}
+
+impl FromStr for Algorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ga" => Ok(Self::Genetic),
+            "comma-es" => Ok(Self::CommaEs),
+            "plus-es" => Ok(Self::PlusEs),
+            "cma-es" => Ok(Self::CmaEs),
+            "nes" => Ok(Self::Nes),
+            _ => Err(format!(
+                "Unknown algorithm {:?} (expected ga, comma-es, plus-es, cma-es or nes)",
+                s
+            )),
+        }
+    }
+}
+
+/// The evolution strategy the Config asked for
+/// Unlike the GeneticAlgorithm these learn as they go, so they're saved along with the rest of the TrainerState
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Strategy {
+    Es(EvolutionStrategy),
+    CmaEs(CmaEs),
+    Nes(Nes),
+}
+
+impl Strategy {
+    /// Create the evolution strategy selected in the provided Config, if it selected one
+    pub fn new(config: &Config) -> Option<Self> {
+        let parents = (config.population_size / 4).max(1);
+
+        match config.algorithm {
+            Algorithm::Genetic => None,
+            Algorithm::CommaEs => Some(Self::Es(EvolutionStrategy::new(
+                parents,
+                Replacement::Comma,
+                config.sigma,
+            ))),
+            Algorithm::PlusEs => Some(Self::Es(EvolutionStrategy::new(
+                parents,
+                Replacement::Plus,
+                config.sigma,
+            ))),
+            Algorithm::CmaEs => Some(Self::CmaEs(CmaEs::new(config.sigma))),
+            Algorithm::Nes => Some(Self::Nes(Nes::new(config.sigma, config.learning_rate))),
+        }
+    }
+}
+
+impl Strategy {
+    /// The strategy's current step size
+    pub fn sigma(&self) -> f32 {
+        match self {
+            Self::Es(strategy) => strategy.sigma(),
+            Self::CmaEs(strategy) => strategy.sigma(),
+            Self::Nes(strategy) => strategy.sigma(),
+        }
+    }
+}
+
+impl Optimizer<AiIndividual> for Strategy {
+    fn evolve(&mut self, prng: &mut dyn RngCore, population: &[AiIndividual]) -> Vec<AiIndividual> {
+        match self {
+            Self::Es(strategy) => strategy.evolve(prng, population),
+            Self::CmaEs(strategy) => strategy.evolve(prng, population),
+            Self::Nes(strategy) => strategy.evolve(prng, population),
+        }
+    }
+}
+
+/// Picks parents using whichever Ranking the Config asked for
+#[derive(Clone, Debug)]
+enum Selection {
+    Weighted(RouletteWheelSelection),
+    Pareto(ParetoTournamentSelection),
+}
+
+impl SelectionMethod for Selection {
+    /// Only Pareto tournaments need anything worked out up front
+    type Prepared = Option<ParetoRanking>;
+
+    fn prepare<I>(&self, population: &[I]) -> Self::Prepared
+    where
+        I: Individual,
+    {
+        match self {
+            Self::Weighted(_) => None,
+            Self::Pareto(selection) => Some(selection.prepare(population)),
+        }
+    }
+
+    fn select<'a, I>(
+        &self,
+        prng: &mut dyn RngCore,
+        population: &'a [I],
+        prepared: &Self::Prepared,
+    ) -> &'a I
+    where
+        I: Individual,
+    {
+        match (self, prepared) {
+            (Self::Weighted(selection), _) => selection.select(prng, population, &()),
+            (Self::Pareto(selection), Some(ranking)) => selection.select(prng, population, ranking),
+            (Self::Pareto(_), None) => {
+                panic!("Pareto selection needs the population ranking first")
+            }
+        }
+    }
+}
+
+/// Evolves a population of brains, by playing pong and then breeding from the best players
+pub struct Trainer {
+    config: Config,
+    arena: Arena,
+    ga: GeneticAlgorithm<Selection, UniformCrossover, GaussianMutation>,
+    population: Vec<Brain>,
+    neat: Option<(Neat, Vec<Genome>)>,
+    speciation: Option<Speciation>,
+    strategy: Option<Strategy>,
+    archipelago: Option<Archipelago<AiIndividual>>,
+    evaluator: Option<MemoizingEvaluator<f32>>,
+    hall_of_fame: VecDeque<Brain>,
+    champion: Option<Brain>,
+    fitnesses: Vec<f32>,
+    history: Vec<Statistics>,
+    generation: usize,
+}
+
+/// Everything about a Trainer that changes as it trains, which is what needs saving to carry on training later
+/// The settings it was trained with are saved alongside, so it can only be resumed with a Config and Arena that match them
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TrainerState {
+    /// The settings the state was trained with, which a resumed Trainer has to share
+    pub settings: TrainerSettings,
+    pub population: Vec<Brain>,
+    pub neat: Option<(Neat, Vec<Genome>)>,
+    pub speciation: Option<Speciation>,
+    pub strategy: Option<Strategy>,
+    /// The scores of the last generation against the wall, which any elites keep rather than playing again
+    pub evaluator: Option<MemoizingEvaluator<f32>>,
+    pub hall_of_fame: VecDeque<Brain>,
+    pub champion: Option<Brain>,
+    /// The fitness of each brain in the last generation to be evaluated
+    pub fitnesses: Vec<f32>,
+    /// The fitness statistics of every generation so far
+    pub history: Vec<Statistics>,
+    pub generation: usize,
+}
+
+impl TrainerState {
+    /// Whether this state could have come from a Trainer with the provided Config and Arena
+    pub fn fits(&self, config: &Config, arena: &Arena) -> bool {
+        self.settings
+            .differences(&TrainerSettings::new(config, arena))
+            .is_empty()
+            && self.population.len() == config.population_size
+            && self.neat.is_some() == (config.topology == Topology::Neat)
+            && self.strategy.is_some() == (config.algorithm != Algorithm::Genetic)
+            && self.evaluator.is_some() == remembers_scores(config, arena)
+    }
+}
+
+/// Everything about a Config and Arena that decides the shape of the brains, how they're scored and how they're bred, so a TrainerState can't be carried on with different ones
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TrainerSettings {
+    pub arena: Arena,
+    pub generation_length: usize,
+    pub fitness: FitnessWeights,
+    pub population_size: usize,
+    pub topology: Topology,
+    pub brain_neurons: usize,
+    pub outputs: usize,
+    pub decoder: Decoder,
+    pub initializer: Initializer,
+    pub mutation_chance: f32,
+    pub mutation_coeff: f32,
+    pub elitism: usize,
+    pub algorithm: Algorithm,
+    pub sigma: f32,
+    pub learning_rate: f32,
+    pub ranking: Ranking,
+    pub speciation: Option<SpeciationSettings>,
+    pub islands: usize,
+    pub migration: Migration,
+}
+
+impl TrainerSettings {
+    /// Take the settings from a Config and Arena
+    pub fn new(config: &Config, arena: &Arena) -> Self {
+        Self {
+            arena: arena.clone(),
+            generation_length: config.generation_length,
+            fitness: config.fitness.clone(),
+            population_size: config.population_size,
+            topology: config.topology,
+            brain_neurons: config.brain_neurons,
+            outputs: config.outputs,
+            decoder: config.decoder,
+            initializer: config.initializer,
+            mutation_chance: config.mutation_chance,
+            mutation_coeff: config.mutation_coeff,
+            elitism: config.elitism,
+            algorithm: config.algorithm,
+            sigma: config.sigma,
+            learning_rate: config.learning_rate,
+            ranking: config.ranking,
+            speciation: config.speciation.clone(),
+            islands: config.islands,
+            migration: config.migration.clone(),
+        }
+    }
+
+    /// The names of every setting that differs between these settings and the other ones
+    pub fn differences(&self, other: &TrainerSettings) -> Vec<&'static str> {
+        let mut differences = Vec::new();
+
+        if self.arena != other.arena {
+            differences.push("arena");
+        }
+        if self.generation_length != other.generation_length {
+            differences.push("generation length");
+        }
+        if self.fitness != other.fitness {
+            differences.push("fitness weights");
+        }
+        if self.population_size != other.population_size {
+            differences.push("population size");
+        }
+        if self.topology != other.topology {
+            differences.push("topology");
+        }
+        if self.brain_neurons != other.brain_neurons {
+            differences.push("brain neurons");
+        }
+        if self.outputs != other.outputs || self.decoder != other.decoder {
+            differences.push("decoder");
+        }
+        if self.initializer != other.initializer {
+            differences.push("initializer");
+        }
+        if self.mutation_chance != other.mutation_chance
+            || self.mutation_coeff != other.mutation_coeff
+        {
+            differences.push("mutation");
+        }
+        if self.elitism != other.elitism {
+            differences.push("elitism");
+        }
+        if self.algorithm != other.algorithm {
+            differences.push("algorithm");
+        }
+        if self.sigma != other.sigma {
+            differences.push("sigma");
+        }
+        if self.learning_rate != other.learning_rate {
+            differences.push("learning rate");
+        }
+        if self.ranking != other.ranking {
+            differences.push("ranking");
+        }
+        if self.speciation != other.speciation {
+            differences.push("speciation");
+        }
+        if self.islands != other.islands || self.migration != other.migration {
+            differences.push("islands");
+        }
+
+        differences
+    }
+}
+
+impl Trainer {
+    /// Create a new Trainer with a population of random brains
+    /// With a NEAT topology, every brain starts off minimal and the population evolves with NEAT instead of the GeneticAlgorithm
+    pub fn new(config: Config, arena: Arena, prng: &mut dyn RngCore) -> Self {
+        // Self-play needs at least two players to make a match
+        assert!(config.population_size > 1);
+
+        let (population, neat) = match config.topology {
+            Topology::Fixed | Topology::Recurrent(_) => {
+                let population = (0..config.population_size)
+                    .map(|_| Brain::random(&config, prng))
+                    .collect();
+
+                (population, None)
+            }
+            Topology::Neat => {
+                let mut neat = Neat::new(
+                    config.eye_photoreceptors,
+                    config.outputs,
+                    NeatSettings::default(),
+                );
+                let genomes = neat.initial_population(prng, config.population_size);
+
+                let normalizer = Normalizer::new(&config);
+                let population = genomes
+                    .iter()
+                    .map(|genome| Brain::from_genome(&config, normalizer.clone(), genome))
+                    .collect();
+
+                (population, Some((neat, genomes)))
+            }
+        };
+
+        let speciation = config.speciation.clone().map(Speciation::new);
+        let strategy = Strategy::new(&config);
+
+        let selection = match config.ranking {
+            Ranking::Weighted => Selection::Weighted(RouletteWheelSelection::new()),
+            Ranking::Pareto => Selection::Pareto(ParetoTournamentSelection::new()),
+        };
+
+        let ga = GeneticAlgorithm::new(
+            selection,
+            UniformCrossover::new(),
+            GaussianMutation::new(config.mutation_chance, config.mutation_coeff),
+        )
+        .with_elitism(config.elitism);
+
+        let evaluator = if remembers_scores(&config, &arena) {
+            Some(MemoizingEvaluator::new())
+        } else {
+            None
+        };
+
+        // Every island starts off breeding the same way, but only ever from its own slice of the population
+        let archipelago = if config.islands > 1 {
+            let islands = (0..config.islands)
+                .map(|_| Box::new(ga.clone()) as Box<dyn Optimizer<AiIndividual>>)
+                .collect();
+
+            Some(Archipelago::new(islands, config.migration.clone()))
+        } else {
+            None
+        };
+
+        Self {
+            config,
+            arena,
+            ga,
+            population,
+            neat,
+            speciation,
+            strategy,
+            archipelago,
+            evaluator,
+            hall_of_fame: VecDeque::new(),
+            champion: None,
+            fitnesses: Vec::new(),
+            history: Vec::new(),
+            generation: 0,
+        }
+    }
+
+    /// Create a Trainer that carries on from a saved TrainerState, using the same Config and Arena it was originally created with
+    pub fn resume(config: Config, arena: Arena, state: TrainerState) -> Self {
+        assert!(state.fits(&config, &arena));
+
+        // The settings get rebuilt exactly as before, so the throwaway RNG here never gets used for anything that's kept
+        let mut trainer = Self::new(config, arena, &mut ChaCha8Rng::seed_from_u64(0));
+
+        if let Some(archipelago) = &mut trainer.archipelago {
+            archipelago.resume(state.generation);
+        }
+
+        Self {
+            population: state.population,
+            neat: state.neat,
+            speciation: state.speciation,
+            strategy: state.strategy,
+            evaluator: state.evaluator,
+            hall_of_fame: state.hall_of_fame,
+            champion: state.champion,
+            fitnesses: state.fitnesses,
+            history: state.history,
+            generation: state.generation,
+            ..trainer
+        }
+    }
+
+    /// Take a copy of everything needed to carry on training later
+    pub fn state(&self) -> TrainerState {
+        TrainerState {
+            settings: TrainerSettings::new(&self.config, &self.arena),
+            population: self.population.clone(),
+            neat: self.neat.clone(),
+            speciation: self.speciation.clone(),
+            strategy: self.strategy.clone(),
+            evaluator: self.evaluator.clone(),
+            hall_of_fame: self.hall_of_fame.clone(),
+            champion: self.champion.clone(),
+            fitnesses: self.fitnesses.clone(),
+            history: self.history.clone(),
+            generation: self.generation,
+        }
+    }
+
+    /// How many generations have been evolved so far
+    pub fn generation(&self) -> usize {
+        self.generation
+    }
+
+    /// The brains that are about to be evaluated
+    pub fn population(&self) -> &[Brain] {
+        &self.population
+    }
+
+    /// How many species the population was split into last generation (an unspeciated population counts as 1)
+    pub fn species_count(&self) -> usize {
+        match (&self.neat, &self.speciation) {
+            (Some((neat, _)), _) => neat.species().len(),
+            (None, Some(speciation)) => speciation.species().len(),
+            (None, None) => 1,
+        }
+    }
+
+    /// The fitness statistics of every generation so far
+    pub fn history(&self) -> &[Statistics] {
+        &self.history
+    }
+
+    /// The fitness of every brain in the last generation, in the order they were evaluated
+    pub fn fitnesses(&self) -> &[f32] {
+        &self.fitnesses
+    }
+
+    /// The past champions the population plays against during self-play, oldest first
+    pub fn hall_of_fame(&self) -> &VecDeque<Brain> {
+        &self.hall_of_fame
+    }
+
+    /// How strongly the next generation gets mutated
+    /// This is the chance of each weight being nudged for the GeneticAlgorithm and NEAT, or the current step size of an evolution strategy
+    pub fn mutation_rate(&self) -> f32 {
+        match (&self.neat, &self.speciation, &self.strategy) {
+            (Some((neat, _)), _, _) => neat.settings().mutation_chance,
+            // Speciation breeds with the GeneticAlgorithm, even when an evolution strategy was asked for
+            (None, None, Some(strategy)) => strategy.sigma(),
+            _ => self.config.mutation_chance,
+        }
+    }
+
+    /// The fittest brain from the last generation, if one has been evolved yet
+    pub fn champion(&self) -> Option<&Brain> {
+        self.champion.as_ref()
+    }
+
+    /// Evaluate the current population, then breed the next generation from it
+    pub fn evolve(&mut self, prng: &mut dyn RngCore) -> Statistics {
+        // Build fresh players for this generation, so they can learn what their inputs look like as they go
+        let mut players: Vec<AiPlayer> = self
+            .population
+            .iter()
+            .map(|brain| {
+                let mut player = AiPlayer::from_brain(&self.config, brain.clone());
+                player.set_learning(true);
+                player
+            })
+            .collect();
+
+        let individuals: Vec<AiIndividual> = match &mut self.evaluator {
+            // Elites playing against the wall keep the score they got last generation, so only the new brains play
+            Some(evaluator) => {
+                let (serves, weights) = (self.config.generation_length, &self.config.fitness);
+                let mut individuals: Vec<AiIndividual> = self
+                    .population
+                    .iter()
+                    .map(|brain| AiIndividual::create(brain.to_chromosome()))
+                    .collect();
+
+                evaluator.evaluate(&mut individuals, |idx, _| {
+                    let performance = play_wall(&mut players[idx], serves, prng).left;
+
+                    Evaluation::with_objectives(
+                        weights.fitness(&performance),
+                        weights.weighted_objectives(&performance),
+                    )
+                });
+
+                individuals
+            }
+            None => {
+                let performances = match &self.arena {
+                    Arena::Wall => self.evaluate_against_wall(&mut players, prng),
+                    Arena::SelfPlay(self_play) => {
+                        self.evaluate_against_each_other(self_play, &mut players, prng)
+                    }
+                };
+
+                players
+                    .iter()
+                    .zip(&performances)
+                    .map(|(player, performance)| {
+                        AiIndividual::new(player.brain(), performance, &self.config.fitness)
+                    })
+                    .collect()
+            }
+        };
+
+        let statistics = Statistics::new(&individuals);
+        let fitnesses: Vec<f32> = individuals.iter().map(Individual::fitness).collect();
+
+        // The champion (the one with the best weighted fitness, even when ranking by Pareto fronts) keeps whatever its normalizer learnt, and passes it on to the next generation
+        let (best, _) = individuals.iter().enumerate().fold(
+            (0, f32::NEG_INFINITY),
+            |(best, best_fitness), (idx, individual)| {
+                let fitness = individual.fitness();
+                if fitness > best_fitness {
+                    (idx, fitness)
+                } else {
+                    (best, best_fitness)
+                }
+            },
+        );
+        let champion = players[best].brain().clone();
+
+        if let Arena::SelfPlay(self_play) = &self.arena {
+            if self_play.hall_of_fame_size > 0 {
+                self.hall_of_fame.push_back(champion.clone());
+
+                // Make room for the new champion by retiring the oldest one
+                while self.hall_of_fame.len() > self_play.hall_of_fame_size {
+                    self.hall_of_fame.pop_front();
+                }
+            }
+        }
+
+        let normalizer = champion.normalizer();
+        let config = &self.config;
+
+        self.population = match &mut self.neat {
+            // NEAT does its own selection within each species, so it only needs the weighted fitness
+            Some((neat, genomes)) => {
+                *genomes = neat.evolve(prng, genomes, &fitnesses);
+
+                genomes
+                    .iter()
+                    .map(|genome| Brain::from_genome(config, normalizer.clone(), genome))
+                    .collect()
+            }
+            None => match (
+                &mut self.speciation,
+                &mut self.strategy,
+                &mut self.archipelago,
+            ) {
+                (Some(speciation), _, _) => {
+                    self.ga.evolve_speciated(prng, &individuals, speciation)
+                }
+                (None, Some(strategy), _) => strategy.evolve(prng, &individuals),
+                // The population is kept in island order, so each island is always the same slice of it
+                (None, None, Some(archipelago)) => {
+                    let populations = islands::split(individuals, archipelago.len());
+                    archipelago
+                        .evolve(prng, &populations)
+                        .into_iter()
+                        .flatten()
+                        .collect()
+                }
+                (None, None, None) => self.ga.evolve(prng, &individuals),
+            }
+            .iter()
+            .map(|child| Brain::from_chromosome(config, normalizer.clone(), &child.chromosome))
+            .collect(),
+        };
+
+        self.champion = Some(champion);
+        self.fitnesses = fitnesses;
+        self.history.push(statistics.clone());
+        self.generation += 1;
+
+        statistics
+    }
+
+    /// Each player plays on its own against a wall
+    fn evaluate_against_wall(
+        &self,
+        players: &mut [AiPlayer],
+        prng: &mut dyn RngCore,
+    ) -> Vec<Performance> {
+        players
+            .iter_mut()
+            .map(|player| play_wall(player, self.config.generation_length, prng).left)
+            .collect()
+    }
+
+    /// Each player plays matches against the rest of the population and the hall of fame
+    fn evaluate_against_each_other(
+        &self,
+        self_play: &SelfPlay,
+        players: &mut [AiPlayer],
+        prng: &mut dyn RngCore,
+    ) -> Vec<Performance> {
+        let mut performances = vec![Performance::default(); players.len()];
+
+        for (a, b) in pairings(self_play.pairing, players.len(), prng) {
+            let (player_a, player_b) = pair_mut(players, a, b);
+            let (performance_a, performance_b) =
+                play_both_sides(player_a, player_b, self.config.generation_length, prng);
+
+            performances[a].merge(&performance_a);
+            performances[b].merge(&performance_b);
+        }
+
+        // Past champions don't learn anything from these matches, and their results don't count for them
+        if !self.hall_of_fame.is_empty() {
+            for (idx, player) in players.iter_mut().enumerate() {
+                for _ in 0..self_play.hall_of_fame_matches {
+                    let brain = &self.hall_of_fame[prng.gen_range(0..self.hall_of_fame.len())];
+                    let mut champion = AiPlayer::from_brain(&self.config, brain.clone());
+
+                    let (performance, _) =
+                        play_both_sides(player, &mut champion, self.config.generation_length, prng);
+
+                    performances[idx].merge(&performance);
+                }
+            }
+        }
+
+        performances
+    }
+}
+
+/// Whether a Trainer remembers the last generation's scores, so elites don't have to play again
+/// Only scores against the wall can be kept, as a self-play score depends on who else is in the population
+fn remembers_scores(config: &Config, arena: &Arena) -> bool {
+    config.elitism > 0 && *arena == Arena::Wall
+}
+
+/// Work out who plays who in a population of the specified size
+fn pairings(pairing: Pairing, population: usize, prng: &mut dyn RngCore) -> Vec<(usize, usize)> {
+    match pairing {
+        Pairing::RoundRobin => (0..population)
+            .flat_map(|a| (a + 1..population).map(move |b| (a, b)))
+            .collect(),
+        Pairing::Sampled(opponents) => (0..population)
+            .flat_map(|a| {
+                let others: Vec<usize> = (0..population).filter(|b| *b != a).collect();
+
+                others
+                    .choose_multiple(prng, opponents)
+                    .map(|b| (a, *b))
+                    .collect::<Vec<_>>()
+            })
+            .collect(),
+    }
+}
+
+/// Borrow two different players from the population at the same time
+fn pair_mut(players: &mut [AiPlayer], a: usize, b: usize) -> (&mut AiPlayer, &mut AiPlayer) {
+    assert_ne!(a, b);
+
+    if a < b {
+        let (left, right) = players.split_at_mut(b);
+        (&mut left[a], &mut right[0])
+    } else {
+        let (left, right) = players.split_at_mut(a);
+        (&mut right[0], &mut left[b])
+    }
+}
+
+/// Play a match with each player on the left, so neither gets an advantage from the side they play on
+/// Returns how each player got on in total
+fn play_both_sides(
+    a: &mut AiPlayer,
+    b: &mut AiPlayer,
+    serves: usize,
+    prng: &mut dyn RngCore,
+) -> (Performance, Performance) {
+    let mut first = play_match(a, b, serves, prng);
+    let second = play_match(b, a, serves, prng);
+
+    first.left.merge(&second.right);
+    first.right.merge(&second.left);
+
+    (first.left, first.right)
+}
+
+/// Make sure the options from the command line make sense together, before anything gets trained
+fn check_options(opts: &TrainOpt) -> std::io::Result<()> {
+    let invalid = |message: &str| {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            message.to_string(),
+        ))
+    };
+
+    // Every island needs at least one AI player to breed from
+    if opts.islands > opts.population {
+        return invalid(&format!(
+            "Can't split a population of {} into {} islands",
+            opts.population, opts.islands
+        ));
+    }
+    if opts.islands > 1 && opts.migration_interval == 0 {
+        return invalid("The migration interval has to be at least one generation");
+    }
+
+    // NEAT breeds (and speciates) in its own way, then speciation takes priority over the other optimizers, which take priority over islands, so whichever lost out would quietly be ignored
+    let options = [
+        ("--neat", opts.neat),
+        ("--recurrent", opts.recurrent.is_some()),
+        ("--species-threshold", opts.species_threshold.is_some()),
+        ("--algorithm", opts.algorithm != Algorithm::Genetic),
+        ("--islands", opts.islands > 1),
+        ("--pareto", opts.pareto),
+        ("--elitism", opts.elitism > 0),
+    ];
+    let conflicts = [
+        ("--neat", "--recurrent"),
+        ("--neat", "--species-threshold"),
+        ("--neat", "--algorithm"),
+        ("--neat", "--islands"),
+        ("--neat", "--pareto"),
+        ("--species-threshold", "--algorithm"),
+        ("--species-threshold", "--islands"),
+        ("--algorithm", "--islands"),
+        ("--algorithm", "--pareto"),
+        ("--neat", "--elitism"),
+        ("--species-threshold", "--elitism"),
+        ("--algorithm", "--elitism"),
+    ];
+    let used = |name: &str| {
+        options
+            .iter()
+            .any(|(option, used)| *option == name && *used)
+    };
+
+    for (first, second) in conflicts.iter() {
+        if used(first) && used(second) {
+            return invalid(&format!("{} can't be used with {}", first, second));
+        }
+    }
+
+    Ok(())
+}
+
+/// Train AI players headlessly with the options from the command line, then save the champion's brain
+pub fn run(opts: &TrainOpt) -> std::io::Result<()> {
+    check_options(opts)?;
+
+    let config = Config {
+        population_size: opts.population,
+        topology: match (opts.neat, opts.recurrent) {
+            (true, _) => Topology::Neat,
+            (false, Some(cell)) => Topology::Recurrent(cell),
+            (false, None) => Topology::Fixed,
+        },
+        ranking: if opts.pareto {
+            Ranking::Pareto
+        } else {
+            Ranking::Weighted
+        },
+        speciation: opts.species_threshold.map(|threshold| SpeciationSettings {
+            threshold,
+            stagnation_limit: opts.stagnation,
+        }),
+        elitism: opts.elitism,
+        algorithm: opts.algorithm,
+        sigma: opts.sigma,
+        learning_rate: opts.learning_rate,
+        islands: opts.islands,
+        migration: Migration {
+            interval: opts.migration_interval,
+            migrants: opts.migrants,
+            topology: opts.migration,
+        },
+        initializer: opts.initializer,
+        ..Config::default().with_decoder(crate::cli::get_decoder())
+    };
+
+    let arena = if opts.self_play {
+        Arena::SelfPlay(SelfPlay {
+            pairing: match opts.opponents {
+                0 => Pairing::RoundRobin,
+                opponents => Pairing::Sampled(opponents),
+            },
+            ..SelfPlay::default()
+        })
+    } else {
+        Arena::Wall
+    };
+
+    let (mut trainer, mut prng) = match &opts.resume {
+        Some(path) => {
+            log::warn!("Resuming training from {:?}", path);
+            let checkpoint = Checkpoint::load(path)?;
+
+            // A checkpoint from a run with different options can't be carried on
+            let differences = checkpoint
+                .state
+                .settings
+                .differences(&TrainerSettings::new(&config, &arena));
+            if !differences.is_empty() || !checkpoint.state.fits(&config, &arena) {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!(
+                        "The checkpoint was saved by a run with different options ({})",
+                        differences.join(", ")
+                    ),
+                ));
+            }
+
+            (
+                Trainer::resume(config, arena, checkpoint.state),
+                checkpoint.prng,
+            )
+        }
+        None => {
+            let mut prng = ChaCha8Rng::seed_from_u64(opts.seed);
+            (Trainer::new(config, arena, &mut prng), prng)
+        }
+    };
+
+    // Finish the current generation and save a checkpoint when Ctrl-C is pressed, rather than losing the whole run
+    let interrupted = Arc::new(AtomicBool::new(false));
+    {
+        let interrupted = interrupted.clone();
+        ctrlc::set_handler(move || interrupted.store(true, Ordering::SeqCst))
+            .map_err(std::io::Error::other)?;
+    }
+
+    let mut metrics = match &opts.metrics_out {
+        Some(path) => {
+            log::warn!("Writing training metrics to {:?}", path);
+            let resumed = opts.resume.as_ref().map(|_| trainer.generation());
+            Some(MetricsWriter::create(path, resumed)?)
+        }
+        None => None,
+    };
+
+    let mut since_checkpoint = 0;
+    let started = Instant::now();
+
+    while trainer.generation() < opts.generations {
+        // The population gets replaced by its children, so hold on to it for the individuals' metrics
+        let brains = metrics.as_ref().map(|_| trainer.population().to_vec());
+
+        let generation_started = Instant::now();
+        let statistics = trainer.evolve(&mut prng);
+        let wall_time = generation_started.elapsed();
+
+        log::warn!(
+            "Generation {}: min {:.3}, avg {:.3}, max {:.3}, species {}",
+            trainer.generation(),
+            statistics.min_fitness(),
+            statistics.avg_fitness(),
+            statistics.max_fitness(),
+            trainer.species_count()
+        );
+
+        if let (Some(metrics), Some(brains)) = (&mut metrics, brains) {
+            metrics.write(
+                &GenerationMetrics::new(&trainer, &statistics, wall_time, started.elapsed()),
+                &IndividualMetrics::generation(trainer.generation(), &brains, trainer.fitnesses()),
+            )?;
+        }
+
+        since_checkpoint += 1;
+        if since_checkpoint == opts.checkpoint_interval {
+            since_checkpoint = 0;
+            Checkpoint::new(&trainer, &prng).save(&opts.checkpoint)?;
+        }
+
+        if interrupted.load(Ordering::SeqCst) {
+            log::warn!(
+                "Interrupted, saving checkpoint to {:?} (carry on with --resume)",
+                &opts.checkpoint
+            );
+            return Checkpoint::new(&trainer, &prng).save(&opts.checkpoint);
+        }
+    }
+
+    match trainer.champion() {
+        Some(champion) => {
+            log::warn!("Saving champion to {:?}", &opts.out);
+            champion.save(&opts.out)
+        }
+        None => {
+            log::warn!("No generations were trained, so there's no champion to save");
+            Ok(())
+        }
+    }
+}
+
+/// A population small enough to train in a test
+#[cfg(test)]
+pub(crate) fn small_config() -> Config {
+    Config {
+        brain_neurons: 4,
+        generation_length: 1,
+        population_size: 4,
+        ..Config::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ga::islands::MigrationTopology;
+    use neural_network::recurrent::Cell;
+    use structopt::StructOpt;
+
+    #[test]
+    fn invalid_options() {
+        let opts = |args: &[&str]| {
+            TrainOpt::from_iter(std::iter::once("train").chain(args.iter().cloned()))
+        };
+
+        assert!(check_options(&opts(&[])).is_ok());
+        assert!(check_options(&opts(&["-p", "4", "--islands", "4"])).is_ok());
+
+        // More islands than AI players would leave some of them empty
+        let error = check_options(&opts(&["-p", "4", "--islands", "5"])).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+
+        // Islands that never migrate aren't allowed, but a single island never migrates anyway
+        assert!(check_options(&opts(&["--islands", "2", "--migration-interval", "0"])).is_err());
+        assert!(check_options(&opts(&["--migration-interval", "0"])).is_ok());
+
+        // Options that would otherwise be silently ignored in favour of another one
+        for args in &[
+            &["--islands", "4", "--algorithm", "cma-es"][..],
+            &["--neat", "--algorithm", "nes"],
+            &["--neat", "--pareto"],
+            &["--neat", "--islands", "2"],
+            &["--neat", "--recurrent", "gru"],
+            &["--species-threshold", "0.5", "--islands", "2"],
+            &["--species-threshold", "0.5", "--algorithm", "plus-es"],
+            &["--pareto", "--algorithm", "comma-es"],
+            &["--elitism", "2", "--neat"],
+            &["--elitism", "2", "--species-threshold", "0.5"],
+            &["--elitism", "2", "--algorithm", "cma-es"],
+        ] {
+            assert!(check_options(&opts(args)).is_err(), "{:?}", args);
+        }
+        assert!(check_options(&opts(&["--neat", "--self-play"])).is_ok());
+        assert!(check_options(&opts(&["--species-threshold", "0.5", "--pareto"])).is_ok());
+        assert!(check_options(&opts(&["--islands", "2", "--pareto"])).is_ok());
+        assert!(check_options(&opts(&["--elitism", "2", "--islands", "2"])).is_ok());
+    }
+
+    #[test]
+    fn self_play_pairings() {
+        let mut prng = ChaCha8Rng::from_seed(Default::default());
+
+        // Round-robin should pair everyone with everyone else exactly once
+        let round_robin = pairings(Pairing::RoundRobin, 4, &mut prng);
+        assert_eq!(
+            round_robin,
+            vec![(0, 1), (0, 2), (0, 3), (1, 2), (1, 3), (2, 3)]
+        );
+
+        // Sampling should give everyone the right number of opponents, and never themselves
+        let sampled = pairings(Pairing::Sampled(2), 5, &mut prng);
+        assert_eq!(sampled.len(), 5 * 2);
+        assert!(sampled.iter().all(|(a, b)| a != b));
+        for a in 0..5 {
+            assert_eq!(sampled.iter().filter(|(first, _)| *first == a).count(), 2);
+        }
+    }
+
+    #[test]
+    fn wall_training() {
+        let mut prng = ChaCha8Rng::from_seed(Default::default());
+        let mut trainer = Trainer::new(small_config(), Arena::Wall, &mut prng);
+
+        let statistics = trainer.evolve(&mut prng);
+
+        assert_eq!(trainer.generation(), 1);
+        assert_eq!(trainer.population().len(), 4);
+        assert!(trainer.champion().is_some());
+        assert!(trainer.hall_of_fame().is_empty());
+        assert!(statistics.min_fitness() >= MIN_FITNESS);
+    }
+
+    #[test]
+    fn elitist_training() {
+        let mut prng = ChaCha8Rng::from_seed(Default::default());
+        let config = Config {
+            elitism: 1,
+            ..small_config()
+        };
+        let mut trainer = Trainer::new(config, Arena::Wall, &mut prng);
+
+        // The champion goes through unchanged and keeps its score, so the best fitness can never drop
+        let mut best = 0.0;
+        for _ in 0..6 {
+            let statistics = trainer.evolve(&mut prng);
+            assert!(statistics.max_fitness() >= best);
+            best = statistics.max_fitness();
+        }
+
+        // ...and it never had to play again to get it
+        let evaluator = trainer.evaluator.as_ref().unwrap();
+        assert!(evaluator.hits() >= 5);
+        assert_eq!(evaluator.hits() + evaluator.misses(), 6 * 4);
+
+        // Self-play scores depend on the rest of the population, so they're never kept
+        let self_play = Trainer::new(
+            Config {
+                elitism: 1,
+                ..small_config()
+            },
+            Arena::SelfPlay(SelfPlay::default()),
+            &mut prng,
+        );
+        assert!(self_play.evaluator.is_none());
+    }
+
+    #[test]
+    fn pareto_training() {
+        let mut prng = ChaCha8Rng::from_seed(Default::default());
+
+        let config = Config {
+            ranking: Ranking::Pareto,
+            ..small_config()
+        };
+        let mut trainer = Trainer::new(config, Arena::Wall, &mut prng);
+
+        for _ in 0..2 {
+            trainer.evolve(&mut prng);
+        }
+
+        assert_eq!(trainer.generation(), 2);
+        assert_eq!(trainer.population().len(), 4);
+    }
+
+    #[test]
+    fn speciated_training() {
+        let mut prng = ChaCha8Rng::from_seed(Default::default());
+
+        let config = Config {
+            speciation: Some(SpeciationSettings::default()),
+            ..small_config()
+        };
+        let mut trainer = Trainer::new(config, Arena::Wall, &mut prng);
+
+        for _ in 0..2 {
+            trainer.evolve(&mut prng);
+        }
+
+        // Random brains are all very different, so there should be more than one species
+        assert_eq!(trainer.population().len(), 4);
+        assert!(trainer.species_count() > 1);
+    }
+
+    #[test]
+    fn island_training() {
+        let mut prng = ChaCha8Rng::from_seed(Default::default());
+
+        let config = Config {
+            population_size: 6,
+            islands: 2,
+            migration: Migration {
+                interval: 1,
+                migrants: 1,
+                topology: MigrationTopology::Ring,
+            },
+            ..small_config()
+        };
+        let mut trainer = Trainer::new(config, Arena::Wall, &mut prng);
+
+        for _ in 0..2 {
+            trainer.evolve(&mut prng);
+        }
+
+        assert_eq!(trainer.generation(), 2);
+        assert_eq!(trainer.population().len(), 6);
+    }
+
+    #[test]
+    fn evolution_strategy_training() {
+        for algorithm in [
+            Algorithm::CommaEs,
+            Algorithm::PlusEs,
+            Algorithm::CmaEs,
+            Algorithm::Nes,
+        ]
+        .iter()
+        {
+            let mut prng = ChaCha8Rng::from_seed(Default::default());
+
+            let config = Config {
+                algorithm: *algorithm,
+                ..small_config()
+            };
+            let mut trainer = Trainer::new(config, Arena::Wall, &mut prng);
+
+            for _ in 0..3 {
+                trainer.evolve(&mut prng);
+            }
+
+            assert_eq!(trainer.generation(), 3);
+            assert_eq!(trainer.population().len(), 4);
+            assert!(trainer.state().strategy.is_some());
+        }
+    }
+
+    #[test]
+    fn neat_training() {
+        let mut prng = ChaCha8Rng::from_seed(Default::default());
+
+        let config = Config {
+            topology: Topology::Neat,
+            ..small_config()
+        };
+        let mut trainer = Trainer::new(config, Arena::Wall, &mut prng);
+
+        // Every brain starts off with the eye wired straight to the output
+        assert!(trainer.population().iter().all(|brain| brain
+            .network()
+            .graph()
+            .map(|graph| graph.nodes().len())
+            == Some(5 + 1)));
+
+        for _ in 0..2 {
+            trainer.evolve(&mut prng);
+        }
+
+        assert_eq!(trainer.generation(), 2);
+        assert_eq!(trainer.population().len(), 4);
+        assert!(trainer.champion().unwrap().network().graph().is_some());
+    }
+
+    #[test]
+    fn recurrent_training() {
+        let mut prng = ChaCha8Rng::from_seed(Default::default());
+
+        for cell in [Cell::Elman, Cell::Gru] {
+            let config = Config {
+                topology: Topology::Recurrent(cell),
+                ..small_config()
+            };
+            let mut trainer = Trainer::new(config, Arena::Wall, &mut prng);
+
+            for _ in 0..2 {
+                trainer.evolve(&mut prng);
+            }
+
+            // The children get rebuilt from the chromosomes the GeneticAlgorithm bred, so they should still be recurrent
+            assert_eq!(trainer.generation(), 2);
+            assert_eq!(trainer.population().len(), 4);
+            assert!(trainer
+                .population()
+                .iter()
+                .all(|brain| brain.network().recurrent().is_some()));
+        }
+    }
+
+    #[test]
+    fn mismatched_resume() {
+        let mut prng = ChaCha8Rng::from_seed(Default::default());
+        let config = small_config();
+        let mut trainer = Trainer::new(config.clone(), Arena::Wall, &mut prng);
+        trainer.evolve(&mut prng);
+
+        let state = trainer.state();
+        assert!(state.fits(&config, &Arena::Wall));
+
+        // Every option that changes the shape of the brains, how they're scored or how they're bred should stop the state from being carried on
+        let mismatched = [
+            config.clone().with_decoder(Decoder::Discrete),
+            Config {
+                topology: Topology::Recurrent(Cell::Gru),
+                ..config.clone()
+            },
+            Config {
+                speciation: Some(SpeciationSettings::default()),
+                ..config.clone()
+            },
+            Config {
+                islands: 2,
+                ..config.clone()
+            },
+            Config {
+                ranking: Ranking::Pareto,
+                ..config.clone()
+            },
+            Config {
+                initializer: Initializer::xavier(),
+                ..config.clone()
+            },
+            Config {
+                generation_length: 2,
+                ..config.clone()
+            },
+            Config {
+                fitness: FitnessWeights {
+                    returns: 2.0,
+                    ..FitnessWeights::default()
+                },
+                ..config.clone()
+            },
+            Config {
+                mutation_chance: 0.5,
+                ..config.clone()
+            },
+            Config {
+                sigma: 0.5,
+                ..config.clone()
+            },
+            Config {
+                elitism: 1,
+                ..config.clone()
+            },
+            Config {
+                learning_rate: 0.5,
+                ..config.clone()
+            },
+        ];
+
+        for other in mismatched.iter() {
+            assert!(!state.fits(other, &Arena::Wall));
+            assert_eq!(
+                state
+                    .settings
+                    .differences(&TrainerSettings::new(other, &Arena::Wall))
+                    .len(),
+                1
+            );
+        }
+
+        // Training against each other (--self-play) instead of the wall, or with a different number of opponents, counts too
+        let self_play = Arena::SelfPlay(SelfPlay::default());
+        assert!(!state.fits(&config, &self_play));
+        assert_eq!(
+            state
+                .settings
+                .differences(&TrainerSettings::new(&config, &self_play)),
+            vec!["arena"]
+        );
+
+        let state = Trainer::new(config.clone(), self_play.clone(), &mut prng).state();
+        let sampled = Arena::SelfPlay(SelfPlay {
+            pairing: Pairing::Sampled(2),
+            ..SelfPlay::default()
+        });
+        assert!(state.fits(&config, &self_play));
+        assert!(!state.fits(&config, &sampled));
+    }
+
+    #[test]
+    fn resume_is_bit_exact() {
+        let configs = [
+            // Speciated self-play, with a hall of fame
+            (
+                Config {
+                    speciation: Some(SpeciationSettings::default()),
+                    ..small_config()
+                },
+                Arena::SelfPlay(SelfPlay {
+                    pairing: Pairing::Sampled(2),
+                    ..SelfPlay::default()
+                }),
+            ),
+            // NEAT
+            (
+                Config {
+                    topology: Topology::Neat,
+                    ..small_config()
+                },
+                Arena::Wall,
+            ),
+            // CMA-ES, which has to carry on with the same mean, covariance and step size
+            (
+                Config {
+                    algorithm: Algorithm::CmaEs,
+                    ..small_config()
+                },
+                Arena::Wall,
+            ),
+            // Elites that keep their scores from before the checkpoint
+            (
+                Config {
+                    elitism: 1,
+                    ..small_config()
+                },
+                Arena::Wall,
+            ),
+            // Islands that migrate straight after resuming
+            (
+                Config {
+                    islands: 2,
+                    migration: Migration {
+                        interval: 3,
+                        ..Migration::default()
+                    },
+                    ..small_config()
+                },
+                Arena::Wall,
+            ),
+        ];
+
+        for (config, arena) in configs.iter() {
+            // Train for 4 generations in one go
+            let mut prng = ChaCha8Rng::from_seed(Default::default());
+            let mut trainer = Trainer::new(config.clone(), arena.clone(), &mut prng);
+            for _ in 0..4 {
+                trainer.evolve(&mut prng);
+            }
+
+            // Train for 2 generations, save a checkpoint, and train for another 2 from the checkpoint
+            let mut interrupted_prng = ChaCha8Rng::from_seed(Default::default());
+            let mut interrupted =
+                Trainer::new(config.clone(), arena.clone(), &mut interrupted_prng);
+            for _ in 0..2 {
+                interrupted.evolve(&mut interrupted_prng);
+            }
+
+            let json = serde_json::to_string(&Checkpoint::new(&interrupted, &interrupted_prng))
+                .expect("Failed to save checkpoint");
+            let checkpoint: Checkpoint =
+                serde_json::from_str(&json).expect("Failed to load checkpoint");
+
+            let mut resumed_prng = checkpoint.prng;
+            let mut resumed = Trainer::resume(config.clone(), arena.clone(), checkpoint.state);
+            for _ in 0..2 {
+                resumed.evolve(&mut resumed_prng);
+            }
+
+            // Everything should have ended up exactly the same
+            assert_eq!(resumed.generation(), 4);
+            assert_eq!(resumed.history(), trainer.history());
+            assert_eq!(
+                serde_json::to_string(resumed.population()).unwrap(),
+                serde_json::to_string(trainer.population()).unwrap()
+            );
+            assert_eq!(resumed_prng.next_u64(), prng.next_u64());
+        }
+    }
+
+    #[test]
+    fn hall_of_fame_is_capped() {
+        let mut prng = ChaCha8Rng::from_seed(Default::default());
+
+        let arena = Arena::SelfPlay(SelfPlay {
+            pairing: Pairing::RoundRobin,
+            hall_of_fame_size: 2,
+            hall_of_fame_matches: 1,
+        });
+        let mut trainer = Trainer::new(small_config(), arena, &mut prng);
+
+        for _ in 0..3 {
+            trainer.evolve(&mut prng);
+        }
+
+        // Only the 2 most recent champions should still be in the hall of fame
+        assert_eq!(trainer.generation(), 3);
+        assert_eq!(trainer.hall_of_fame().len(), 2);
+        assert_eq!(
+            trainer.hall_of_fame().back().map(Brain::to_chromosome),
+            trainer.champion().map(Brain::to_chromosome)
+        );
+    }
+}
@@ -0,0 +1,351 @@
+use ggez::graphics::Rect;
+use rand::{Rng, RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use std::collections::VecDeque;
+use std::str::FromStr;
+
+use crate::core::Ball;
+use crate::player::{Policy, Snapshot};
+use crate::settings::*;
+
+/// The scripted (i.e. non-neural) opponents that come built in
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BotKind {
+    Tracker,
+    Predictor,
+    Easy,
+    Medium,
+    Hard,
+}
+
+impl BotKind {
+    /// Every kind of bot, from the two perfect ones down to the human-like ones
+    pub const ALL: [BotKind; 5] = [
+        BotKind::Tracker,
+        BotKind::Predictor,
+        BotKind::Easy,
+        BotKind::Medium,
+        BotKind::Hard,
+    ];
+
+    /// Create a new bot of this kind (the human-like bots seed their own randomness from the provided PRNG)
+    pub fn build(&self, prng: &mut dyn RngCore) -> Box<dyn Policy> {
+        match self {
+            Self::Tracker => Box::new(TrackerBot::new(PADDLE_SPEED)),
+            Self::Predictor => Box::new(PredictorBot::new(PADDLE_SPEED)),
+            Self::Easy => Box::new(HumanLikeBot::new(Difficulty::EASY, prng)),
+            Self::Medium => Box::new(HumanLikeBot::new(Difficulty::MEDIUM, prng)),
+            Self::Hard => Box::new(HumanLikeBot::new(Difficulty::HARD, prng)),
+        }
+    }
+}
+
+impl std::fmt::Display for BotKind {
+    /// The same name the bot is picked by on the command line
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = match self {
+            Self::Tracker => "tracker",
+            Self::Predictor => "predictor",
+            Self::Easy => "easy",
+            Self::Medium => "medium",
+            Self::Hard => "hard",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for BotKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "tracker" => Ok(Self::Tracker),
+            "predictor" => Ok(Self::Predictor),
+            "easy" => Ok(Self::Easy),
+            "medium" => Ok(Self::Medium),
+            "hard" => Ok(Self::Hard),
+            _ => Err(format!(
+                "Unknown bot '{}' (expected tracker, predictor, easy, medium or hard)",
+                s
+            )),
+        }
+    }
+}
+
+/// Move the paddle towards the target Y position, but no faster than max_speed
+fn steer_towards(paddle: Rect, target_y: f32, max_speed: f32) -> f32 {
+    (target_y - paddle.center().y).clamp(-max_speed, max_speed)
+}
+
+/// Work out the Y position at which the ball will reach the paddle, allowing for bounces off the top and bottom walls
+/// Returns None if the ball is heading away from the paddle
+pub fn predict_intercept(paddle: Rect, ball: &Ball) -> Option<f32> {
+    // Which side of the screen is the paddle on, and is the ball actually heading towards it?
+    let paddle_face = if paddle.center().x < SCREEN_WIDTH / 2.0 {
+        if ball.vel.x >= 0.0 {
+            return None;
+        }
+        paddle.right() + ball.rect.w / 2.0
+    } else {
+        if ball.vel.x <= 0.0 {
+            return None;
+        }
+        paddle.left() - ball.rect.w / 2.0
+    };
+
+    // How many frames until the ball gets there, and where would it be if there were no walls?
+    let frames = (paddle_face - ball.rect.center().x) / ball.vel.x;
+    let unbounded_y = ball.rect.center().y + ball.vel.y * frames.max(0.0);
+
+    // The ball's centre bounces between these two limits (see GameState::ball_hit_wall)
+    let min_y = ball.rect.h / 2.0;
+    let max_y = SCREEN_HEIGHT - BALL_RADIUS - ball.rect.h / 2.0;
+
+    // Fold the unbounded path back into the screen, like reflecting it in a pair of mirrors
+    let span = max_y - min_y;
+    let folded = (unbounded_y - min_y).rem_euclid(2.0 * span);
+
+    Some(if folded <= span {
+        min_y + folded
+    } else {
+        max_y - (folded - span)
+    })
+}
+
+/// Always moves the paddle to wherever the ball is right now
+#[derive(Clone, Debug)]
+pub struct TrackerBot {
+    max_speed: f32,
+}
+
+impl TrackerBot {
+    pub fn new(max_speed: f32) -> Self {
+        Self { max_speed }
+    }
+}
+
+impl Policy for TrackerBot {
+    fn act(&mut self, snapshot: &Snapshot) -> f32 {
+        steer_towards(
+            snapshot.paddle,
+            snapshot.ball.rect.center().y,
+            self.max_speed,
+        )
+    }
+
+    fn name(&self) -> &'static str {
+        "Tracker"
+    }
+}
+
+/// Moves the paddle to wherever the ball is going to arrive, and back to the middle while it's heading away
+#[derive(Clone, Debug)]
+pub struct PredictorBot {
+    max_speed: f32,
+}
+
+impl PredictorBot {
+    pub fn new(max_speed: f32) -> Self {
+        Self { max_speed }
+    }
+}
+
+impl Policy for PredictorBot {
+    fn act(&mut self, snapshot: &Snapshot) -> f32 {
+        let target =
+            predict_intercept(snapshot.paddle, &snapshot.ball).unwrap_or(SCREEN_HEIGHT / 2.0);
+
+        steer_towards(snapshot.paddle, target, self.max_speed)
+    }
+
+    fn name(&self) -> &'static str {
+        "Predictor"
+    }
+}
+
+/// How good a HumanLikeBot is
+#[derive(Clone, Debug, PartialEq)]
+pub struct Difficulty {
+    /// How many frames behind the action the bot is
+    pub reaction_delay: usize,
+    /// How far (at most) the bot misjudges where the ball will arrive, re-rolled every time the ball changes direction
+    pub noise: f32,
+    /// How fast the bot can move its paddle
+    pub max_speed: f32,
+}
+
+impl Difficulty {
+    pub const EASY: Difficulty = Difficulty {
+        reaction_delay: 30,
+        noise: PADDLE_HEIGHT,
+        max_speed: PADDLE_SPEED / 2.0,
+    };
+
+    pub const MEDIUM: Difficulty = Difficulty {
+        reaction_delay: 15,
+        noise: PADDLE_HEIGHT / 2.0,
+        max_speed: PADDLE_SPEED * 0.75,
+    };
+
+    pub const HARD: Difficulty = Difficulty {
+        reaction_delay: 5,
+        noise: PADDLE_HEIGHT / 4.0,
+        max_speed: PADDLE_SPEED,
+    };
+}
+
+/// A predictor that reacts late, misjudges things and can't move as fast, so it can be beaten
+#[derive(Clone, Debug)]
+pub struct HumanLikeBot {
+    difficulty: Difficulty,
+    prng: ChaCha8Rng,
+    seen: VecDeque<Ball>,
+    aim_offset: f32,
+    heading_right: bool,
+}
+
+impl HumanLikeBot {
+    pub fn new(difficulty: Difficulty, prng: &mut dyn RngCore) -> Self {
+        Self {
+            difficulty,
+            prng: ChaCha8Rng::from_rng(prng).expect("Failed to seed HumanLikeBot PRNG!"),
+            seen: VecDeque::new(),
+            aim_offset: 0.0,
+            heading_right: false,
+        }
+    }
+}
+
+impl Policy for HumanLikeBot {
+    fn act(&mut self, snapshot: &Snapshot) -> f32 {
+        // Remember what the ball is doing now, but only act on what it was doing reaction_delay frames ago
+        self.seen.push_back(snapshot.ball.clone());
+        if self.seen.len() <= self.difficulty.reaction_delay {
+            return 0.0;
+        }
+        let ball = self.seen.pop_front().expect("No ball has been seen yet!");
+
+        // Every time the ball changes direction, misjudge the next shot by a different amount
+        if (ball.vel.x > 0.0) != self.heading_right {
+            self.heading_right = ball.vel.x > 0.0;
+            self.aim_offset = if self.difficulty.noise > 0.0 {
+                self.prng
+                    .gen_range(-self.difficulty.noise..=self.difficulty.noise)
+            } else {
+                0.0
+            };
+        }
+
+        let target = match predict_intercept(snapshot.paddle, &ball) {
+            Some(intercept) => intercept + self.aim_offset,
+            None => SCREEN_HEIGHT / 2.0,
+        };
+
+        steer_towards(snapshot.paddle, target, self.difficulty.max_speed)
+    }
+
+    fn name(&self) -> &'static str {
+        "CPU"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ggez::mint::Vector2;
+
+    fn ball_at(x: f32, y: f32, vel_x: f32, vel_y: f32) -> Ball {
+        Ball {
+            rect: Rect::new(
+                x - BALL_RADIUS / 2.0,
+                y - BALL_RADIUS / 2.0,
+                BALL_RADIUS,
+                BALL_RADIUS,
+            ),
+            vel: Vector2::<f32> { x: vel_x, y: vel_y },
+            spd: vel_x.hypot(vel_y),
+        }
+    }
+
+    fn left_paddle_at(y: f32) -> Rect {
+        Rect::new(
+            X_OFFSET,
+            y - PADDLE_HEIGHT / 2.0,
+            PADDLE_WIDTH,
+            PADDLE_HEIGHT,
+        )
+    }
+
+    #[test]
+    fn intercept_prediction() {
+        let paddle = left_paddle_at(300.0);
+        let paddle_face = paddle.right() + BALL_RADIUS / 2.0;
+
+        // A ball heading away from the paddle can't be intercepted
+        assert_eq!(
+            predict_intercept(paddle, &ball_at(400.0, 300.0, 2.0, 2.0)),
+            None
+        );
+
+        // A ball heading straight at the paddle arrives at the same height
+        let intercept = predict_intercept(paddle, &ball_at(400.0, 200.0, -2.0, 0.0)).unwrap();
+        approx::assert_relative_eq!(intercept, 200.0);
+
+        // A ball that doesn't reach a wall just follows its slope
+        let intercept = predict_intercept(paddle, &ball_at(400.0, 200.0, -4.0, 1.0)).unwrap();
+        approx::assert_relative_eq!(intercept, 200.0 + (400.0 - paddle_face) / 4.0);
+
+        // A ball that bounces off the top wall gets reflected back down
+        let top = BALL_RADIUS / 2.0;
+        let intercept = predict_intercept(paddle, &ball_at(400.0, 100.0, -2.0, -2.0)).unwrap();
+        approx::assert_relative_eq!(intercept, top + ((400.0 - paddle_face) - (100.0 - top)));
+    }
+
+    #[test]
+    fn tracker_and_predictor_moves() {
+        // The ball is level with the paddle, but it's going to end up below it
+        let paddle = left_paddle_at(300.0);
+        let ball = ball_at(300.0, 300.0, -2.0, 0.2);
+        let snapshot = Snapshot::new(&paddle, &ball);
+
+        // The tracker stays where it is, because it only cares where the ball is now...
+        approx::assert_relative_eq!(TrackerBot::new(PADDLE_SPEED).act(&snapshot), 0.0);
+
+        // ...but the predictor starts moving down to meet it
+        approx::assert_relative_eq!(PredictorBot::new(PADDLE_SPEED).act(&snapshot), PADDLE_SPEED);
+
+        // While the ball is heading away, the predictor drifts back towards the middle
+        let paddle = left_paddle_at(500.0);
+        let ball = ball_at(300.0, 100.0, 2.0, 0.2);
+        approx::assert_relative_eq!(
+            PredictorBot::new(2.0).act(&Snapshot::new(&paddle, &ball)),
+            -2.0
+        );
+    }
+
+    #[test]
+    fn human_like_bot_reaction_delay() {
+        let mut prng = ChaCha8Rng::from_seed(Default::default());
+
+        let difficulty = Difficulty {
+            reaction_delay: 3,
+            noise: 0.0,
+            max_speed: PADDLE_SPEED,
+        };
+        let mut bot = HumanLikeBot::new(difficulty, &mut prng);
+
+        let paddle = left_paddle_at(300.0);
+        let ball = ball_at(300.0, 300.0, -2.0, 1.0);
+        let snapshot = Snapshot::new(&paddle, &ball);
+
+        // The bot shouldn't react to anything until it has caught up with the action...
+        for _ in 0..3 {
+            approx::assert_relative_eq!(bot.act(&snapshot), 0.0);
+        }
+
+        // ...and then it should play like a predictor that's slightly behind
+        approx::assert_relative_eq!(bot.act(&snapshot), PADDLE_SPEED);
+    }
+}
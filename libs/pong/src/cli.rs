@@ -1,12 +1,22 @@
 use structopt::StructOpt;
 
+use std::path::PathBuf;
+
+use genetic_algorithm::islands::MigrationTopology;
+use neural_network::recurrent::Cell;
+use neural_network::topology::Initializer;
+
+use crate::ai::decoder::Decoder;
+use crate::ai::imitation::OptimizerKind;
+use crate::ai::trainer::Algorithm;
+use crate::bots::BotKind;
 use crate::player::*;
 use crate::settings::*;
 
 #[derive(StructOpt, Debug)]
 pub struct Opt {
     // Game mode
-    /// 1 = Human vs Human, 2 = Human vs AI, 3 = AI vs Human, 4 = AI vs AI, 5 = Human only, 6 = AI only, 7 = train AI
+    /// 1 = Human vs Human, 2 = Human vs AI, 3 = AI vs Human, 4 = AI vs AI, 5 = Human only, 6 = AI only, 7 = train AI, 8 = Human vs CPU, 9 = AI vs CPU
     #[structopt(short, long, default_value = "1")]
     pub mode: u8,
 
@@ -14,6 +24,354 @@ pub struct Opt {
     /// Target frames per second (0 = unlimited)
     #[structopt(short, long, default_value = "0")]
     pub fps: u8,
+
+    // Saved AI brain
+    /// Load AI players from a brain saved during training, instead of creating random ones
+    #[structopt(short, long, parse(from_os_str))]
+    pub brain: Option<PathBuf>,
+
+    // Saved DQN agent
+    /// Load AI players from an agent saved by the dqn command, instead of a brain
+    #[structopt(long, parse(from_os_str))]
+    pub dqn: Option<PathBuf>,
+
+    // AI action decoder
+    /// How new AI players turn their brain's outputs into a move: sign, discrete, proportional or target-y
+    #[structopt(short, long, default_value = "sign")]
+    pub decoder: Decoder,
+
+    // CPU difficulty
+    /// Which scripted bot to play against in the vs CPU modes: tracker, predictor, easy, medium or hard
+    #[structopt(long, default_value = "medium")]
+    pub cpu: BotKind,
+
+    // Debug overlay
+    /// Start with the AI players' networks drawn over the game (this can also be toggled with the Tab key)
+    #[structopt(long)]
+    pub overlay: bool,
+
+    // Recording
+    /// Record what every human player sees and does each tick to this dataset file (added to the end if it already exists), for the imitate command to learn from
+    #[structopt(long, parse(from_os_str))]
+    pub record: Option<PathBuf>,
+
+    // Headless commands
+    #[structopt(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(StructOpt, Debug)]
+pub enum Command {
+    /// Train AI players headlessly (without opening a window), then save the champion's brain
+    Train(TrainOpt),
+    /// Train an AI player headlessly to copy recorded games and/or a scripted bot, then save its brain
+    Imitate(ImitateOpt),
+    /// Train an AI player headlessly with deep Q-learning, then save it
+    Dqn(DqnOpt),
+    /// Play a saved brain against the same seeded serves against the wall and every scripted bot, and report how it did
+    Evaluate(EvaluateOpt),
+    /// Play every brain saved in a directory and the scripted bots against each other, and rank them with Elo ratings
+    Tournament(TournamentOpt),
+}
+
+#[derive(StructOpt, Debug)]
+pub struct TrainOpt {
+    // Training length
+    /// How many generations to train for
+    #[structopt(short, long, default_value = "100")]
+    pub generations: usize,
+
+    // Population size
+    /// How many AI players to train at once
+    #[structopt(short, long, default_value = "50")]
+    pub population: usize,
+
+    // Self-play
+    /// Train the AI players by playing against each other (and past champions), instead of against the wall
+    #[structopt(long)]
+    pub self_play: bool,
+
+    // NEAT
+    /// Grow each brain's network topology with NEAT, instead of only evolving the weights of a fixed network
+    #[structopt(long)]
+    pub neat: bool,
+
+    // Recurrent
+    /// Give each brain's hidden neurons a memory of the last tick, using elman or gru cells (forgotten every serve)
+    #[structopt(long)]
+    pub recurrent: Option<Cell>,
+
+    // Weight initialization
+    /// How random brains pick their starting weights: uniform, uniform:<range>, xavier or he, optionally followed by +zero-bias (fixed and recurrent topologies only)
+    #[structopt(long, default_value = "uniform")]
+    pub initializer: Initializer,
+
+    // Speciation
+    /// Split the population into species of brains whose weights differ by less than this on average (fixed topology only)
+    #[structopt(long)]
+    pub species_threshold: Option<f32>,
+
+    // Stagnation
+    /// How many generations a species can go without improving before it gets pruned
+    #[structopt(long, default_value = "15")]
+    pub stagnation: usize,
+
+    // Islands
+    /// How many separate sub-populations to evolve, which only swap their best AI players when they migrate (fixed topology without speciation only)
+    #[structopt(long, default_value = "1")]
+    pub islands: usize,
+
+    // Migration topology
+    /// Where each island sends its migrants: ring (to the next island) or random
+    #[structopt(long, default_value = "ring")]
+    pub migration: MigrationTopology,
+
+    // Migration interval
+    /// How many generations pass between migrations
+    #[structopt(long, default_value = "10")]
+    pub migration_interval: usize,
+
+    // Migrants
+    /// How many of each island's best AI players migrate each time
+    #[structopt(long, default_value = "2")]
+    pub migrants: usize,
+
+    // Elitism
+    /// How many of the best AI players go through to the next generation unchanged (genetic algorithm only). Against the wall they also keep their score instead of playing again
+    #[structopt(long, default_value = "0")]
+    pub elitism: usize,
+
+    // Optimizer
+    /// How to breed each generation of a fixed topology: ga, comma-es, plus-es, cma-es or nes (not with --species-threshold)
+    #[structopt(long, default_value = "ga")]
+    pub algorithm: Algorithm,
+
+    // Evolution strategy step size
+    /// How far the evolution strategies search around the best weights to start with
+    #[structopt(long, default_value = "0.1")]
+    pub sigma: f32,
+
+    // NES learning rate
+    /// How far NES moves its weights each generation
+    #[structopt(long, default_value = "0.05")]
+    pub learning_rate: f32,
+
+    // Pareto ranking
+    /// Pick parents with NSGA-II style Pareto tournaments over every fitness objective, instead of a roulette wheel over their weighted sum
+    #[structopt(long)]
+    pub pareto: bool,
+
+    // Self-play opponents
+    /// How many randomly chosen opponents each AI player meets every generation during self-play (0 = everyone)
+    #[structopt(long, default_value = "5")]
+    pub opponents: usize,
+
+    // Training seed
+    /// Seed for the random number generator, so training runs can be repeated
+    #[structopt(long, default_value = "42")]
+    pub seed: u64,
+
+    // Champion brain
+    /// Where to save the brain of the last generation's champion
+    #[structopt(short, long, parse(from_os_str), default_value = "champion.json")]
+    pub out: PathBuf,
+
+    // Checkpoint file
+    /// Where to save checkpoints of the whole training run, so it can be carried on later with --resume
+    #[structopt(long, parse(from_os_str), default_value = "checkpoint.json")]
+    pub checkpoint: PathBuf,
+
+    // Checkpoint interval
+    /// Save a checkpoint every this many generations (0 = only when interrupted with Ctrl-C)
+    #[structopt(long, default_value = "10")]
+    pub checkpoint_interval: usize,
+
+    // Training metrics
+    /// Write each generation's fitness, species count, mutation rate, wall time and champion to this .csv or .ndjson file while training, with every individual's fitness going to a "-individuals" file next to it
+    #[structopt(long, parse(from_os_str))]
+    pub metrics_out: Option<PathBuf>,
+
+    // Resume training
+    /// Carry on training from a saved checkpoint, using the same options as the original run (-g still counts the generations trained before the checkpoint)
+    #[structopt(long, parse(from_os_str))]
+    pub resume: Option<PathBuf>,
+}
+
+#[derive(StructOpt, Debug)]
+pub struct ImitateOpt {
+    // Recorded games
+    /// A dataset recorded with --record to copy (can be given more than once)
+    #[structopt(long, parse(from_os_str))]
+    pub dataset: Vec<PathBuf>,
+
+    // Scripted bot
+    /// A scripted bot to copy as well: tracker, predictor, easy, medium or hard
+    #[structopt(long)]
+    pub bot: Option<BotKind>,
+
+    // Bot serves
+    /// How many serves to watch the scripted bot play against the wall for
+    #[structopt(long, default_value = "50")]
+    pub serves: usize,
+
+    // Training length
+    /// How many times to go through the whole dataset
+    #[structopt(short, long, default_value = "50")]
+    pub epochs: usize,
+
+    // Mini-batch size
+    /// How many moves to learn from before each update of the brain's weights
+    #[structopt(long, default_value = "32")]
+    pub batch_size: usize,
+
+    // Optimizer
+    /// How to update the brain's weights: sgd or adam
+    #[structopt(long, default_value = "adam")]
+    pub optimizer: OptimizerKind,
+
+    // Learning rate
+    /// How far the optimizer moves the brain's weights each update
+    #[structopt(long, default_value = "0.001")]
+    pub learning_rate: f32,
+
+    // Training seed
+    /// Seed for the random number generator, so training runs can be repeated
+    #[structopt(long, default_value = "42")]
+    pub seed: u64,
+
+    // Imitation brain
+    /// Where to save the trained brain (which needs a --decoder other than sign, e.g. discrete)
+    #[structopt(short, long, parse(from_os_str), default_value = "imitation.json")]
+    pub out: PathBuf,
+}
+
+#[derive(StructOpt, Debug)]
+pub struct DqnOpt {
+    // Training length
+    /// How many steps to train for, counted across every game
+    #[structopt(short, long, default_value = "200000")]
+    pub steps: usize,
+
+    // Parallel games
+    /// How many games to play at once
+    #[structopt(long, default_value = "8")]
+    pub envs: usize,
+
+    // Opponent
+    /// A scripted bot to train against instead of the wall: tracker, predictor, easy, medium or hard
+    #[structopt(long)]
+    pub opponent: Option<BotKind>,
+
+    // Hidden layer
+    /// How many neurons the hidden layer of the Q-network gets
+    #[structopt(long, default_value = "32")]
+    pub hidden: usize,
+
+    // Discount
+    /// How much future rewards are worth compared to the ones straight away
+    #[structopt(long, default_value = "0.99")]
+    pub gamma: f32,
+
+    // Learning rate
+    /// How far Adam moves the Q-network's weights each update
+    #[structopt(long, default_value = "0.001")]
+    pub learning_rate: f32,
+
+    // Mini-batch size
+    /// How many remembered steps to learn from at once
+    #[structopt(long, default_value = "32")]
+    pub batch_size: usize,
+
+    // Replay buffer
+    /// How many steps to remember
+    #[structopt(long, default_value = "50000")]
+    pub buffer: usize,
+
+    // Target network
+    /// How many steps to go between updates of the target network
+    #[structopt(long, default_value = "1000")]
+    pub target_update: usize,
+
+    // Exploration
+    /// How many steps it takes to go from always exploring to hardly ever exploring
+    #[structopt(long, default_value = "50000")]
+    pub epsilon_decay: usize,
+
+    // Progress reports
+    /// How many steps to go between progress reports
+    #[structopt(long, default_value = "10000")]
+    pub report_every: usize,
+
+    // Training seed
+    /// Seed for the random number generator, so training runs can be repeated
+    #[structopt(long, default_value = "42")]
+    pub seed: u64,
+
+    // DQN agent
+    /// Where to save the trained agent
+    #[structopt(short, long, parse(from_os_str), default_value = "dqn.json")]
+    pub out: PathBuf,
+}
+
+#[derive(StructOpt, Debug)]
+pub struct EvaluateOpt {
+    // Saved AI brain
+    /// The brain to evaluate, as saved by the train or imitate commands
+    #[structopt(short, long, parse(from_os_str))]
+    pub brain: PathBuf,
+
+    // Serves
+    /// How many serves to play against each opponent
+    #[structopt(short, long, default_value = "100")]
+    pub serves: usize,
+
+    // Evaluation seed
+    /// Seed for the serves and the bots, so every brain evaluated with the same seed faces exactly the same games
+    #[structopt(long, default_value = "42")]
+    pub seed: u64,
+
+    // Results
+    /// Also save the results to this JSON file
+    #[structopt(short, long, parse(from_os_str))]
+    pub out: Option<PathBuf>,
+}
+
+#[derive(StructOpt, Debug)]
+pub struct TournamentOpt {
+    // Saved AI brains
+    /// A directory of brains to enter, as saved by the train or imitate commands (any other JSON files are skipped)
+    #[structopt(short, long, parse(from_os_str))]
+    pub brains: PathBuf,
+
+    // Bots
+    /// Leave the scripted bots out, so only the saved brains play
+    #[structopt(long)]
+    pub no_bots: bool,
+
+    // Matches
+    /// How many matches each pair of entrants plays, taking turns on the left
+    #[structopt(short, long, default_value = "10")]
+    pub matches: usize,
+
+    // Serves
+    /// How many serves each match lasts
+    #[structopt(short, long, default_value = "10")]
+    pub serves: usize,
+
+    // Elo K factor
+    /// How far the ratings move on each pass while they're worked out from the results (too big and they can overshoot)
+    #[structopt(long, default_value = "32")]
+    pub k_factor: f32,
+
+    // Tournament seed
+    /// Seed for the serves and the bots, so a tournament between the same entrants always finishes the same way
+    #[structopt(long, default_value = "42")]
+    pub seed: u64,
+
+    // Leaderboard
+    /// Also save the leaderboard to this JSON file
+    #[structopt(short, long, parse(from_os_str))]
+    pub out: Option<PathBuf>,
 }
 
 #[derive(Debug, Clone)]
@@ -40,6 +398,8 @@ pub fn get_game_mode() -> Result<Mode, ModeError> {
         5 => Ok(PLAYER_VS_SELF),
         6 => Ok(AI_VS_SELF),
         7 => Ok(TRAIN_AI),
+        8 => Ok(PLAYER_VS_BOT),
+        9 => Ok(AI_VS_BOT),
         _ => Err(ModeError),
     }
 }
@@ -53,3 +413,52 @@ pub fn get_target_fps() -> u8 {
         _ => args.fps,
     }
 }
+
+pub fn get_brain_path() -> Option<PathBuf> {
+    // Read command line args, if any
+    let args = Opt::from_args();
+
+    args.brain
+}
+
+pub fn get_dqn_path() -> Option<PathBuf> {
+    // Read command line args, if any
+    let args = Opt::from_args();
+
+    args.dqn
+}
+
+pub fn get_decoder() -> Decoder {
+    // Read command line args, if any
+    let args = Opt::from_args();
+
+    args.decoder
+}
+
+pub fn get_cpu() -> BotKind {
+    // Read command line args, if any
+    let args = Opt::from_args();
+
+    args.cpu
+}
+
+pub fn get_overlay() -> bool {
+    // Read command line args, if any
+    let args = Opt::from_args();
+
+    args.overlay
+}
+
+pub fn get_record_path() -> Option<PathBuf> {
+    // Read command line args, if any
+    let args = Opt::from_args();
+
+    args.record
+}
+
+pub fn get_command() -> Option<Command> {
+    // Read command line args, if any
+    let args = Opt::from_args();
+
+    args.command
+}
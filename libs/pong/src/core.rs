@@ -7,12 +7,21 @@ use ggez::{Context, GameResult};
 use glam::*;
 use rand::RngCore;
 
+use std::fs::File;
+use std::io::LineWriter;
+
+use crate::ai::dqn::DqnPlayer;
+use crate::ai::imitation::Recorder;
 use crate::ai::player::*;
+use crate::ai::trainer::Arena;
 use crate::cli;
+use crate::dashboard::Dashboard;
+use crate::overlay;
 use crate::player::*;
 use crate::settings::*;
+use crate::sim::{Event, Table};
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Wall {
     Top,
     Bottom,
@@ -20,7 +29,7 @@ pub enum Wall {
     Right,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Paddle {
     Left,
     Right,
@@ -28,68 +37,69 @@ pub enum Paddle {
 
 #[derive(Debug)]
 pub struct GameState {
-    paddle_left: Rect,
-    paddle_right: Rect,
-    ball: Ball,
+    table: Table,
     score: Score,
     pause_for: u64,
     mode: Mode,
-    player_one: Box<dyn Move>,
+    /// Nobody plays on the table while AI training shows the dashboard instead
+    player_one: Option<Box<dyn Move>>,
     player_two: Option<Box<dyn Move>>,
+    recorder: Option<Recorder<LineWriter<File>>>,
+    /// Whether to draw what the AI players are thinking over the game
+    overlay: bool,
+    /// Plots how the population evolving in the background is doing, for AI training
+    dashboard: Option<Dashboard>,
 }
 
 impl GameState {
     /// Create a new GameState struct for a game with the specified number of players
     pub fn new(mode: Mode, prng: &mut dyn RngCore) -> GameResult<GameState> {
         Ok(GameState {
-            paddle_left: Rect::new(
-                X_OFFSET,
-                SCREEN_HEIGHT / 2.0 - PADDLE_HEIGHT / 2.0,
-                PADDLE_WIDTH,
-                PADDLE_HEIGHT,
+            // 1 player games and AI training play against a full height wall instead of a right paddle
+            table: Table::new(
+                matches!(&mode, Mode::OnePlayer(_) | Mode::TrainAI(_)),
+                &mut rand::thread_rng(),
             ),
-
-            paddle_right: match &mode {
-                Mode::OnePlayer(_) | Mode::TrainAI(_) => Rect::new(
-                    SCREEN_WIDTH - X_OFFSET - PADDLE_WIDTH,
-                    0.0,
-                    PADDLE_WIDTH,
-                    SCREEN_HEIGHT,
-                ),
-                Mode::TwoPlayer(_, _) => Rect::new(
-                    SCREEN_WIDTH - X_OFFSET - PADDLE_WIDTH,
-                    SCREEN_HEIGHT / 2.0 - PADDLE_HEIGHT / 2.0,
-                    PADDLE_WIDTH,
-                    PADDLE_HEIGHT,
-                ),
-            },
-            ball: Ball::random(),
             score: Score::default(),
             pause_for: 0,
             player_one: match &mode {
                 Mode::OnePlayer(p1) => match p1 {
                     Player::Human => {
                         log::warn!("P1: Human");
-                        Box::new(HumanPlayer::new(keyboard::KeyCode::W, keyboard::KeyCode::S))
+                        Some(Box::new(HumanPlayer::new(
+                            keyboard::KeyCode::W,
+                            keyboard::KeyCode::S,
+                        )))
                     }
                     Player::Computer => {
                         log::warn!("P1: AI");
-                        Box::new(AiPlayer::random(&Config::default(), prng))
+                        Some(new_ai_player(prng))
+                    }
+                    Player::Bot => {
+                        log::warn!("P1: CPU");
+                        Some(Box::new(cli::get_cpu().build(prng)))
                     }
                 },
                 Mode::TwoPlayer(p1, _) => match p1 {
                     Player::Human => {
                         log::warn!("P1: Human vs...");
-                        Box::new(HumanPlayer::new(keyboard::KeyCode::W, keyboard::KeyCode::S))
+                        Some(Box::new(HumanPlayer::new(
+                            keyboard::KeyCode::W,
+                            keyboard::KeyCode::S,
+                        )))
                     }
                     Player::Computer => {
                         log::warn!("P1: AI vs...");
-                        Box::new(AiPlayer::random(&Config::default(), prng))
+                        Some(new_ai_player(prng))
+                    }
+                    Player::Bot => {
+                        log::warn!("P1: CPU vs...");
+                        Some(Box::new(cli::get_cpu().build(prng)))
                     }
                 },
                 Mode::TrainAI(_) => {
                     log::warn!("P1: AI training");
-                    Box::new(AiPlayer::random(&Config::default(), prng))
+                    None
                 }
             },
             player_two: match &mode {
@@ -103,95 +113,130 @@ impl GameState {
                     }
                     Player::Computer => {
                         log::warn!("... P2: AI");
-                        Some(Box::new(AiPlayer::random(&Config::default(), prng)))
+                        Some(new_ai_player(prng))
+                    }
+                    Player::Bot => {
+                        log::warn!("... P2: CPU");
+                        Some(Box::new(cli::get_cpu().build(prng)))
                     }
                 },
                 _ => None,
             },
+            recorder: cli::get_record_path().and_then(|path| match Recorder::create(&path) {
+                Ok(recorder) => {
+                    log::warn!("Recording human players to {:?}", path);
+                    Some(recorder)
+                }
+                Err(e) => {
+                    log::error!("Failed to start recording to {:?}: {}", path, e);
+                    None
+                }
+            }),
+            overlay: cli::get_overlay(),
+            dashboard: match &mode {
+                // Just a default population against the wall (see Mode::TrainAI)
+                Mode::TrainAI(_) => Some(Dashboard::spawn(
+                    Config::default().with_decoder(cli::get_decoder()),
+                    Arena::Wall,
+                    prng.next_u64(),
+                )),
+                _ => None,
+            },
             mode,
         })
     }
 
-    /// Check if the ball hit a paddle
-    fn ball_hit_paddle(&mut self) -> Option<Paddle> {
-        if self.ball.vel.x < 0.0 && self.ball.rect.overlaps(&self.paddle_left) {
-            // In 1 player mode we also score a point
-            if let Mode::OnePlayer(_) = self.mode {
-                self.score.p1 += 1;
-            }
+    /// Put the ball and paddles back in the middle, and let the players know a new rally is starting
+    fn serve(&mut self) {
+        self.table.serve(&mut rand::thread_rng());
 
-            Some(Paddle::Left)
-        } else if self.ball.vel.x > 0.0 && self.ball.rect.overlaps(&self.paddle_right) {
-            Some(Paddle::Right)
-        } else {
-            None
+        if let Some(player_one) = &mut self.player_one {
+            player_one.reset();
         }
-    }
-
-    /// Check if the ball hit a wall
-    fn ball_hit_wall(&mut self) -> Option<Wall> {
-        if self.ball.vel.y < 0.0 && self.ball.rect.top() < 0.0 {
-            Some(Wall::Top)
-        } else if self.ball.vel.y > 0.0 && self.ball.rect.bottom() > SCREEN_HEIGHT - BALL_RADIUS {
-            Some(Wall::Bottom)
-        } else if self.ball.rect.left() < 0.0 {
-            Some(Wall::Left)
-        } else if self.ball.rect.right() > SCREEN_WIDTH - BALL_RADIUS {
-            Some(Wall::Right)
-        } else {
-            None
+        if let Some(player_two) = &mut self.player_two {
+            player_two.reset();
         }
     }
 
-    /// Checks for Human and/or AI player input and moves the paddles accordingly
-    fn handle_input(&mut self, ctx: &mut Context) {
+    /// Checks for Human and/or AI player input, then moves the paddles and ball accordingly
+    fn step(&mut self, ctx: &mut Context) -> Option<Event> {
+        let (left_snapshot, right_snapshot) =
+            (self.table.left_snapshot(), self.table.right_snapshot());
+
         // Check player 1 input
-        let p1_move = self
-            .player_one
-            .make_move(ctx, &Snapshot::new(&self.paddle_left, &self.ball));
-        move_paddle(&mut self.paddle_left, p1_move);
+        let p1_move = match &mut self.player_one {
+            Some(player_one) => player_one.make_move(ctx, &left_snapshot),
+            None => 0.0,
+        };
 
         // Check player 2 input, but only if we're playing a 2 player game
-        if let Some(player_two) = &self.player_two {
-            let p2_move = player_two.make_move(ctx, &Snapshot::new(&self.paddle_right, &self.ball));
-            move_paddle(&mut self.paddle_right, p2_move);
+        let p2_move = match &mut self.player_two {
+            Some(player_two) => player_two.make_move(ctx, &right_snapshot),
+            None => 0.0,
+        };
+
+        // Write down what any human players saw and did, so an AI can learn to copy them later
+        if let Some(recorder) = &mut self.recorder {
+            let recorded = match &self.mode {
+                Mode::OnePlayer(Player::Human) => recorder.record(&left_snapshot, p1_move),
+                Mode::TwoPlayer(p1, p2) => {
+                    let mut recorded = Ok(());
+                    if let Player::Human = p1 {
+                        recorded = recorded.and(recorder.record(&left_snapshot, p1_move));
+                    }
+                    if let Player::Human = p2 {
+                        recorded = recorded.and(recorder.record(&right_snapshot, p2_move));
+                    }
+                    recorded
+                }
+                _ => Ok(()),
+            };
+
+            // Give up on recording rather than spamming the log every frame
+            if let Err(e) = recorded {
+                log::error!("Failed to record, so recording has stopped: {}", e);
+                self.recorder = None;
+            }
         }
-    }
 
-    fn reset_paddles(&mut self) {
-        self.paddle_left = Rect::new(
-            X_OFFSET,
-            SCREEN_HEIGHT / 2.0 - PADDLE_HEIGHT / 2.0,
-            PADDLE_WIDTH,
-            PADDLE_HEIGHT,
-        );
-
-        self.paddle_right = match &self.mode {
-            Mode::OnePlayer(_) | Mode::TrainAI(_) => Rect::new(
-                SCREEN_WIDTH - X_OFFSET - PADDLE_WIDTH,
-                0.0,
-                PADDLE_WIDTH,
-                SCREEN_HEIGHT,
-            ),
-            Mode::TwoPlayer(_, _) => Rect::new(
-                SCREEN_WIDTH - X_OFFSET - PADDLE_WIDTH,
-                SCREEN_HEIGHT / 2.0 - PADDLE_HEIGHT / 2.0,
-                PADDLE_WIDTH,
-                PADDLE_HEIGHT,
-            ),
-        };
+        let event = self.table.step(p1_move, p2_move);
+
+        match event {
+            Some(Event::Hit(paddle)) => {
+                log::warn!("{:?} paddle hit!", paddle);
+
+                // In 1 player mode we also score a point
+                if let (Mode::OnePlayer(_), Paddle::Left) = (&self.mode, paddle) {
+                    self.score.p1 += 1;
+                }
+            }
+            Some(Event::Miss(Paddle::Left)) => log::warn!("Left wall hit!"),
+            Some(Event::Miss(Paddle::Right)) => log::warn!("Right wall hit!"),
+            None => {}
+        }
+
+        event
     }
 }
 
-/// Move the specified paddle, but prevent it from moving off the screen
-fn move_paddle(paddle: &mut Rect, amount: f32) {
-    if paddle.top() + amount < 0.0 {
-        paddle.y = 0.0;
-    } else if paddle.bottom() + amount > SCREEN_HEIGHT {
-        paddle.y = SCREEN_HEIGHT - PADDLE_HEIGHT;
-    } else {
-        paddle.y += amount;
+/// Create a new AI player, using the saved DQN agent or brain from the command line if there is one
+fn new_ai_player(prng: &mut dyn RngCore) -> Box<dyn Move> {
+    if let Some(path) = cli::get_dqn_path() {
+        match DqnPlayer::load(&path) {
+            Ok(player) => return Box::new(player),
+            Err(e) => log::error!("Failed to load DQN agent from {:?}: {}", path, e),
+        }
     }
+
+    let config = Config::default().with_decoder(cli::get_decoder());
+
+    Box::new(match cli::get_brain_path() {
+        Some(path) => AiPlayer::load(&config, &path).unwrap_or_else(|e| {
+            log::error!("Failed to load brain from {:?}: {}", path, e);
+            AiPlayer::random(&config, prng)
+        }),
+        None => AiPlayer::random(&config, prng),
+    })
 }
 
 impl event::EventHandler for GameState {
@@ -207,113 +252,53 @@ impl event::EventHandler for GameState {
                     // Only handle key presses if the game isn't paused
                     match self.pause_for {
                         0 => {
-                            // Handle player and/or AI control input
-                            self.handle_input(ctx);
-
-                            // Move the ball based on its velocity
-                            self.ball.rect.translate(self.ball.vel);
-
-                            // Check for ball-on-paddle collisions and reverse horizontal velocity of the ball (and increase it slightly!)
-                            // This will score a point for P1 if it's a 1 player game
-                            if let Some(paddle) = self.ball_hit_paddle() {
-                                log::warn!("{:?} paddle hit!", paddle);
-
-                                // Reverse the direction and slightly increase the horizontal speed
-                                self.ball.vel.x *= -1.01;
-                            }
-
-                            // Check for ball-on-wall collisions act accordingly
-                            match self.ball_hit_wall() {
-                                // If it hit the top or bottom wall, just reverse the vertical velocity of the ball (and increase it slightly!)
-                                Some(Wall::Top) | Some(Wall::Bottom) => {
-                                    log::warn!("Top/Bottom wall hit!");
-                                    self.ball.bounce_off(Wall::Top); // This is a little clumsy, but a Wall::Top or Wall::Bottom will do the same thing
-                                }
-
+                            // Handle player and/or AI control input, and move the ball
+                            match self.step(ctx) {
                                 // If it hit the left wall, either score a point for P2 in a 2 player game, or dock a point from P1 in a 1 player game
-                                Some(Wall::Left) => {
-                                    log::warn!("Left wall hit!");
-
+                                Some(Event::Miss(Paddle::Left)) => {
                                     match self.mode {
                                         Mode::OnePlayer(_) | Mode::TrainAI(_) => {
                                             self.score.p1 -= 1;
-
-                                            // Pause for 1 second's worth of frames before starting over
-                                            self.pause_for = ggez::timer::fps(ctx) as u64;
                                         }
                                         Mode::TwoPlayer(_, _) => {
                                             self.score.p2 += 1;
-
-                                            // Pause for 1 second's worth of frames before starting over
-                                            self.pause_for = ggez::timer::fps(ctx) as u64;
                                         }
                                     }
+
+                                    // Pause for 1 second's worth of frames before starting over
+                                    self.pause_for = ggez::timer::fps(ctx) as u64;
                                 }
 
-                                // If it hit the right wall, score a point for P1 in a 2 player game, otherwise do nothing (in a 1 player game this should never happen)
-                                Some(Wall::Right) => {
-                                    log::warn!("Right wall hit!");
-                                    if let Mode::TwoPlayer(_, _) = self.mode {
-                                        self.score.p1 += 1;
+                                // If it hit the right wall, score a point for P1 (in a 1 player game this should never happen)
+                                Some(Event::Miss(Paddle::Right)) => {
+                                    self.score.p1 += 1;
 
-                                        // Pause for 1 second's worth of frames before starting over
-                                        self.pause_for = ggez::timer::fps(ctx) as u64;
-                                    }
+                                    // Pause for 1 second's worth of frames before starting over
+                                    self.pause_for = ggez::timer::fps(ctx) as u64;
                                 }
 
-                                None => {}
+                                _ => {}
                             }
                         }
                         1 => {
                             self.pause_for -= 1;
 
-                            // Reset the ball
-                            self.ball = Ball::random();
-
-                            // Reset the paddles
-                            self.reset_paddles();
+                            // Reset the ball and paddles
+                            self.serve();
                         }
                         _ => self.pause_for -= 1,
                     }
                 }
             }
-            // Don't bother drawing etc for AI training modes
+            // The table sits idle while the dashboard is shown instead
             Mode::TrainAI(_) => {
-                // Handle AI control input
-                self.handle_input(ctx);
-
-                // Move the ball based on its velocity
-                self.ball.rect.translate(self.ball.vel);
+                // Keep the dashboard up to date with the population training in the background, replaying its champion at the usual speed
+                if let Some(dashboard) = &mut self.dashboard {
+                    dashboard.poll();
 
-                // Check for ball-on-paddle collisions and reverse horizontal velocity of the ball (and increase it slightly!)
-                if let Some(paddle) = self.ball_hit_paddle() {
-                    log::warn!("{:?} paddle hit!", paddle);
-
-                    // Reverse the direction and slightly increase the horizontal speed
-                    self.ball.vel.x *= -1.01;
-                }
-
-                // Check for ball-on-wall collisions act accordingly
-                match self.ball_hit_wall() {
-                    // If it hit the top or bottom wall, just reverse the vertical velocity of the ball (and increase it slightly!)
-                    Some(Wall::Top) | Some(Wall::Bottom) => {
-                        log::warn!("Top/Bottom wall hit!");
-                        self.ball.bounce_off(Wall::Top); // This is a little clumsy, but a Wall::Top or Wall::Bottom will do the same thing
+                    while ggez::timer::check_update_time(ctx, cli::get_target_fps() as u32) {
+                        dashboard.step();
                     }
-
-                    // If the ball hit the left wall, stop the sim and return the score
-                    Some(Wall::Left) => {
-                        log::warn!("Left wall hit!");
-
-                        self.score.p1 -= 1;
-                        // TODO: Stop the simulation
-                    }
-
-                    // In a 1 player game the ball should never hit the right wall, so don't bother handling that
-                    Some(_) => {}
-
-                    // Do nothing if the ball didn't hit a wall
-                    None => {}
                 }
             }
         }
@@ -321,6 +306,22 @@ impl event::EventHandler for GameState {
         Ok(())
     }
 
+    /// Called whenever a key gets pressed (as opposed to held down)
+    fn key_down_event(
+        &mut self,
+        ctx: &mut Context,
+        keycode: keyboard::KeyCode,
+        _keymods: keyboard::KeyMods,
+        _repeat: bool,
+    ) {
+        match keycode {
+            // Keep the usual behaviour of quitting on Escape
+            keyboard::KeyCode::Escape => event::quit(ctx),
+            OVERLAY_KEY => self.overlay = !self.overlay,
+            _ => {}
+        }
+    }
+
     /// Draw the game screen
     fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
         log::info!("Drawing!");
@@ -328,11 +329,18 @@ impl event::EventHandler for GameState {
         // Clear the screen to white
         graphics::clear(ctx, Color::from_rgba(0, 0, 0, 255));
 
+        // AI training shows the dashboard instead of the table
+        if let Some(dashboard) = &self.dashboard {
+            dashboard.draw(ctx)?;
+            graphics::present(ctx).expect("Error presenting graphics!");
+            return Ok(());
+        }
+
         // Create the ball mesh
         let ball_mesh = graphics::Mesh::new_rectangle(
             ctx,
             graphics::DrawMode::fill(),
-            self.ball.rect,
+            self.table.ball.rect,
             Color::from_rgba(255, 255, 255, 255),
         )
         .expect("Error creating ball_mesh!");
@@ -341,7 +349,7 @@ impl event::EventHandler for GameState {
         let paddle_left_mesh = graphics::Mesh::new_rectangle(
             ctx,
             graphics::DrawMode::fill(),
-            self.paddle_left,
+            self.table.paddle_left,
             Color::from_rgba(255, 255, 255, 255),
         )
         .expect("Error creating paddle_left_mesh!");
@@ -350,7 +358,7 @@ impl event::EventHandler for GameState {
         let paddle_right_mesh = graphics::Mesh::new_rectangle(
             ctx,
             graphics::DrawMode::fill(),
-            self.paddle_right,
+            self.table.paddle_right,
             Color::from_rgba(255, 255, 255, 255),
         )
         .expect("Error creating paddle_right_mesh!");
@@ -393,15 +401,41 @@ impl event::EventHandler for GameState {
         let debug_text = graphics::Text::new(format!(
             "[fps: {}] [vel: {:.3},{:.3} | spd: {:.3}] [t: {:.1}]",
             fps,
-            self.ball.vel.x,
-            self.ball.vel.y,
-            (self.ball.vel.x.hypot(self.ball.vel.y)),
+            self.table.ball.vel.x,
+            self.table.ball.vel.y,
+            (self.table.ball.vel.x.hypot(self.table.ball.vel.y)),
             ggez::timer::duration_to_f64(ggez::timer::time_since_start(ctx))
         ));
         let params = graphics::DrawParam::default()
             .dest([20.0, SCREEN_HEIGHT - 20.0 - debug_text.height(ctx) as f32]);
         graphics::draw(ctx, &debug_text, params).expect("Error drawing debug text!");
 
+        // Draw each AI player's network on their half of the table, if the overlay is on
+        if self.overlay {
+            let left_area = Rect::new(
+                X_OFFSET + PADDLE_WIDTH + 60.0,
+                80.0,
+                SCREEN_WIDTH / 2.0 - X_OFFSET - PADDLE_WIDTH - 100.0,
+                SCREEN_HEIGHT - 160.0,
+            );
+            let right_area = Rect::new(
+                SCREEN_WIDTH / 2.0 + 40.0,
+                left_area.y,
+                left_area.w,
+                left_area.h,
+            );
+
+            if let Some(inspection) = self.player_one.as_ref().and_then(|player| player.inspect()) {
+                overlay::draw(ctx, &inspection, left_area, self.table.paddle_left)
+                    .expect("Error drawing player one's overlay!");
+            }
+
+            if let Some(inspection) = self.player_two.as_ref().and_then(|player| player.inspect()) {
+                overlay::draw(ctx, &inspection, right_area, self.table.paddle_right)
+                    .expect("Error drawing player two's overlay!");
+            }
+        }
+
         // Update the screen
         graphics::present(ctx).expect("Error presenting graphics!");
 
@@ -425,11 +459,10 @@ impl Ball {
         }
     }*/
 
-    fn random() -> Ball {
+    /// Serve a new ball from the middle of the screen, in a random direction
+    pub(crate) fn serve(prng: &mut dyn RngCore) -> Ball {
         use rand::prelude::*;
 
-        let mut prng = rand::thread_rng();
-
         let mut random_velocity = || -> f32 {
             let flip = prng.gen::<bool>();
 
@@ -455,11 +488,11 @@ impl Ball {
         }
     }
 
-    fn bounce_off(&mut self, wall: Wall) {
+    pub(crate) fn bounce_off(&mut self, wall: Wall) {
         match wall {
             Wall::Top | Wall::Bottom => {
                 if self.vel.y > 0.0 {
-                    log::warn!(
+                    log::debug!(
                         "pos - bvy: {}, bvy * BALL_ACCELERATION: {}, clamped: {}, r: {}",
                         self.vel.y,
                         self.vel.y * BALL_ACCELERATION,
@@ -469,7 +502,7 @@ impl Ball {
                     self.vel.y =
                         (self.vel.y * -BALL_ACCELERATION).clamp(-BALL_MAX_VEL, -BALL_MIN_VEL);
                 } else {
-                    log::warn!(
+                    log::debug!(
                         "neg - bvy: {}, bvy * BALL_ACCELERATION: {}, clamped: {}, r: {}",
                         self.vel.y,
                         self.vel.y * BALL_ACCELERATION,
@@ -0,0 +1,424 @@
+// Training dashboard
+// Evolves AI players headlessly on a background thread, while the window plots how each generation did and replays the current champion in a small viewport
+
+use ggez::graphics;
+use ggez::graphics::*;
+use ggez::mint::Point2;
+use ggez::{Context, GameResult};
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+
+use genetic_algorithm::statistics::Statistics;
+
+use crate::ai::brain::Brain;
+use crate::ai::player::AiPlayer;
+use crate::ai::trainer::{Arena, Trainer};
+use crate::player::Policy;
+use crate::settings::*;
+use crate::sim::{Event, Table};
+
+/// How many bars the fitness histogram is split into
+const HISTOGRAM_BINS: usize = 20;
+/// A replayed rally gets cut short after this many frames, so a champion that never misses still gets swapped for newer ones
+const REPLAY_FRAMES: usize = 1800;
+
+const TEXT_SIZE: f32 = 18.0;
+
+/// One line on the fitness plot: which statistic it follows, what colour it's drawn in, and its label
+type PlotLine = (fn(&Statistics) -> f32, Color, &'static str);
+
+/// What the background thread reports after every generation
+#[derive(Clone, Debug)]
+pub struct Progress {
+    pub generation: usize,
+    pub statistics: Statistics,
+    /// The fitness of every brain in the generation
+    pub fitnesses: Vec<f32>,
+    pub species: usize,
+    pub champion: Option<Brain>,
+}
+
+/// The champion playing against the wall, one serve at a time
+struct Replay {
+    player: AiPlayer,
+    table: Table,
+    frames: usize,
+}
+
+pub struct Dashboard {
+    config: Config,
+    progress: Receiver<Progress>,
+    stop: Arc<AtomicBool>,
+    history: Vec<Statistics>,
+    latest: Option<Progress>,
+    replay: Option<Replay>,
+    prng: ChaCha8Rng,
+}
+
+impl Dashboard {
+    /// Start training a new population on a background thread (which is only ever watched, so nothing it evolves gets saved)
+    pub fn spawn(config: Config, arena: Arena, seed: u64) -> Self {
+        let (sender, progress) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+
+        // The thread is left to finish on its own, rather than joined (see Drop)
+        {
+            let config = config.clone();
+            let stop = stop.clone();
+
+            std::thread::spawn(move || {
+                let mut prng = ChaCha8Rng::seed_from_u64(seed);
+                let mut trainer = Trainer::new(config, arena, &mut prng);
+
+                while !stop.load(Ordering::SeqCst) {
+                    let statistics = trainer.evolve(&mut prng);
+
+                    let progress = Progress {
+                        generation: trainer.generation(),
+                        statistics,
+                        fitnesses: trainer.fitnesses().to_vec(),
+                        species: trainer.species_count(),
+                        champion: trainer.champion().cloned(),
+                    };
+
+                    // Nobody's watching any more
+                    if sender.send(progress).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+
+        Self {
+            config,
+            progress,
+            stop,
+            history: Vec::new(),
+            latest: None,
+            replay: None,
+            prng: ChaCha8Rng::seed_from_u64(seed),
+        }
+    }
+
+    /// Pick up every generation the background thread has finished since last time
+    pub fn poll(&mut self) {
+        for progress in self.progress.try_iter() {
+            self.history.push(progress.statistics.clone());
+            self.latest = Some(progress);
+        }
+    }
+
+    /// Move the replayed champion on by one frame, starting a new serve with the latest champion whenever the last one ends
+    pub fn step(&mut self) {
+        if self.replay.is_none() {
+            self.replay = self.new_replay();
+        }
+
+        let finished = match &mut self.replay {
+            Some(replay) => {
+                let movement = replay.player.act(&replay.table.left_snapshot());
+                let event = replay.table.step(movement, 0.0);
+                replay.frames += 1;
+
+                matches!(event, Some(Event::Miss(_))) || replay.frames >= REPLAY_FRAMES
+            }
+            None => false,
+        };
+
+        if finished {
+            self.replay = self.new_replay();
+        }
+    }
+
+    /// Serve a fresh ball for the latest champion, if there is one yet
+    fn new_replay(&mut self) -> Option<Replay> {
+        let champion = self.latest.as_ref()?.champion.clone()?;
+
+        Some(Replay {
+            player: AiPlayer::from_brain(&self.config, champion),
+            table: Table::new(true, &mut self.prng),
+            frames: 0,
+        })
+    }
+
+    /// How many generations have been reported so far
+    pub fn generation(&self) -> usize {
+        self.latest
+            .as_ref()
+            .map_or(0, |progress| progress.generation)
+    }
+
+    /// The fitness statistics of every generation reported so far
+    pub fn history(&self) -> &[Statistics] {
+        &self.history
+    }
+
+    /// Draw the fitness plot, the histogram of the latest generation, and the champion's replay
+    pub fn draw(&self, ctx: &mut Context) -> GameResult {
+        let header = match &self.latest {
+            Some(progress) => format!(
+                "Generation {}    best {:.3}    avg {:.3}    min {:.3}    species {}",
+                progress.generation,
+                progress.statistics.max_fitness(),
+                progress.statistics.avg_fitness(),
+                progress.statistics.min_fitness(),
+                progress.species
+            ),
+            None => "Evaluating the first generation...".to_string(),
+        };
+        draw_text(ctx, &header, [20.0, 20.0], TEXT_SIZE)?;
+
+        let plot_area = Rect::new(40.0, 70.0, 460.0, 290.0);
+        let histogram_area = Rect::new(40.0, 420.0, 460.0, 150.0);
+        let replay_area = Rect::new(530.0, 70.0, SCREEN_WIDTH * 0.3, SCREEN_HEIGHT * 0.3);
+
+        self.draw_plot(ctx, plot_area)?;
+        if let Some(progress) = &self.latest {
+            draw_histogram(ctx, &progress.fitnesses, histogram_area)?;
+        }
+        self.draw_replay(ctx, replay_area)
+    }
+
+    /// Plot the best, average and worst fitness of every generation
+    fn draw_plot(&self, ctx: &mut Context, area: Rect) -> GameResult {
+        draw_frame(ctx, area)?;
+        draw_text(
+            ctx,
+            "Fitness per generation",
+            [area.x, area.y - 24.0],
+            TEXT_SIZE,
+        )?;
+
+        let (low, high) =
+            self.history
+                .iter()
+                .fold((f32::MAX, f32::MIN), |(low, high), statistics| {
+                    (
+                        low.min(statistics.min_fitness()),
+                        high.max(statistics.max_fitness()),
+                    )
+                });
+
+        // A line needs at least two points
+        if self.history.len() < 2 {
+            return Ok(());
+        }
+
+        let lines: [PlotLine; 3] = [
+            (Statistics::max_fitness, Color::from_rgb(0, 220, 0), "best"),
+            (Statistics::avg_fitness, Color::from_rgb(240, 220, 0), "avg"),
+            (Statistics::min_fitness, Color::from_rgb(220, 0, 0), "min"),
+        ];
+
+        for (index, (fitness, color, label)) in lines.iter().enumerate() {
+            let values: Vec<f32> = self.history.iter().map(fitness).collect();
+            let points = plot_points(&values, low, high, area);
+
+            let line = graphics::Mesh::new_line(ctx, &points, 2.0, *color)?;
+            graphics::draw(ctx, &line, graphics::DrawParam::default())?;
+
+            let legend = [area.right() - 60.0, area.y + 6.0 + index as f32 * TEXT_SIZE];
+            let mut text = graphics::Text::new(*label);
+            text.set_font(graphics::Font::default(), PxScale::from(TEXT_SIZE));
+            graphics::draw(
+                ctx,
+                &text,
+                graphics::DrawParam::default().dest(legend).color(*color),
+            )?;
+        }
+
+        draw_text(ctx, &format!("{:.2}", high), [area.x - 36.0, area.y], 12.0)?;
+        draw_text(
+            ctx,
+            &format!("{:.2}", low),
+            [area.x - 36.0, area.bottom() - 12.0],
+            12.0,
+        )
+    }
+
+    /// Draw the champion's table shrunk down into the area
+    fn draw_replay(&self, ctx: &mut Context, area: Rect) -> GameResult {
+        draw_frame(ctx, area)?;
+        draw_text(ctx, "Champion replay", [area.x, area.y - 24.0], TEXT_SIZE)?;
+
+        let replay = match &self.replay {
+            Some(replay) => replay,
+            None => return Ok(()),
+        };
+
+        let scale = area.w / SCREEN_WIDTH;
+        let shrink = |rect: Rect| {
+            Rect::new(
+                area.x + rect.x * scale,
+                area.y + rect.y * scale,
+                (rect.w * scale).max(1.0),
+                (rect.h * scale).max(1.0),
+            )
+        };
+
+        for rect in [
+            replay.table.paddle_left,
+            replay.table.paddle_right,
+            replay.table.ball.rect,
+        ] {
+            let mesh = graphics::Mesh::new_rectangle(
+                ctx,
+                graphics::DrawMode::fill(),
+                shrink(rect),
+                Color::from_rgba(255, 255, 255, 255),
+            )?;
+            graphics::draw(ctx, &mesh, graphics::DrawParam::default())?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for Dashboard {
+    /// Tell the background thread to stop once it's finished the generation it's on
+    /// It isn't waited for, as a generation can take a while and the window shouldn't stay open until it's done
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+    }
+}
+
+impl std::fmt::Debug for Dashboard {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(fmt, "Dashboard {{ generation: {:?} }}", self.generation())
+    }
+}
+
+/// Count how many of the fitnesses fall into each of the bins, which evenly split the range from the lowest to the highest
+pub fn histogram(fitnesses: &[f32], bins: usize) -> Vec<usize> {
+    let mut counts = vec![0; bins];
+
+    let low = fitnesses.iter().copied().fold(f32::MAX, f32::min);
+    let high = fitnesses.iter().copied().fold(f32::MIN, f32::max);
+    let width = (high - low) / bins as f32;
+
+    for fitness in fitnesses {
+        // Everyone lands in the first bin if they're all equally fit
+        let bin = if width > 0.0 {
+            (((fitness - low) / width) as usize).min(bins - 1)
+        } else {
+            0
+        };
+
+        counts[bin] += 1;
+    }
+
+    counts
+}
+
+/// Spread the values evenly across the area from left to right, with low at the bottom and high at the top
+fn plot_points(values: &[f32], low: f32, high: f32, area: Rect) -> Vec<Point2<f32>> {
+    let span = (high - low).max(f32::EPSILON);
+    let steps = (values.len() - 1).max(1) as f32;
+
+    values
+        .iter()
+        .enumerate()
+        .map(|(index, value)| Point2 {
+            x: area.x + area.w * index as f32 / steps,
+            y: area.bottom() - area.h * (value - low) / span,
+        })
+        .collect()
+}
+
+fn draw_histogram(ctx: &mut Context, fitnesses: &[f32], area: Rect) -> GameResult {
+    draw_frame(ctx, area)?;
+    draw_text(
+        ctx,
+        "Fitness of the latest generation",
+        [area.x, area.y - 24.0],
+        TEXT_SIZE,
+    )?;
+
+    if fitnesses.is_empty() {
+        return Ok(());
+    }
+
+    let counts = histogram(fitnesses, HISTOGRAM_BINS);
+    let tallest = counts.iter().copied().max().unwrap_or(1).max(1) as f32;
+    let width = area.w / HISTOGRAM_BINS as f32;
+
+    for (bin, count) in counts.iter().enumerate().filter(|(_, count)| **count > 0) {
+        let height = area.h * *count as f32 / tallest;
+        let bar = graphics::Mesh::new_rectangle(
+            ctx,
+            graphics::DrawMode::fill(),
+            Rect::new(
+                area.x + bin as f32 * width + 1.0,
+                area.bottom() - height,
+                width - 2.0,
+                height,
+            ),
+            Color::from_rgb(80, 140, 255),
+        )?;
+        graphics::draw(ctx, &bar, graphics::DrawParam::default())?;
+    }
+
+    Ok(())
+}
+
+/// Outline the area
+fn draw_frame(ctx: &mut Context, area: Rect) -> GameResult {
+    let frame = graphics::Mesh::new_rectangle(
+        ctx,
+        graphics::DrawMode::stroke(1.0),
+        area,
+        Color::from_rgba(255, 255, 255, 120),
+    )?;
+
+    graphics::draw(ctx, &frame, graphics::DrawParam::default())
+}
+
+fn draw_text(ctx: &mut Context, text: &str, dest: [f32; 2], size: f32) -> GameResult {
+    let mut text = graphics::Text::new(text);
+    text.set_font(graphics::Font::default(), PxScale::from(size));
+
+    graphics::draw(ctx, &text, graphics::DrawParam::default().dest(dest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::trainer::small_config;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn fitness_histogram() {
+        assert_eq!(histogram(&[0.0, 0.1, 0.5, 0.9, 1.0], 2), vec![2, 3]);
+        assert_eq!(histogram(&[0.3, 0.3, 0.3], 4), vec![3, 0, 0, 0]);
+
+        let points = plot_points(&[1.0, 2.0, 3.0], 1.0, 3.0, Rect::new(0.0, 0.0, 100.0, 50.0));
+        approx::assert_relative_eq!(points[0].x, 0.0);
+        approx::assert_relative_eq!(points[0].y, 50.0);
+        approx::assert_relative_eq!(points[2].x, 100.0);
+        approx::assert_relative_eq!(points[2].y, 0.0);
+    }
+
+    #[test]
+    fn background_training() {
+        let mut dashboard = Dashboard::spawn(small_config(), Arena::Wall, 42);
+
+        // Keep polling until a couple of generations have come in from the background thread
+        let started = Instant::now();
+        while dashboard.generation() < 2 {
+            assert!(started.elapsed() < Duration::from_secs(60));
+            std::thread::sleep(Duration::from_millis(10));
+            dashboard.poll();
+        }
+
+        assert_eq!(dashboard.history().len(), dashboard.generation());
+
+        // Once there's a champion, it should be replayed
+        dashboard.step();
+        assert!(dashboard.replay.is_some());
+        assert_eq!(dashboard.replay.as_ref().unwrap().frames, 1);
+    }
+}
@@ -0,0 +1,532 @@
+// Reinforcement learning environments
+// A Gym-style wrapper around the headless Table: an agent controls the left paddle one frame at a time, and gets told what it can see and how well it's doing after every step
+
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+use crate::ai::eye::Eye;
+use crate::ai::normalizer::Normalizer;
+use crate::bots::BotKind;
+use crate::core::Paddle;
+use crate::player::{Policy, Snapshot};
+use crate::settings::*;
+use crate::sim::{Event, Table};
+
+/// What the agent can see: the same photoreceptors as an AI player's Eye, scaled to roughly -1..=1 using SENSOR_RANGES
+pub type Observation = Vec<f32>;
+
+/// Work out what the paddle in the snapshot can see (e.g. so an agent trained in a PongEnv can play in a normal game)
+pub fn observe(snapshot: &Snapshot) -> Observation {
+    Observer::default().observe(snapshot).to_vec()
+}
+
+/// Turns snapshots into observations, reusing the same Eye and output buffer every time so a PongEnv (or a DqnPlayer) doesn't allocate them again every step
+#[derive(Clone, Debug)]
+pub(crate) struct Observer {
+    eye: Eye,
+    normalizer: Normalizer,
+    observation: Observation,
+}
+
+impl Default for Observer {
+    fn default() -> Self {
+        let eye = Eye::new(&Config::default());
+
+        Self {
+            observation: vec![0.0; eye.photoreceptors.len()],
+            normalizer: Normalizer::fixed(&SENSOR_RANGES),
+            eye,
+        }
+    }
+}
+
+impl Observer {
+    /// Work out what the paddle in the snapshot can see, which stays valid until the next observation
+    pub(crate) fn observe(&mut self, snapshot: &Snapshot) -> &[f32] {
+        self.eye.look(snapshot.paddle, &snapshot.ball);
+        self.normalizer
+            .normalize_into(&self.eye.photoreceptors, &mut self.observation);
+
+        &self.observation
+    }
+}
+
+/// The moves an agent can make each step
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    Up,
+    Stay,
+    Down,
+}
+
+impl Action {
+    /// Every action, in the same order as their indexes
+    pub const ALL: [Action; 3] = [Action::Up, Action::Stay, Action::Down];
+
+    /// The action with the specified index (e.g. the output of a network with one output per action)
+    pub fn from_index(idx: usize) -> Action {
+        Self::ALL[idx]
+    }
+
+    pub fn index(&self) -> usize {
+        match self {
+            Self::Up => 0,
+            Self::Stay => 1,
+            Self::Down => 2,
+        }
+    }
+
+    /// How far this action moves the paddle
+    pub fn movement(&self) -> f32 {
+        match self {
+            Self::Up => -PADDLE_SPEED,
+            Self::Stay => 0.0,
+            Self::Down => PADDLE_SPEED,
+        }
+    }
+}
+
+/// Decides how much reward the agent gets after each step
+pub trait Reward {
+    /// The reward for a step, given whatever happened during it (if anything) and what the agent can see afterwards
+    fn reward(&mut self, event: Option<Event>, snapshot: &Snapshot) -> f32;
+}
+
+// Any closure will do for one-off reward shaping
+impl<F> Reward for F
+where
+    F: FnMut(Option<Event>, &Snapshot) -> f32,
+{
+    fn reward(&mut self, event: Option<Event>, snapshot: &Snapshot) -> f32 {
+        self(event, snapshot)
+    }
+}
+
+/// Fixed rewards for each kind of event
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rewards {
+    /// For returning the ball
+    pub hit: f32,
+    /// For getting the ball past the opponent (which never happens against the wall)
+    pub point_won: f32,
+    /// For letting the ball get past
+    pub point_lost: f32,
+    /// For every step where nothing happens
+    pub step: f32,
+}
+
+impl Default for Rewards {
+    fn default() -> Self {
+        Self {
+            hit: 0.1,
+            point_won: 1.0,
+            point_lost: -1.0,
+            step: 0.0,
+        }
+    }
+}
+
+impl Reward for Rewards {
+    fn reward(&mut self, event: Option<Event>, _snapshot: &Snapshot) -> f32 {
+        match event {
+            Some(Event::Hit(Paddle::Left)) => self.hit,
+            Some(Event::Miss(Paddle::Right)) => self.point_won,
+            Some(Event::Miss(Paddle::Left)) => self.point_lost,
+            Some(Event::Hit(Paddle::Right)) | None => self.step,
+        }
+    }
+}
+
+/// Who the agent plays against
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Opponent {
+    /// A full height wall that never misses
+    Wall,
+    /// One of the scripted bots
+    Bot(BotKind),
+}
+
+impl std::fmt::Display for Opponent {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Wall => write!(f, "wall"),
+            Self::Bot(bot) => write!(f, "{}", bot),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct EnvSettings {
+    pub opponent: Opponent,
+    /// How many serves make up an episode
+    pub serves: usize,
+    /// A rally that goes on for longer than this is abandoned without anyone scoring
+    pub max_frames: usize,
+}
+
+impl Default for EnvSettings {
+    fn default() -> Self {
+        Self {
+            opponent: Opponent::Wall,
+            serves: 1,
+            max_frames: MAX_RALLY_FRAMES,
+        }
+    }
+}
+
+/// Anything else worth knowing about a step, which the agent shouldn't learn from directly
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Info {
+    /// Whatever happened to the ball during the step
+    pub event: Option<Event>,
+    /// How many steps into the episode this was
+    pub frame: usize,
+    /// Whether the rally was abandoned for going on too long, rather than ending with a point
+    pub truncated: bool,
+    /// When a VecEnv starts a new episode straight after one finishes, this is the last observation of the old one
+    pub final_observation: Option<Observation>,
+}
+
+/// A game of pong for a reinforcement learning agent, which plays the left paddle
+pub struct PongEnv {
+    settings: EnvSettings,
+    table: Table,
+    opponent: Option<Box<dyn Policy>>,
+    reward: Box<dyn Reward>,
+    observer: Observer,
+    prng: ChaCha8Rng,
+    serves: usize,
+    rally: usize,
+    frame: usize,
+    done: bool,
+}
+
+impl PongEnv {
+    /// Create a new PongEnv with the default Rewards, ready to go as if it had been reset with a seed of 0
+    pub fn new(settings: EnvSettings) -> Self {
+        let mut prng = ChaCha8Rng::seed_from_u64(0);
+
+        let mut env = Self {
+            table: Table::new(settings.opponent == Opponent::Wall, &mut prng),
+            settings,
+            opponent: None,
+            reward: Box::new(Rewards::default()),
+            observer: Observer::default(),
+            prng,
+            serves: 0,
+            rally: 0,
+            frame: 0,
+            done: false,
+        };
+        env.reset(0);
+
+        env
+    }
+
+    /// Use the specified reward hook instead of the default Rewards
+    pub fn with_reward(self, reward: impl Reward + 'static) -> Self {
+        Self {
+            reward: Box::new(reward),
+            ..self
+        }
+    }
+
+    /// Start a new episode, with everything random about it (the serves, and any bot's mistakes) decided by the seed
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        self.prng = ChaCha8Rng::seed_from_u64(seed);
+
+        self.table = Table::new(self.settings.opponent == Opponent::Wall, &mut self.prng);
+        self.opponent = match self.settings.opponent {
+            Opponent::Wall => None,
+            Opponent::Bot(kind) => Some(kind.build(&mut self.prng)),
+        };
+
+        self.serves = 0;
+        self.rally = 0;
+        self.frame = 0;
+        self.done = false;
+
+        self.observer.observe(&self.table.left_snapshot()).to_vec()
+    }
+
+    /// Move the left paddle for one frame, and return what it can see afterwards, the reward for the step, whether the episode is over, and anything else worth knowing
+    pub fn step(&mut self, action: Action) -> (Observation, f32, bool, Info) {
+        assert!(
+            !self.done,
+            "The episode is over - call reset() before stepping again"
+        );
+
+        let right_move = match &mut self.opponent {
+            Some(opponent) => opponent.act(&self.table.right_snapshot()),
+            None => 0.0,
+        };
+
+        let event = self.table.step(action.movement(), right_move);
+        self.rally += 1;
+        self.frame += 1;
+
+        let reward = self.reward.reward(event, &self.table.left_snapshot());
+
+        let point = matches!(event, Some(Event::Miss(_)));
+        let truncated = !point && self.rally >= self.settings.max_frames;
+
+        if point || truncated {
+            self.serves += 1;
+            self.rally = 0;
+
+            if self.serves >= self.settings.serves {
+                self.done = true;
+            } else {
+                self.table.serve(&mut self.prng);
+
+                if let Some(opponent) = &mut self.opponent {
+                    opponent.reset();
+                }
+            }
+        }
+
+        let info = Info {
+            event,
+            frame: self.frame,
+            truncated,
+            final_observation: None,
+        };
+
+        (
+            self.observer.observe(&self.table.left_snapshot()).to_vec(),
+            reward,
+            self.done,
+            info,
+        )
+    }
+}
+
+/// A batch of PongEnvs stepped together, for agents that learn from lots of games at once
+/// Whenever one of the games finishes it starts a new episode straight away, so every step always has an observation for every game
+pub struct VecEnv {
+    envs: Vec<PongEnv>,
+    next_seed: u64,
+}
+
+impl VecEnv {
+    /// Create the specified number of PongEnvs with the same settings, each reset with its index as the seed (as reset(0) would) so they don't all start with the same serve
+    pub fn new(count: usize, settings: &EnvSettings) -> Self {
+        Self::from_fn(count, |idx| {
+            let mut env = PongEnv::new(settings.clone());
+            env.reset(idx as u64);
+            env
+        })
+    }
+
+    /// Create the specified number of PongEnvs, each built by the provided function from its index (e.g. to give them each their own reward hook)
+    pub fn from_fn(count: usize, build: impl FnMut(usize) -> PongEnv) -> Self {
+        assert!(count > 0);
+
+        Self {
+            envs: (0..count).map(build).collect(),
+            next_seed: count as u64,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.envs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.envs.is_empty()
+    }
+
+    /// Start a new episode in every game, each with its own seed counting up from the one specified
+    /// The episodes that get started automatically later on carry on counting up from there
+    pub fn reset(&mut self, seed: u64) -> Vec<Observation> {
+        let observations = self
+            .envs
+            .iter_mut()
+            .enumerate()
+            .map(|(idx, env)| env.reset(seed.wrapping_add(idx as u64)))
+            .collect();
+
+        self.next_seed = seed.wrapping_add(self.envs.len() as u64);
+
+        observations
+    }
+
+    /// Step every game with its own action
+    pub fn step(&mut self, actions: &[Action]) -> Vec<(Observation, f32, bool, Info)> {
+        assert_eq!(actions.len(), self.envs.len());
+
+        let mut results = Vec::with_capacity(self.envs.len());
+
+        for (env, action) in self.envs.iter_mut().zip(actions) {
+            let (mut observation, reward, done, mut info) = env.step(*action);
+
+            if done {
+                info.final_observation = Some(observation);
+                observation = env.reset(self.next_seed);
+                self.next_seed = self.next_seed.wrapping_add(1);
+            }
+
+            results.push((observation, reward, done, info));
+        }
+
+        results
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    /// Run an episode to the end, choosing each action from the latest observation
+    fn play_episode(
+        env: &mut PongEnv,
+        seed: u64,
+        mut policy: impl FnMut(&Observation) -> Action,
+    ) -> (f32, Info) {
+        let mut observation = env.reset(seed);
+        let mut total = 0.0;
+
+        loop {
+            let (next, reward, done, info) = env.step(policy(&observation));
+            total += reward;
+            observation = next;
+
+            if done {
+                return (total, info);
+            }
+        }
+    }
+
+    /// Chase the ball up and down, using the normalized paddle Y and ball Y
+    fn chase(observation: &Observation) -> Action {
+        if observation[2] < observation[0] - 0.05 {
+            Action::Up
+        } else if observation[2] > observation[0] + 0.05 {
+            Action::Down
+        } else {
+            Action::Stay
+        }
+    }
+
+    #[test]
+    fn seeded_reset() {
+        let mut env = PongEnv::new(EnvSettings::default());
+
+        let first = env.reset(7);
+        let (stepped, _, _, _) = env.step(Action::Up);
+        let again = env.reset(7);
+        let (stepped_again, _, _, _) = env.step(Action::Up);
+
+        assert_eq!(first, again);
+        assert_eq!(stepped, stepped_again);
+        assert_eq!(first.len(), SENSOR_RANGES.len());
+        assert!(first.iter().all(|input| (-1.0..=1.0).contains(input)));
+
+        // A different seed should serve the ball a different way
+        assert_ne!(env.reset(8), first);
+    }
+
+    #[test]
+    fn losing_an_episode() {
+        let mut env = PongEnv::new(EnvSettings {
+            serves: 3,
+            ..EnvSettings::default()
+        });
+
+        // A paddle that never moves should lose every serve, though it might get lucky with a few hits along the way
+        let (total, info) = play_episode(&mut env, 42, |_| Action::Stay);
+
+        assert!(total < -2.0);
+        assert_eq!(info.event, Some(Event::Miss(Paddle::Left)));
+        assert!(!info.truncated);
+    }
+
+    #[test]
+    fn truncated_rally() {
+        let mut env = PongEnv::new(EnvSettings {
+            max_frames: 2000,
+            ..EnvSettings::default()
+        });
+
+        // Chasing the ball is enough to never miss against the wall, so the rally has to be abandoned
+        let (total, info) = play_episode(&mut env, 42, chase);
+
+        assert!(total > 0.0);
+        assert!(info.truncated);
+        assert_eq!(info.frame, 2000);
+    }
+
+    #[test]
+    fn reward_hooks() {
+        let hits = Rc::new(Cell::new(0));
+        let counted = hits.clone();
+
+        let mut env = PongEnv::new(EnvSettings {
+            opponent: Opponent::Bot(BotKind::Tracker),
+            serves: 2,
+            max_frames: 2000,
+        })
+        .with_reward(move |event: Option<Event>, _: &Snapshot| {
+            if let Some(Event::Hit(Paddle::Right)) = event {
+                counted.set(counted.get() + 1);
+            }
+
+            0.0
+        });
+
+        // The tracker never misses either, so both paddles should keep returning the ball until the rallies get abandoned
+        let (total, info) = play_episode(&mut env, 42, chase);
+
+        approx::assert_relative_eq!(total, 0.0);
+        assert!(info.truncated);
+        assert!(hits.get() > 2);
+    }
+
+    #[test]
+    fn vectorized_environments() {
+        let mut envs = VecEnv::new(
+            4,
+            &EnvSettings {
+                max_frames: 50,
+                ..EnvSettings::default()
+            },
+        );
+
+        // A new batch starts off just as if it had been reset with a seed of 0
+        let mut fresh = VecEnv::new(
+            4,
+            &EnvSettings {
+                max_frames: 50,
+                ..EnvSettings::default()
+            },
+        );
+        let observations = envs.reset(0);
+        assert_eq!(observations.len(), 4);
+        assert_eq!(
+            fresh.step(&[Action::Stay; 4]),
+            envs.step(&[Action::Stay; 4])
+        );
+        envs.reset(0);
+
+        // Each game gets its own seed, so no two should start the same
+        assert_ne!(observations[0], observations[1]);
+
+        // Every game gets abandoned on the 50th step and should start over straight away
+        for step in 1..=50 {
+            let results = envs.step(&[Action::Up, Action::Stay, Action::Down, Action::Stay]);
+            assert_eq!(results.len(), 4);
+
+            for (_, _, done, info) in &results {
+                assert_eq!(*done, step == 50 && info.truncated);
+                assert_eq!(info.final_observation.is_some(), *done);
+            }
+        }
+
+        // ...and the next step should be the first of a new episode
+        let results = envs.step(&[Action::Stay; 4]);
+        assert!(results.iter().all(|(_, _, _, info)| info.frame == 1));
+    }
+}
@@ -1,5 +1,10 @@
 pub mod ai;
+pub mod bots;
 pub mod cli;
 pub mod core;
+pub mod dashboard;
+pub mod env;
+pub mod overlay;
 pub mod player;
 pub mod settings;
+pub mod sim;
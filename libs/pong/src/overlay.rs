@@ -0,0 +1,125 @@
+// Debug overlay
+// Draws an AI player's network over the game: edges are green for positive weights and red for negative ones, nodes are coloured the same way by their value, and the decoded move is shown next to the paddle
+
+use ggez::graphics;
+use ggez::graphics::*;
+use ggez::mint::Point2;
+use ggez::{Context, GameResult};
+
+use crate::ai::inspect::Inspection;
+
+const NODE_RADIUS: f32 = 7.0;
+const TEXT_SIZE: f32 = 12.0;
+
+/// Green for positive values and red for negative ones, fading out as they get closer to zero
+fn value_color(value: f32, max_alpha: f32) -> Color {
+    let alpha = value.abs().min(1.0) * max_alpha;
+
+    if value >= 0.0 {
+        Color::new(0.2, 0.9, 0.2, alpha)
+    } else {
+        Color::new(0.9, 0.2, 0.2, alpha)
+    }
+}
+
+/// Draw the player's network inside the area, and how far it decided to move next to its paddle
+pub fn draw(ctx: &mut Context, inspection: &Inspection, area: Rect, paddle: Rect) -> GameResult {
+    let diagram = &inspection.diagram;
+    let positions = diagram.positions(area);
+
+    // Edges go underneath the nodes, and get thicker the stronger they are
+    for edge in &diagram.edges {
+        let from = positions[edge.from.0][edge.from.1];
+        let to = positions[edge.to.0][edge.to.1];
+
+        let line = graphics::Mesh::new_line(
+            ctx,
+            &[from, to],
+            1.0 + edge.weight.abs().min(2.0),
+            value_color(edge.weight, 0.6),
+        )?;
+        graphics::draw(ctx, &line, graphics::DrawParam::default())?;
+    }
+
+    for (values, points) in diagram.columns.iter().zip(&positions) {
+        for (value, point) in values.iter().zip(points) {
+            let fill = graphics::Mesh::new_circle(
+                ctx,
+                graphics::DrawMode::fill(),
+                *point,
+                NODE_RADIUS,
+                0.5,
+                Color::from_rgba(0, 0, 0, 255),
+            )?;
+            graphics::draw(ctx, &fill, graphics::DrawParam::default())?;
+
+            let node = graphics::Mesh::new_circle(
+                ctx,
+                graphics::DrawMode::fill(),
+                *point,
+                NODE_RADIUS,
+                0.5,
+                value_color(*value, 1.0),
+            )?;
+            graphics::draw(ctx, &node, graphics::DrawParam::default())?;
+
+            let outline = graphics::Mesh::new_circle(
+                ctx,
+                graphics::DrawMode::stroke(1.0),
+                *point,
+                NODE_RADIUS,
+                0.5,
+                Color::from_rgba(255, 255, 255, 160),
+            )?;
+            graphics::draw(ctx, &outline, graphics::DrawParam::default())?;
+
+            let mut text = graphics::Text::new(format!("{:.2}", value));
+            text.set_font(graphics::Font::default(), PxScale::from(TEXT_SIZE));
+            let params = graphics::DrawParam::default()
+                .dest([point.x + NODE_RADIUS + 2.0, point.y - TEXT_SIZE / 2.0]);
+            graphics::draw(ctx, &text, params)?;
+        }
+    }
+
+    draw_move(ctx, inspection.movement, paddle)
+}
+
+/// Show the decoded move beside the paddle, with a line pointing the way it's moving
+fn draw_move(ctx: &mut Context, movement: f32, paddle: Rect) -> GameResult {
+    let center = paddle.center();
+
+    // Keep the text on the table side of the paddle
+    let on_left = center.x < crate::settings::SCREEN_WIDTH / 2.0;
+    let x = if on_left {
+        paddle.right() + 8.0
+    } else {
+        paddle.left() - 8.0
+    };
+
+    // A zero length line can't be drawn, so a paddle that's staying put only gets the text
+    if movement.abs() > f32::EPSILON {
+        let line = graphics::Mesh::new_line(
+            ctx,
+            &[
+                Point2 { x, y: center.y },
+                Point2 {
+                    x,
+                    y: center.y + movement * 4.0,
+                },
+            ],
+            3.0,
+            Color::from_rgba(255, 255, 0, 255),
+        )?;
+        graphics::draw(ctx, &line, graphics::DrawParam::default())?;
+    }
+
+    let mut text = graphics::Text::new(format!("{:+.2}", movement));
+    text.set_font(graphics::Font::default(), PxScale::from(TEXT_SIZE));
+    let width = text.width(ctx) as f32;
+    let params = graphics::DrawParam::default().dest([
+        if on_left { x + 6.0 } else { x - 6.0 - width },
+        center.y - TEXT_SIZE / 2.0,
+    ]);
+
+    graphics::draw(ctx, &text, params)
+}
@@ -1,11 +1,14 @@
 use ggez::input::keyboard;
 
+use crate::ai::inspect::Inspection;
 use crate::settings::*;
 
 #[derive(Debug)]
 pub enum Mode {
     OnePlayer(Player),
     TwoPlayer(Player, Player),
+    /// Evolve a default population (Config::default() with the --decoder option) against the wall in the background, and show how it's doing on the dashboard
+    /// None of the train command's options apply here and no champion gets saved - use the train command for a run worth keeping
     TrainAI(Player),
 }
 
@@ -13,8 +16,10 @@ pub enum Mode {
 pub enum Player {
     Human,
     Computer,
+    Bot,
 }
 
+#[derive(Clone, Debug)]
 pub struct Snapshot {
     pub(crate) paddle: ggez::graphics::Rect,
     pub(crate) ball: crate::core::Ball,
@@ -30,8 +35,74 @@ impl Snapshot {
 }
 
 pub trait Move {
-    fn make_move(&self, ctx: &mut ggez::Context, _snapshot: &Snapshot) -> f32;
+    fn make_move(&mut self, ctx: &mut ggez::Context, _snapshot: &Snapshot) -> f32;
     fn name(&self) -> &'static str;
+
+    /// See Policy::reset (keyboard players have nothing to forget)
+    fn reset(&mut self) {}
+
+    /// See Policy::inspect (keyboard players have nothing to show)
+    fn inspect(&self) -> Option<Inspection> {
+        None
+    }
+}
+
+/// A player that decides how to move purely from what it can see, without needing any keyboard input
+/// Unlike Move this doesn't need a ggez::Context, so it can also be used in headless games (e.g. for training)
+pub trait Policy {
+    fn act(&mut self, snapshot: &Snapshot) -> f32;
+    fn name(&self) -> &'static str;
+
+    /// Called whenever a new ball gets served, so players with a memory can forget the last rally
+    fn reset(&mut self) {}
+
+    /// What the player is thinking about, for the debug overlay (only AI players have anything to show)
+    fn inspect(&self) -> Option<Inspection> {
+        None
+    }
+}
+
+impl<P> Policy for Box<P>
+where
+    P: Policy + ?Sized,
+{
+    fn act(&mut self, snapshot: &Snapshot) -> f32 {
+        (**self).act(snapshot)
+    }
+
+    fn name(&self) -> &'static str {
+        (**self).name()
+    }
+
+    fn reset(&mut self) {
+        (**self).reset()
+    }
+
+    fn inspect(&self) -> Option<Inspection> {
+        (**self).inspect()
+    }
+}
+
+// Anything that can play without a keyboard can obviously play in a normal game too
+impl<P> Move for P
+where
+    P: Policy,
+{
+    fn make_move(&mut self, _ctx: &mut ggez::Context, snapshot: &Snapshot) -> f32 {
+        self.act(snapshot)
+    }
+
+    fn name(&self) -> &'static str {
+        Policy::name(self)
+    }
+
+    fn reset(&mut self) {
+        Policy::reset(self)
+    }
+
+    fn inspect(&self) -> Option<Inspection> {
+        Policy::inspect(self)
+    }
 }
 
 impl std::fmt::Debug for dyn Move {
@@ -63,7 +134,7 @@ impl HumanPlayer {
 }
 
 impl Move for HumanPlayer {
-    fn make_move(&self, ctx: &mut ggez::Context, _snapshot: &Snapshot) -> f32 {
+    fn make_move(&mut self, ctx: &mut ggez::Context, _snapshot: &Snapshot) -> f32 {
         // Check for key presses and move Player 1 paddle accordingly
         if keyboard::is_key_pressed(ctx, self.controls.up) {
             -PADDLE_SPEED
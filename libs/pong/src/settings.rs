@@ -14,7 +14,29 @@ pub const BALL_MAX_VEL: f32 = 3.0;
 pub const BALL_MAX_BOUNCE_ANGLE: f32 = 75.0; // Max angle in radians at which a ball can bounce off a paddle
 pub const BALL_ACCELERATION: f32 = 1.0;
 
+pub const MAX_RALLY_FRAMES: usize = 10_000; // Headless games give up on a rally that goes on for longer than this
+
+pub const OVERLAY_KEY: ggez::input::keyboard::KeyCode = ggez::input::keyboard::KeyCode::Tab; // Shows/hides what the AI players are thinking
+
+// Fixed ranges used to normalize each of the eye photoreceptors: Paddle Y, Ball X, Ball Y, Ball VX, Ball VY
+// The horizontal velocity grows a little with every paddle hit, so allow it some headroom over BALL_MAX_VEL
+pub const SENSOR_RANGES: [(f32, f32); 5] = [
+    (0.0, SCREEN_HEIGHT),
+    (0.0, SCREEN_WIDTH),
+    (0.0, SCREEN_HEIGHT),
+    (-2.0 * BALL_MAX_VEL, 2.0 * BALL_MAX_VEL),
+    (-BALL_MAX_VEL, BALL_MAX_VEL),
+];
+
+use crate::ai::brain::Topology;
+use crate::ai::decoder::Decoder;
+use crate::ai::fitness::{FitnessWeights, Ranking};
+use crate::ai::normalizer::Normalization;
+use crate::ai::trainer::Algorithm;
 use crate::player::*;
+use genetic_algorithm::islands::Migration;
+use genetic_algorithm::speciation::SpeciationSettings;
+use neural_network::topology::Initializer;
 pub const PLAYER_VS_PLAYER: Mode = Mode::TwoPlayer(Player::Human, Player::Human);
 pub const PLAYER_VS_AI: Mode = Mode::TwoPlayer(Player::Human, Player::Computer);
 pub const PLAYER_VS_SELF: Mode = Mode::OnePlayer(Player::Human);
@@ -22,13 +44,31 @@ pub const AI_VS_PLAYER: Mode = Mode::TwoPlayer(Player::Computer, Player::Human);
 pub const AI_VS_AI: Mode = Mode::TwoPlayer(Player::Computer, Player::Computer);
 pub const AI_VS_SELF: Mode = Mode::OnePlayer(Player::Computer);
 pub const TRAIN_AI: Mode = Mode::TrainAI(Player::Computer);
+pub const PLAYER_VS_BOT: Mode = Mode::TwoPlayer(Player::Human, Player::Bot);
+pub const AI_VS_BOT: Mode = Mode::TwoPlayer(Player::Computer, Player::Bot);
 
 #[derive(Clone, Debug)]
 pub struct Config {
     pub eye_photoreceptors: usize,
     pub brain_neurons: usize,
+    pub topology: Topology,
     pub outputs: usize,
     pub generation_length: usize,
+    pub population_size: usize,
+    pub mutation_chance: f32,
+    pub mutation_coeff: f32,
+    pub elitism: usize,
+    pub algorithm: Algorithm,
+    pub sigma: f32,
+    pub learning_rate: f32,
+    pub fitness: FitnessWeights,
+    pub ranking: Ranking,
+    pub speciation: Option<SpeciationSettings>,
+    pub islands: usize,
+    pub migration: Migration,
+    pub normalization: Normalization,
+    pub decoder: Decoder,
+    pub initializer: Initializer,
 }
 
 impl Default for Config {
@@ -36,8 +76,35 @@ impl Default for Config {
         Self {
             eye_photoreceptors: 5, // Paddle Y, Ball X, Ball Y, Ball VX, Ball VY
             brain_neurons: 15,
-            outputs: 1,            // Whether the move the paddle up or down
-            generation_length: 10, // How many serves to play for
+            topology: Topology::Fixed, // Don't grow the network with NEAT
+            outputs: 1,                // Whether the move the paddle up or down
+            generation_length: 10,     // How many serves to play for
+            population_size: 50,       // How many AI players to train at once
+            mutation_chance: 0.01,     // How likely each weight is to be mutated
+            mutation_coeff: 0.3,       // How much a mutated weight can change by
+            elitism: 0, // How many of the best AIs go through to the next generation unchanged
+            algorithm: Algorithm::Genetic, // How to breed each generation of a fixed topology
+            sigma: 0.1, // How far the evolution strategies search around the best weights to start with
+            learning_rate: 0.05, // How far NES moves its weights each generation
+            fitness: FitnessWeights::default(), // What counts towards an AI's fitness
+            ranking: Ranking::Weighted, // How to pick the parents of the next generation
+            speciation: None, // Don't split the fixed-topology population into species
+            islands: 1, // How many sub-populations to evolve separately
+            migration: Migration::default(), // How often the islands swap their best AIs
+            normalization: Normalization::Fixed, // Scale the eye inputs using SENSOR_RANGES
+            decoder: Decoder::Sign, // How to turn the outputs into a paddle move
+            initializer: Initializer::default(), // How random brains pick their weights (uniformly from -1.0..=1.0)
+        }
+    }
+}
+
+impl Config {
+    /// Use the specified decoder, along with however many outputs it needs
+    pub fn with_decoder(self, decoder: Decoder) -> Self {
+        Self {
+            outputs: decoder.outputs(),
+            decoder,
+            ..self
         }
     }
 }
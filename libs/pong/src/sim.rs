@@ -0,0 +1,378 @@
+use ggez::graphics::Rect;
+use rand::RngCore;
+
+use crate::core::{Ball, Paddle, Wall};
+use crate::player::{Policy, Snapshot};
+use crate::settings::*;
+
+/// Something that happened during a single step of the simulation
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Event {
+    /// The ball bounced off the specified paddle
+    Hit(Paddle),
+    /// The ball got past the specified paddle and hit the wall behind it
+    Miss(Paddle),
+}
+
+/// The paddles and ball, along with the physics that moves them about
+/// This knows nothing about ggez Contexts, scores or pauses, so it can run headless just as well as inside a GameState
+#[derive(Clone, Debug)]
+pub struct Table {
+    pub(crate) paddle_left: Rect,
+    pub(crate) paddle_right: Rect,
+    pub(crate) ball: Ball,
+    walled: bool,
+}
+
+impl Table {
+    /// Create a new Table with a freshly served ball
+    /// If walled is true, the right paddle is replaced by a full height wall (as used for 1 player games and AI training)
+    pub fn new(walled: bool, prng: &mut dyn RngCore) -> Table {
+        Table {
+            paddle_left: Self::left_paddle(),
+            paddle_right: Self::right_paddle(walled),
+            ball: Ball::serve(prng),
+            walled,
+        }
+    }
+
+    fn left_paddle() -> Rect {
+        Rect::new(
+            X_OFFSET,
+            SCREEN_HEIGHT / 2.0 - PADDLE_HEIGHT / 2.0,
+            PADDLE_WIDTH,
+            PADDLE_HEIGHT,
+        )
+    }
+
+    fn right_paddle(walled: bool) -> Rect {
+        if walled {
+            Rect::new(
+                SCREEN_WIDTH - X_OFFSET - PADDLE_WIDTH,
+                0.0,
+                PADDLE_WIDTH,
+                SCREEN_HEIGHT,
+            )
+        } else {
+            Rect::new(
+                SCREEN_WIDTH - X_OFFSET - PADDLE_WIDTH,
+                SCREEN_HEIGHT / 2.0 - PADDLE_HEIGHT / 2.0,
+                PADDLE_WIDTH,
+                PADDLE_HEIGHT,
+            )
+        }
+    }
+
+    /// Whether the right paddle is actually a full height wall
+    pub fn walled(&self) -> bool {
+        self.walled
+    }
+
+    /// Put the paddles back in the middle and serve a new ball
+    pub fn serve(&mut self, prng: &mut dyn RngCore) {
+        self.paddle_left = Self::left_paddle();
+        self.paddle_right = Self::right_paddle(self.walled);
+        self.ball = Ball::serve(prng);
+    }
+
+    /// What the left paddle's player can see
+    pub fn left_snapshot(&self) -> Snapshot {
+        Snapshot::new(&self.paddle_left, &self.ball)
+    }
+
+    /// What the right paddle's player can see
+    pub fn right_snapshot(&self) -> Snapshot {
+        Snapshot::new(&self.paddle_right, &self.ball)
+    }
+
+    /// Check if the ball hit a paddle
+    fn ball_hit_paddle(&self) -> Option<Paddle> {
+        if self.ball.vel.x < 0.0 && self.ball.rect.overlaps(&self.paddle_left) {
+            Some(Paddle::Left)
+        } else if self.ball.vel.x > 0.0 && self.ball.rect.overlaps(&self.paddle_right) {
+            Some(Paddle::Right)
+        } else {
+            None
+        }
+    }
+
+    /// Check if the ball hit a wall
+    fn ball_hit_wall(&self) -> Option<Wall> {
+        if self.ball.vel.y < 0.0 && self.ball.rect.top() < 0.0 {
+            Some(Wall::Top)
+        } else if self.ball.vel.y > 0.0 && self.ball.rect.bottom() > SCREEN_HEIGHT - BALL_RADIUS {
+            Some(Wall::Bottom)
+        } else if self.ball.rect.left() < 0.0 {
+            Some(Wall::Left)
+        } else if self.ball.rect.right() > SCREEN_WIDTH - BALL_RADIUS {
+            Some(Wall::Right)
+        } else {
+            None
+        }
+    }
+
+    /// Move the paddles by the specified amounts, then move the ball and bounce it off anything it hits
+    /// The right paddle's move is ignored if it's actually a wall
+    pub fn step(&mut self, left_move: f32, right_move: f32) -> Option<Event> {
+        move_paddle(&mut self.paddle_left, left_move);
+        if !self.walled {
+            move_paddle(&mut self.paddle_right, right_move);
+        }
+
+        // Move the ball based on its velocity
+        self.ball.rect.translate(self.ball.vel);
+
+        // Check for ball-on-paddle collisions and reverse horizontal velocity of the ball (and increase it slightly!)
+        let hit = self.ball_hit_paddle();
+        if hit.is_some() {
+            self.ball.vel.x *= -1.01;
+        }
+
+        // Check for ball-on-wall collisions act accordingly
+        match self.ball_hit_wall() {
+            // If it hit the top or bottom wall, just reverse the vertical velocity of the ball (and increase it slightly!)
+            Some(Wall::Top) | Some(Wall::Bottom) => {
+                self.ball.bounce_off(Wall::Top); // This is a little clumsy, but a Wall::Top or Wall::Bottom will do the same thing
+                hit.map(Event::Hit)
+            }
+
+            // If it hit the wall behind a paddle, that paddle missed
+            Some(Wall::Left) => Some(Event::Miss(Paddle::Left)),
+            Some(Wall::Right) => Some(Event::Miss(Paddle::Right)),
+
+            None => hit.map(Event::Hit),
+        }
+    }
+}
+
+/// Move the specified paddle, but prevent it from moving off the screen
+fn move_paddle(paddle: &mut Rect, amount: f32) {
+    if paddle.top() + amount < 0.0 {
+        paddle.y = 0.0;
+    } else if paddle.bottom() + amount > SCREEN_HEIGHT {
+        paddle.y = SCREEN_HEIGHT - PADDLE_HEIGHT;
+    } else {
+        paddle.y += amount;
+    }
+}
+
+/// How one player got on during a headless game, used to work out how fit an AI is
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Performance {
+    /// How many serves were played
+    pub serves: usize,
+    /// How many times the player returned the ball
+    pub returns: usize,
+    /// How many times the ball got past the player
+    pub misses: usize,
+    /// How many times the ball got past the opponent
+    pub points: usize,
+    /// How many frames were played in total, across every rally
+    pub frames: usize,
+    /// The total vertical distance between the paddle and the ball at each miss
+    pub miss_distance: f32,
+    /// The total distance the paddle moved
+    pub movement: f32,
+}
+
+impl Performance {
+    /// Add the performance from another game to this one
+    pub fn merge(&mut self, other: &Performance) {
+        self.serves += other.serves;
+        self.returns += other.returns;
+        self.misses += other.misses;
+        self.points += other.points;
+        self.frames += other.frames;
+        self.miss_distance += other.miss_distance;
+        self.movement += other.movement;
+    }
+}
+
+/// The outcome of a headless two player match
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MatchResult {
+    pub points_left: usize,
+    pub points_right: usize,
+    pub hits_left: usize,
+    pub hits_right: usize,
+    pub left: Performance,
+    pub right: Performance,
+}
+
+/// Play a headless two player match of the specified number of serves
+/// A rally that goes on for longer than MAX_RALLY_FRAMES is abandoned without anyone scoring
+pub fn play_match(
+    left: &mut dyn Policy,
+    right: &mut dyn Policy,
+    serves: usize,
+    prng: &mut dyn RngCore,
+) -> MatchResult {
+    play(Table::new(false, prng), left, Some(right), serves, prng)
+}
+
+/// Play a headless one player game against the wall for the specified number of serves
+/// The player is on the left, so every hit counts towards hits_left and every miss towards points_right
+pub fn play_wall(player: &mut dyn Policy, serves: usize, prng: &mut dyn RngCore) -> MatchResult {
+    play(Table::new(true, prng), player, None, serves, prng)
+}
+
+/// Play out the specified number of serves on a table (the right player can only be missing if the table is walled)
+fn play(
+    mut table: Table,
+    left: &mut dyn Policy,
+    mut right: Option<&mut dyn Policy>,
+    serves: usize,
+    prng: &mut dyn RngCore,
+) -> MatchResult {
+    assert!(right.is_some() || table.walled());
+
+    let mut result = MatchResult::default();
+
+    for serve in 0..serves {
+        if serve > 0 {
+            table.serve(prng);
+        }
+
+        // Every serve starts from scratch, even for players that remember things
+        left.reset();
+        if let Some(right) = right.as_mut() {
+            right.reset();
+        }
+
+        result.left.serves += 1;
+        result.right.serves += 1;
+
+        for _ in 0..MAX_RALLY_FRAMES {
+            let left_move = left.act(&table.left_snapshot());
+            let right_move = match right.as_mut() {
+                Some(right) => right.act(&table.right_snapshot()),
+                None => 0.0,
+            };
+
+            // Keep track of how far the paddles actually moved, rather than how far they wanted to
+            let (left_y, right_y) = (table.paddle_left.y, table.paddle_right.y);
+            let event = table.step(left_move, right_move);
+
+            result.left.frames += 1;
+            result.right.frames += 1;
+            result.left.movement += (table.paddle_left.y - left_y).abs();
+            result.right.movement += (table.paddle_right.y - right_y).abs();
+
+            match event {
+                Some(Event::Hit(Paddle::Left)) => {
+                    result.hits_left += 1;
+                    result.left.returns += 1;
+                }
+                Some(Event::Hit(Paddle::Right)) => {
+                    result.hits_right += 1;
+                    result.right.returns += 1;
+                }
+                Some(Event::Miss(Paddle::Left)) => {
+                    result.points_right += 1;
+                    result.left.misses += 1;
+                    result.left.miss_distance += miss_distance(table.paddle_left, &table.ball);
+                    result.right.points += 1;
+                    break;
+                }
+                Some(Event::Miss(Paddle::Right)) => {
+                    result.points_left += 1;
+                    result.right.misses += 1;
+                    result.right.miss_distance += miss_distance(table.paddle_right, &table.ball);
+                    result.left.points += 1;
+                    break;
+                }
+                None => {}
+            }
+        }
+    }
+
+    result
+}
+
+/// How far the paddle was from the ball when it missed it
+fn miss_distance(paddle: Rect, ball: &Ball) -> f32 {
+    (paddle.center().y - ball.rect.center().y).abs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bots::TrackerBot;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    /// A player that never moves its paddle
+    struct Statue;
+
+    impl Policy for Statue {
+        fn act(&mut self, _snapshot: &Snapshot) -> f32 {
+            0.0
+        }
+
+        fn name(&self) -> &'static str {
+            "Statue"
+        }
+    }
+
+    #[test]
+    fn walled_table() {
+        let mut prng = ChaCha8Rng::from_seed(Default::default());
+        let mut table = Table::new(true, &mut prng);
+
+        // The wall should never move, and the ball should always bounce off it
+        let mut events = Vec::new();
+        for _ in 0..MAX_RALLY_FRAMES {
+            match table.step(0.0, -PADDLE_SPEED) {
+                Some(Event::Miss(paddle)) => {
+                    events.push(Event::Miss(paddle));
+                    break;
+                }
+                Some(event) => events.push(event),
+                None => {}
+            }
+        }
+
+        approx::assert_relative_eq!(table.paddle_right.y, 0.0);
+        assert_eq!(events.last(), Some(&Event::Miss(Paddle::Left)));
+        assert!(!events.contains(&Event::Miss(Paddle::Right)));
+    }
+
+    #[test]
+    fn headless_match() {
+        let mut prng = ChaCha8Rng::from_seed(Default::default());
+
+        // A tracker should never miss, so it should win every point against a paddle that never moves
+        let result = play_match(
+            &mut TrackerBot::new(PADDLE_SPEED),
+            &mut Statue,
+            10,
+            &mut prng,
+        );
+
+        assert_eq!(result.points_left, 10);
+        assert_eq!(result.points_right, 0);
+        assert!(result.hits_left > 0);
+    }
+
+    #[test]
+    fn headless_wall_game() {
+        let mut prng = ChaCha8Rng::from_seed(Default::default());
+
+        // The wall never misses, so a paddle that never moves should eventually lose every serve
+        let result = play_wall(&mut Statue, 10, &mut prng);
+
+        assert_eq!(result.points_right, 10);
+        assert_eq!(result.points_left, 0);
+
+        // Every return from the paddle comes back off the wall
+        assert!(result.hits_right >= result.hits_left);
+
+        // The paddle never moved, and the wall can't
+        assert_eq!(result.left.serves, 10);
+        assert_eq!(result.left.misses, 10);
+        assert_eq!(result.right.points, 10);
+        approx::assert_relative_eq!(result.left.movement, 0.0);
+        approx::assert_relative_eq!(result.right.movement, 0.0);
+        assert!(result.left.miss_distance > 0.0);
+    }
+}